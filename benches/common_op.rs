@@ -3,17 +3,62 @@ use std::alloc::{GlobalAlloc, Layout, System};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use dlmalloc::GlobalDlmalloc;
 use linked_list_allocator::LockedHeap;
-use simpile::{linked::Allocator, space::Mmap, Space};
+use simpile::{
+    bump,
+    linked::{Allocator, Config, Placement},
+    small,
+    space::{DynSpace, Mmap},
+    Space,
+};
 
 #[cfg(not(feature = "std"))]
 compile_error!("feature \"std\" is required to compile");
 
 fn run(c: &mut Criterion) {
-    fn new_alloc() -> Allocator<Mmap> {
+    fn new_alloc(placement: Placement) -> Allocator<Mmap> {
         let mut space = Mmap::new();
         space.set_size(128 << 10);
-        Allocator::new(space)
+        Allocator::with_config(
+            space,
+            Config {
+                placement,
+                ..Default::default()
+            },
+        )
     }
+    // the same heap behind an erased space, for the cost of the virtual calls
+    fn new_dyn() -> Allocator<DynSpace<'static>> {
+        let mut space = Mmap::new();
+        space.set_size(128 << 10);
+        Allocator::new(DynSpace::new(space))
+    }
+    fn new_caching(config: Config) -> Allocator<Mmap> {
+        let mut space = Mmap::new();
+        space.set_size(128 << 10);
+        Allocator::with_config(space, config)
+    }
+    fn new_fast() -> Allocator<Mmap> {
+        new_caching(Config {
+            fast_bins: true,
+            ..Default::default()
+        })
+    }
+    fn new_deferred() -> Allocator<Mmap> {
+        new_caching(Config {
+            deferred_coalescing: true,
+            ..Default::default()
+        })
+    }
+    fn new_bump() -> bump::Allocator<Mmap> {
+        let mut space = Mmap::new();
+        space.set_size(128 << 10);
+        bump::Allocator::new(space)
+    }
+    // both placements of the linked allocator are compared
+    const PLACEMENTS: [(&str, Placement); 2] = [
+        ("linked", Placement::BestFit),
+        ("linked first fit", Placement::FirstFit),
+    ];
 
     fn one_alloc(alloc: &impl GlobalAlloc) {
         let layout = Layout::from_size_align(1, 1).unwrap();
@@ -30,8 +75,22 @@ fn run(c: &mut Criterion) {
         drop(space)
     });
     group.bench_function("dl", |b| b.iter(|| one_alloc(&GlobalDlmalloc)));
-    group.bench_function("linked", |b| {
-        let alloc = new_alloc();
+    for (name, placement) in PLACEMENTS {
+        group.bench_function(name, |b| {
+            let alloc = new_alloc(placement);
+            b.iter(|| one_alloc(&alloc))
+        });
+    }
+    group.bench_function("linked dyn", |b| {
+        let alloc = new_dyn();
+        b.iter(|| one_alloc(&alloc))
+    });
+    group.bench_function("linked fast bins", |b| {
+        let alloc = new_fast();
+        b.iter(|| one_alloc(&alloc))
+    });
+    group.bench_function("bump", |b| {
+        let alloc = new_bump();
         b.iter(|| one_alloc(&alloc))
     });
     group.finish();
@@ -65,15 +124,17 @@ fn run(c: &mut Criterion) {
         b.iter(|| one_alloc(&System));
         unsafe { GlobalDlmalloc.dealloc(occupied_higher, layout) }
     });
-    group.bench_function("linked", |b| {
-        let alloc = new_alloc();
-        let layout = Layout::from_size_align(1, 1).unwrap();
-        let ptr = unsafe { alloc.alloc(layout) };
-        let occupied_higher = unsafe { alloc.alloc(layout) };
-        unsafe { alloc.dealloc(ptr, layout) }
-        b.iter(|| one_alloc(&alloc));
-        unsafe { alloc.dealloc(occupied_higher, layout) }
-    });
+    for (name, placement) in PLACEMENTS {
+        group.bench_function(name, |b| {
+            let alloc = new_alloc(placement);
+            let layout = Layout::from_size_align(1, 1).unwrap();
+            let ptr = unsafe { alloc.alloc(layout) };
+            let occupied_higher = unsafe { alloc.alloc(layout) };
+            unsafe { alloc.dealloc(ptr, layout) }
+            b.iter(|| one_alloc(&alloc));
+            unsafe { alloc.dealloc(occupied_higher, layout) }
+        });
+    }
     group.finish();
 
     fn hundred_alloc(alloc: &impl GlobalAlloc, lifo: bool) {
@@ -98,8 +159,22 @@ fn run(c: &mut Criterion) {
         drop(space)
     });
     group.bench_function("dl", |b| b.iter(|| hundred_alloc(&GlobalDlmalloc, false)));
-    group.bench_function("linked", |b| {
-        let alloc = new_alloc();
+    for (name, placement) in PLACEMENTS {
+        group.bench_function(name, |b| {
+            let alloc = new_alloc(placement);
+            b.iter(|| hundred_alloc(&alloc, false))
+        });
+    }
+    group.bench_function("linked dyn", |b| {
+        let alloc = new_dyn();
+        b.iter(|| hundred_alloc(&alloc, false))
+    });
+    group.bench_function("linked fast bins", |b| {
+        let alloc = new_fast();
+        b.iter(|| hundred_alloc(&alloc, false))
+    });
+    group.bench_function("small", |b| {
+        let alloc: small::Allocator<_> = small::Allocator::new(new_alloc(Placement::BestFit));
         b.iter(|| hundred_alloc(&alloc, false))
     });
     group.finish();
@@ -111,11 +186,26 @@ fn run(c: &mut Criterion) {
         space.set_size(128 << 10);
         let alloc = unsafe { LockedHeap::new(space.as_mut_ptr(), space.len()) };
         b.iter(|| hundred_alloc(&alloc, true));
-        drop(alloc)
+        drop(space)
     });
     group.bench_function("dl", |b| b.iter(|| hundred_alloc(&GlobalDlmalloc, true)));
-    group.bench_function("linked", |b| {
-        let alloc = new_alloc();
+    for (name, placement) in PLACEMENTS {
+        group.bench_function(name, |b| {
+            let alloc = new_alloc(placement);
+            b.iter(|| hundred_alloc(&alloc, true))
+        });
+    }
+    group.bench_function("linked deferred", |b| {
+        let alloc = new_deferred();
+        b.iter(|| hundred_alloc(&alloc, true))
+    });
+    group.bench_function("small", |b| {
+        let alloc: small::Allocator<_> = small::Allocator::new(new_alloc(Placement::BestFit));
+        b.iter(|| hundred_alloc(&alloc, true))
+    });
+    // freeing in reverse gives everything back, otherwise it runs out
+    group.bench_function("bump", |b| {
+        let alloc = new_bump();
         b.iter(|| hundred_alloc(&alloc, true))
     });
     group.finish();
@@ -125,9 +215,9 @@ fn run(c: &mut Criterion) {
         let mut size = 1;
         let mut layout = Layout::from_size_align(size, 1).unwrap();
         let mut ptr = black_box(unsafe { alloc.alloc(layout) });
-        for i in 0..100 {
+        for slot in &mut interleaved {
             if interleave {
-                interleaved[i] = unsafe { alloc.alloc(Layout::from_size_align(1, 1).unwrap()) };
+                *slot = unsafe { alloc.alloc(Layout::from_size_align(1, 1).unwrap()) };
             }
             size += 8;
             ptr = black_box(unsafe { alloc.realloc(ptr, layout, size) });
@@ -150,10 +240,12 @@ fn run(c: &mut Criterion) {
         drop(space)
     });
     group.bench_function("dl", |b| b.iter(|| hundred_realloc(&GlobalDlmalloc, false)));
-    group.bench_function("linked", |b| {
-        let alloc = new_alloc();
-        b.iter(|| hundred_realloc(&alloc, false))
-    });
+    for (name, placement) in PLACEMENTS {
+        group.bench_function(name, |b| {
+            let alloc = new_alloc(placement);
+            b.iter(|| hundred_realloc(&alloc, false))
+        });
+    }
     group.finish();
 
     let mut group = c.benchmark_group("100 (+8) Realloc Copied");
@@ -166,8 +258,14 @@ fn run(c: &mut Criterion) {
         drop(space)
     });
     group.bench_function("dl", |b| b.iter(|| hundred_realloc(&GlobalDlmalloc, true)));
-    group.bench_function("linked", |b| {
-        let alloc = new_alloc();
+    for (name, placement) in PLACEMENTS {
+        group.bench_function(name, |b| {
+            let alloc = new_alloc(placement);
+            b.iter(|| hundred_realloc(&alloc, true))
+        });
+    }
+    group.bench_function("linked dyn", |b| {
+        let alloc = new_dyn();
         b.iter(|| hundred_realloc(&alloc, true))
     });
     group.finish();
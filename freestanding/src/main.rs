@@ -0,0 +1,51 @@
+#![cfg_attr(target_os = "none", no_std, no_main)]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, vec::Vec};
+
+use simpile::{linked::Allocator, space::Static};
+
+// the heap is laid out in the static by the first allocation
+#[global_allocator]
+static GLOBAL: Allocator<Static<{ 64 << 10 }>> = Allocator::lazy(Static::new());
+
+// collections on the heap, then everything freed and the heap intact. a hosted runtime may hold
+// allocations of its own, so only the ones made here are counted
+fn smoke() -> bool {
+    let count = GLOBAL.stats().allocation_count;
+    let mut vec = Vec::new();
+    for index in 0..1000u32 {
+        vec.push(index)
+    }
+    let boxes = Vec::from_iter((0..100).map(|index| Box::new([index as u8; 40])));
+    let sum = vec.iter().map(|index| *index as u64).sum::<u64>();
+    let ok = sum == 999 * 1000 / 2 && boxes.iter().enumerate().all(|(i, b)| b[39] == i as u8);
+    drop((vec, boxes));
+    ok && GLOBAL.check().is_ok() && GLOBAL.stats().allocation_count == count
+}
+
+#[cfg(target_os = "none")]
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    // nothing to report to without an operating system, a debugger can tell where it stops
+    if !smoke() {
+        panic!()
+    }
+    loop {
+        core::hint::spin_loop()
+    }
+}
+
+#[cfg(target_os = "none")]
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {
+        core::hint::spin_loop()
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {
+    assert!(smoke());
+}
@@ -0,0 +1,9 @@
+use std::process::Command;
+
+#[test]
+fn smoke() {
+    let status = Command::new(env!("CARGO_BIN_EXE_simpile-freestanding"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
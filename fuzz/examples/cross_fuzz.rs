@@ -0,0 +1,26 @@
+use std::alloc::System;
+
+use afl::fuzz;
+use simpile::fuzz::Harness;
+use simpile::{linked::Allocator, space::Mmap, tlsf, Space};
+
+fn linked() -> Allocator<Mmap> {
+    let mut space = Mmap::new();
+    space.set_size(4 << 10);
+    Allocator::new(space)
+}
+
+fn tlsf() -> tlsf::Allocator<Mmap> {
+    let mut space = Mmap::new();
+    space.set_size(64 << 10);
+    tlsf::Allocator::new(space)
+}
+
+fn main() {
+    // same input against every allocator, so a crash in one of them can be compared with the others
+    let harness = Harness::new()
+        .target("system", || System)
+        .checked_target("linked", linked)
+        .target("tlsf", tlsf);
+    fuzz!(|bytes: &[u8]| harness.run_bytes(bytes));
+}
@@ -0,0 +1,104 @@
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    cell::Cell,
+    fmt,
+};
+
+use afl::fuzz;
+use simpile::fuzz::{Check, Method};
+use simpile::{
+    linked::{Allocator, Bins, CheckError, Config, Placement, ScrubCursor, TopPolicy},
+    space::Mmap,
+    Space,
+};
+
+const KEY: [u8; 32] = [0x5a; 32];
+
+// seals and unseals the heap at every check between the methods, and scrubs a few chunks of it,
+// so the sealing and the poison filling go through every heap layout the methods reach
+struct Hardened {
+    alloc: Allocator<Mmap>,
+    seal: bool,
+    scrub_budget: Option<usize>,
+    cursor: Cell<ScrubCursor>,
+}
+
+unsafe impl GlobalAlloc for Hardened {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { self.alloc.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.alloc.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe { self.alloc.realloc(ptr, layout, new_size) }
+    }
+}
+
+impl Check for Hardened {
+    type Error = CheckError;
+
+    fn check(&self) -> Result<(), Self::Error> {
+        if self.seal {
+            assert!(self.alloc.seal(&KEY));
+            assert_eq!(self.alloc.check(), Err(CheckError::Marker));
+            assert!(self.alloc.unseal(&KEY));
+        }
+        if let Some(budget) = self.scrub_budget {
+            let mut cursor = self.cursor.get();
+            self.alloc.scrub(&mut cursor, budget)?;
+            self.cursor.set(cursor);
+        }
+        self.alloc.check()
+    }
+
+    fn dump(&self, out: &mut String) -> fmt::Result {
+        self.alloc.dump(out)
+    }
+
+    fn zero_size(&self) -> bool {
+        self.alloc.zero_size()
+    }
+}
+
+fn main() {
+    fuzz!(|bytes: &[u8]| {
+        // the first byte picks the top policy, the placement, the bins, whether the heap is sealed
+        // and how much of it is scrubbed at every check. the growable space gets the growing and
+        // shrinking paths checked as well
+        let Some((&settings, bytes)) = bytes.split_first() else {
+            return;
+        };
+        let config = Config {
+            top_policy: if settings & 1 == 0 {
+                TopPolicy::KeepWilderness
+            } else {
+                TopPolicy::UseWilderness
+            },
+            placement: if settings & 2 == 0 {
+                Placement::BestFit
+            } else {
+                Placement::FirstFit
+            },
+            bins: if settings & 4 == 0 {
+                Bins::Sorted
+            } else {
+                Bins::Compact
+            },
+            ..Default::default()
+        };
+        let mut space = Mmap::new();
+        space.set_size(4 << 10);
+        let alloc = Hardened {
+            alloc: Allocator::with_config(space, config),
+            seal: settings & 8 != 0,
+            // none, a chunk or two, a few of them, or the whole heap at a time
+            scrub_budget: [None, Some(64), Some(1 << 10), Some(usize::MAX)]
+                [(settings >> 4) as usize & 3],
+            cursor: Default::default(),
+        };
+        Method::run_fuzz_checked(&Method::from_bytes_profiled(bytes), alloc);
+    });
+}
@@ -0,0 +1,23 @@
+use afl::fuzz;
+use simpile::fuzz::Method;
+use simpile::{linked::Allocator, space::Mmap, Space, Switchable};
+
+fn main() {
+    fuzz!(|bytes: &[u8]| {
+        // the first 8 bytes tell whether the heap is enabled after each method, repeating every 64
+        // methods, so pointers from both sides get mixed and freed or reallocated by either
+        let (toggles, bytes) = bytes.split_at(usize::min(bytes.len(), 8));
+        let mut pattern = [0; 8];
+        pattern[..toggles.len()].copy_from_slice(toggles);
+        let pattern = u64::from_le_bytes(pattern);
+
+        let mut space = Mmap::new();
+        space.set_size(4 << 10);
+        let alloc = Switchable::new(Allocator::new(space));
+        Method::run_fuzz_with(
+            Method::from_bytes_profiled(bytes).into_iter(),
+            &alloc,
+            |index| alloc.set_enable((pattern >> (index % 64)) & 1 == 0),
+        );
+    });
+}
@@ -1,31 +1,24 @@
 use std::fs::{create_dir, write};
 
-use simpile::fuzz::Method;
+use simpile::fuzz::{Method, Profile};
 
 fn main() -> std::io::Result<()> {
     create_dir("in")?;
-    write("in/0", Method::to_bytes(&[]))?;
-    write(
-        "in/1",
-        Method::to_bytes(&[
+    let seeds = [
+        &[][..],
+        &[
             Method::Alloc { size: 1, align: 1 },
             Method::Dealloc { index: 0 },
-        ]),
-    )?;
-    write(
-        "in/2",
-        Method::to_bytes(&[
+        ],
+        &[
             Method::Alloc { size: 1, align: 1 },
             Method::Realloc {
                 index: 0,
                 new_size: 2,
             },
             Method::Dealloc { index: 0 },
-        ]),
-    )?;
-    write(
-        "in/3",
-        Method::to_bytes(&[
+        ],
+        &[
             Method::Alloc { size: 1, align: 64 },
             Method::Alloc { size: 1, align: 1 },
             Method::Realloc {
@@ -33,7 +26,21 @@ fn main() -> std::io::Result<()> {
                 new_size: 2,
             },
             Method::Dealloc { index: 0 },
-        ]),
-    )?;
+        ],
+    ];
+    for profile in 0..Profile::ALL.len() {
+        for (index, seed) in seeds.iter().enumerate() {
+            write(
+                format!("in/{profile}-{index}"),
+                Method::to_bytes_profiled(seed, profile),
+            )?;
+        }
+    }
+    for (index, methods) in Method::padded_sequences().iter().enumerate() {
+        write(
+            format!("in/padded-{index}"),
+            Method::to_bytes_profiled(methods, 0),
+        )?;
+    }
     Ok(())
 }
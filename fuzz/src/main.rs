@@ -1,25 +1,15 @@
 use afl::fuzz;
 use simpile::fuzz::Method;
-use simpile::{linked::Allocator, space::Fixed};
-
-#[repr(align(4096))]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-struct Page(u8);
-
-union Data {
-    page: Page,
-    buf: [u8; 4096],
-}
+use simpile::{linked::Allocator, space::SystemBuffer};
 
 fn main() {
     fuzz!(|bytes: &[u8]| {
         // keep alignment same to make sure the failure is reproducible
-        let mut data = Data {
-            page: Default::default(),
-        };
-        Method::run_fuzz(
-            Method::from_bytes(bytes).into_iter(),
-            Allocator::new(Fixed::from(unsafe { &mut data.buf[..] })),
-        );
+        let methods = Method::from_bytes_profiled(bytes);
+        let alloc = Allocator::new(SystemBuffer::new(4096, 4096));
+        #[cfg(feature = "check")]
+        Method::run_fuzz_checked(&methods, alloc);
+        #[cfg(not(feature = "check"))]
+        Method::run_fuzz(methods.into_iter(), alloc);
     });
 }
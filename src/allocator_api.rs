@@ -0,0 +1,212 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ptr::{copy_nonoverlapping, without_provenance_mut, NonNull},
+};
+
+use crate::{linked::Allocator, Space};
+
+// so containers take a heap directly, e.g. `Vec::new_in(&heap)`, with either the nightly trait of
+// core or the same one of allocator-api2 on stable. the returned slices cover the usable size of
+// the chunk, and containers may pass any size between the requested one and that back, which the
+// heap accepts as the chunk is found by the pointer
+macro_rules! impl_allocator {
+    ($allocator:path, $error:path) => {
+        unsafe impl<S, R> $allocator for Allocator<S, R>
+        where
+            S: Space,
+            R: lock_api::RawMutex,
+        {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, $error> {
+                self.allocate_slice(layout).ok_or($error)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                unsafe { self.deallocate_slice(ptr, layout) }
+            }
+
+            unsafe fn grow(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, $error> {
+                unsafe { self.resize(ptr, old_layout, new_layout) }.ok_or($error)
+            }
+
+            unsafe fn grow_zeroed(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, $error> {
+                let new_ptr = unsafe { self.resize(ptr, old_layout, new_layout) }.ok_or($error)?;
+                let bytes = new_ptr.cast::<u8>().as_ptr();
+                unsafe {
+                    bytes
+                        .add(old_layout.size())
+                        .write_bytes(0, new_ptr.len() - old_layout.size())
+                };
+                Ok(new_ptr)
+            }
+
+            unsafe fn shrink(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, $error> {
+                unsafe { self.resize(ptr, old_layout, new_layout) }.ok_or($error)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "allocator-api")]
+impl_allocator!(core::alloc::Allocator, core::alloc::AllocError);
+#[cfg(feature = "allocator-api2")]
+impl_allocator!(
+    allocator_api2::alloc::Allocator,
+    allocator_api2::alloc::AllocError
+);
+
+impl<S, R> Allocator<S, R>
+where
+    S: Space,
+    R: lock_api::RawMutex,
+{
+    fn allocate_slice(&self, layout: Layout) -> Option<NonNull<[u8]>> {
+        if layout.size() == 0 {
+            let dangling = NonNull::new(without_provenance_mut(layout.align())).unwrap();
+            return Some(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+        let ptr = NonNull::new(unsafe { self.alloc(layout) })?;
+        let size = unsafe { self.usable_size(ptr.as_ptr(), layout) };
+        self.recount_requested(layout.size(), size);
+        Some(NonNull::slice_from_raw_parts(ptr, size))
+    }
+
+    unsafe fn deallocate_slice(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            let size = unsafe { self.usable_size(ptr.as_ptr(), layout) };
+            self.recount_requested(size, layout.size());
+            unsafe { self.dealloc(ptr.as_ptr(), layout) }
+        }
+    }
+
+    // through `realloc_layout`, which keeps the chunk in place if it can, also when the alignment
+    // changes
+    unsafe fn resize(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Option<NonNull<[u8]>> {
+        if old_layout.size() == 0 || new_layout.size() == 0 {
+            let new_ptr = self.allocate_slice(new_layout)?;
+            unsafe {
+                copy_nonoverlapping(
+                    ptr.as_ptr(),
+                    new_ptr.cast::<u8>().as_ptr(),
+                    usize::min(old_layout.size(), new_layout.size()),
+                );
+                self.deallocate_slice(ptr, old_layout)
+            }
+            return Some(new_ptr);
+        }
+        let old_size = unsafe { self.usable_size(ptr.as_ptr(), old_layout) };
+        self.recount_requested(old_size, old_layout.size());
+        let Some(new_ptr) =
+            NonNull::new(unsafe { self.realloc_layout(ptr.as_ptr(), old_layout, new_layout) })
+        else {
+            self.recount_requested(old_layout.size(), old_size);
+            return None;
+        };
+        let size = unsafe { self.usable_size(new_ptr.as_ptr(), new_layout) };
+        self.recount_requested(new_layout.size(), size);
+        Some(NonNull::slice_from_raw_parts(new_ptr, size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use crate::space::Fixed;
+
+    use super::*;
+
+    #[cfg(feature = "allocator-api")]
+    #[test]
+    fn containers() {
+        use core::alloc::Allocator as _;
+        use std::{boxed::Box, vec::Vec};
+
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let mut numbers = Vec::new_in(&alloc);
+        numbers.extend(0..100u32);
+        let boxed = Box::new_in([1u64; 4], &alloc);
+        assert_eq!(numbers.iter().sum::<u32>(), 4950);
+        assert_eq!(alloc.stats().allocation_count, 2);
+        numbers.shrink_to_fit();
+        drop((numbers, boxed));
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.check(), Ok(()));
+
+        let ptr = alloc
+            .allocate(Layout::from_size_align(1, 1).unwrap())
+            .unwrap();
+        unsafe { ptr.cast::<u8>().as_ptr().write_bytes(0xa5, ptr.len()) };
+        let layout = Layout::from_size_align(ptr.len(), 1).unwrap();
+        let new_layout = Layout::from_size_align(100, 1).unwrap();
+        let grown = unsafe { alloc.grow_zeroed(ptr.cast(), layout, new_layout) }.unwrap();
+        let bytes = unsafe { grown.as_ref() };
+        assert!(bytes[..ptr.len()].iter().all(|byte| *byte == 0xa5));
+        assert!(bytes[ptr.len()..].iter().all(|byte| *byte == 0));
+        unsafe { alloc.deallocate(grown.cast(), new_layout) };
+
+        let empty = alloc
+            .allocate(Layout::from_size_align(0, 64).unwrap())
+            .unwrap();
+        assert_eq!(empty.len(), 0);
+        assert_eq!(empty.cast::<u8>().as_ptr() as usize % 64, 0);
+    }
+
+    #[cfg(feature = "allocator-api2")]
+    #[test]
+    fn stable_containers() {
+        use allocator_api2::{boxed::Box, vec::Vec};
+
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let mut numbers = Vec::new_in(&alloc);
+        numbers.extend(0..100u32);
+        let boxed = Box::new_in([1u64; 4], &alloc);
+        assert_eq!(numbers.iter().sum::<u32>(), 4950);
+        assert_eq!(alloc.stats().allocation_count, 2);
+        drop((numbers, boxed));
+        assert_eq!(alloc.stats().allocation_count, 0);
+    }
+
+    #[test]
+    fn usable_size() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(1, 1).unwrap();
+        let ptr = alloc.allocate_slice(layout).unwrap();
+        assert!(ptr.len() >= 24);
+        // the whole slice is usable, and can be given back with any size in it
+        unsafe { ptr.cast::<u8>().as_ptr().write_bytes(0xa5, ptr.len()) };
+        let layout = Layout::from_size_align(ptr.len(), 1).unwrap();
+        let new_layout = Layout::from_size_align(100, 1).unwrap();
+        let grown = unsafe { alloc.resize(ptr.cast(), layout, new_layout) }.unwrap();
+        assert!(grown.len() >= 100);
+        let bytes = unsafe { grown.as_ref() };
+        assert!(bytes[..ptr.len()].iter().all(|byte| *byte == 0xa5));
+        assert_eq!(alloc.stats().requested_bytes, grown.len());
+        let layout = Layout::from_size_align(50, 1).unwrap();
+        unsafe { alloc.deallocate_slice(grown.cast(), layout) };
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.stats().requested_bytes, 0);
+    }
+}
@@ -0,0 +1,195 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ptr::null_mut,
+};
+
+use lock_api::RawMutex;
+
+use crate::{
+    linked::{Allocator, DefaultLock, Stats},
+    Owns, Space,
+};
+
+// `N` independent heaps, each thread allocating from the one its key hashes to, so threads mostly
+// take different locks instead of all of them taking one. when that heap runs out the others are
+// tried in order. a pointer is freed into the heap whose space contains it, whichever thread frees
+// it, the heap of the freeing thread first as it is the likely one
+pub struct Arenas<S, R = DefaultLock, const N: usize = 4> {
+    arenas: [Allocator<S, R>; N],
+    key: fn() -> usize,
+}
+
+// the address of a thread local, which is distinct between the running threads and taken without
+// allocating, unlike `thread::current().id()` on first use
+#[cfg(any(feature = "std", test))]
+pub fn thread_key() -> usize {
+    std::thread_local! {
+        static KEY: u8 = const { 0 };
+    }
+    KEY.with(|key| key as *const u8 as usize)
+}
+
+impl<S, R, const N: usize> Arenas<S, R, N>
+where
+    R: RawMutex,
+{
+    #[cfg(any(feature = "std", test))]
+    pub const fn new(arenas: [Allocator<S, R>; N]) -> Self {
+        Self::with_key(arenas, thread_key)
+    }
+
+    // `key` tells apart the threads, or the cores on bare metal. equal keys share a heap
+    pub const fn with_key(arenas: [Allocator<S, R>; N], key: fn() -> usize) -> Self {
+        assert!(N != 0);
+        Self { arenas, key }
+    }
+
+    pub fn arenas(&self) -> &[Allocator<S, R>; N] {
+        &self.arenas
+    }
+
+    fn index(&self) -> usize {
+        // fibonacci hashing, the keys of thread locals are aligned and close to each other
+        let hash = (self.key)().wrapping_mul(0x9e37_79b9_7f4a_7c15_u64 as usize);
+        (hash >> (usize::BITS / 2)) % N
+    }
+
+    // the heap of this thread first, then the others in order
+    fn iter_from_own(&self) -> impl Iterator<Item = &Allocator<S, R>> {
+        let index = self.index();
+        self.arenas[index..].iter().chain(&self.arenas[..index])
+    }
+}
+
+impl<S, R, const N: usize> Arenas<S, R, N>
+where
+    S: Space,
+    R: RawMutex,
+{
+    // of all the heaps together
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        for arena in &self.arenas {
+            stats.merge(arena.stats())
+        }
+        stats
+    }
+
+    fn owner_of(&self, ptr: *const u8) -> Option<&Allocator<S, R>> {
+        self.iter_from_own().find(|arena| arena.owns(ptr))
+    }
+}
+
+impl<S, R, const N: usize> Owns for Arenas<S, R, N>
+where
+    S: Space,
+    R: RawMutex,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        self.owner_of(ptr).is_some()
+    }
+}
+
+unsafe impl<S, R, const N: usize> GlobalAlloc for Arenas<S, R, N>
+where
+    S: Space,
+    R: RawMutex,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.iter_from_own()
+            .map(|arena| unsafe { arena.alloc(layout) })
+            .find(|ptr| !ptr.is_null())
+            .unwrap_or(null_mut())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        for arena in self.iter_from_own() {
+            if unsafe { arena.dealloc_owned(ptr, layout) } {
+                return;
+            }
+        }
+        // the foreign policy of the own heap
+        unsafe { self.arenas[self.index()].dealloc(ptr, layout) }
+    }
+
+    // within the heap that owns `ptr`, which may run out even if the others would not
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let owner = self.owner_of(ptr).unwrap_or(&self.arenas[self.index()]);
+        unsafe { owner.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, vec::Vec};
+
+    use crate::space::SystemBuffer;
+
+    use super::*;
+
+    fn new_arenas<const N: usize>(
+        len: usize,
+        key: fn() -> usize,
+    ) -> Arenas<SystemBuffer, DefaultLock, N> {
+        Arenas::with_key(
+            core::array::from_fn(|_| Allocator::new(SystemBuffer::new(len, 4096))),
+            key,
+        )
+    }
+
+    #[test]
+    fn fallback() {
+        let arenas = new_arenas::<3>(4 << 10, || 0);
+        let layout = Layout::from_size_align(1 << 10, 8).unwrap();
+        let ptrs = Vec::from_iter((0..6).map(|_| unsafe { arenas.alloc(layout) }));
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        let own = arenas.index();
+        assert!(arenas.arenas()[own].owns(ptrs[0]));
+        assert!(ptrs.iter().any(|ptr| !arenas.arenas()[own].owns(*ptr)));
+        assert_eq!(arenas.stats().allocation_count, 6);
+        for ptr in ptrs {
+            unsafe { arenas.dealloc(ptr, layout) }
+        }
+        for arena in arenas.arenas() {
+            assert_eq!(arena.stats().allocation_count, 0);
+            assert_eq!(arena.check(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn cross_thread() {
+        let arenas = Arenas::new(core::array::from_fn::<_, 4, _>(|_| {
+            Allocator::new(SystemBuffer::new(64 << 10, 4096))
+        }));
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let (sender, receiver) = mpsc::channel::<usize>();
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                let sender = sender.clone();
+                let arenas = &arenas;
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        let ptr = unsafe { arenas.alloc(layout) };
+                        assert!(!ptr.is_null());
+                        sender.send(ptr as usize).unwrap()
+                    }
+                });
+            }
+            drop(sender);
+            // freed on another thread than allocated
+            scope.spawn(|| {
+                for ptr in receiver {
+                    let ptr = ptr as *mut u8;
+                    let realloc = unsafe { arenas.realloc(ptr, layout, 128) };
+                    assert!(!realloc.is_null());
+                    let layout = Layout::from_size_align(128, 8).unwrap();
+                    unsafe { arenas.dealloc(realloc, layout) }
+                }
+            });
+        });
+        assert_eq!(arenas.stats().allocation_count, 0);
+        for arena in arenas.arenas() {
+            assert_eq!(arena.check(), Ok(()));
+        }
+    }
+}
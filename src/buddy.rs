@@ -0,0 +1,341 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ptr::{copy_nonoverlapping, null_mut},
+};
+
+use spin::{Mutex, MutexGuard};
+
+use crate::{Owns, Space};
+
+// a power of two buddy allocator, as an alternative to `linked` with O(log n) worst case for every
+// operation. a block of order k is `MIN_BLOCK << k` bytes and aligned to its size relative to the
+// arena, which is the largest power of two sized region that fits in the space, and the space is
+// not grown
+// the space starts with the header:
+// slot 0: marker
+// slot 1: offset of the arena
+// slot 2: order of the arena
+// slots 3..3 + ORDERS: head of the free list of each order
+// and then a bitmap with one bit per `MIN_BLOCK` of the arena, set for the first unit of a free
+// block. a free block holds its next and prev free block of the same order and its order, and an
+// in-use block holds nothing but user data, as `dealloc` tells the order by the layout
+struct Overlay {
+    space: *mut u8,
+    arena: *mut u8,
+    top_order: usize,
+}
+
+impl Overlay {
+    const MIN_BLOCK: usize = 32;
+    const ORDERS: usize = 48;
+    const ARENA_INDEX: usize = 1;
+    const ORDER_INDEX: usize = 2;
+    const HEADS_INDEX: usize = 3;
+    const BITMAP_OFFSET: usize = 8 * (Self::HEADS_INDEX + Self::ORDERS);
+    // an arena aligned beyond the page is not worth the padding
+    const MAX_ALIGN: usize = 4096;
+    const MARKER: u8 = 0xb0;
+
+    fn new(space: &mut impl Space) -> Self {
+        let space = space.as_mut_ptr();
+        unsafe {
+            Self {
+                space,
+                arena: space.add(*space.add(8 * Self::ARENA_INDEX).cast::<u64>() as usize),
+                top_order: *space.add(8 * Self::ORDER_INDEX).cast::<u64>() as usize,
+            }
+        }
+    }
+
+    fn arena_size(&self) -> usize {
+        Self::MIN_BLOCK << self.top_order
+    }
+
+    unsafe fn init(space: &mut impl Space) {
+        let len = space.len();
+        let base = space.as_mut_ptr();
+        assert!(
+            len >= Self::BITMAP_OFFSET + Self::MIN_BLOCK,
+            "space too small for a buddy heap"
+        );
+        let mut top_order = usize::min((len / Self::MIN_BLOCK).ilog2() as usize, Self::ORDERS - 1);
+        let arena_offset = loop {
+            let size = Self::MIN_BLOCK << top_order;
+            let bitmap_end = Self::BITMAP_OFFSET + (size / Self::MIN_BLOCK).div_ceil(8);
+            let align = usize::min(size, Self::MAX_ALIGN);
+            let offset = (base as usize + bitmap_end).next_multiple_of(align) - base as usize;
+            if offset + size <= len {
+                break offset;
+            }
+            assert!(top_order > 0, "space too small for a buddy heap");
+            top_order -= 1;
+        };
+        unsafe {
+            base.write_bytes(0, arena_offset);
+            *base.add(8 * Self::ARENA_INDEX).cast::<u64>() = arena_offset as _;
+            *base.add(8 * Self::ORDER_INDEX).cast::<u64>() = top_order as _;
+            let mut overlay = Self::new(space);
+            overlay.push(overlay.arena, top_order);
+            *base = Self::MARKER;
+        }
+    }
+
+    // the order of the smallest block that fits `layout`, `None` if no block can
+    fn order_of(&self, layout: Layout) -> Option<usize> {
+        if layout.align() > usize::min(self.arena_size(), Self::MAX_ALIGN) {
+            return None;
+        }
+        let size = usize::max(layout.size(), layout.align()).max(Self::MIN_BLOCK);
+        let order = (size.checked_next_power_of_two()? / Self::MIN_BLOCK).trailing_zeros() as usize;
+        Some(order).filter(|order| *order <= self.top_order)
+    }
+
+    unsafe fn head(&self, order: usize) -> *mut *mut u8 {
+        unsafe { self.space.add(8 * (Self::HEADS_INDEX + order)).cast() }
+    }
+
+    fn unit(&self, block: *mut u8) -> usize {
+        (block as usize - self.arena as usize) / Self::MIN_BLOCK
+    }
+
+    unsafe fn is_free(&self, block: *mut u8) -> bool {
+        let unit = self.unit(block);
+        unsafe { *self.space.add(Self::BITMAP_OFFSET + unit / 8) & (1 << (unit % 8)) != 0 }
+    }
+
+    unsafe fn set_free(&mut self, block: *mut u8, free: bool) {
+        let unit = self.unit(block);
+        let byte = unsafe { &mut *self.space.add(Self::BITMAP_OFFSET + unit / 8) };
+        if free {
+            *byte |= 1 << (unit % 8)
+        } else {
+            *byte &= !(1 << (unit % 8))
+        }
+    }
+
+    // next, prev and order of a free block
+    unsafe fn links(block: *mut u8) -> *mut [*mut u8; 3] {
+        block.cast()
+    }
+
+    unsafe fn push(&mut self, block: *mut u8, order: usize) {
+        unsafe {
+            let head = *self.head(order);
+            *Self::links(block) = [head, null_mut(), order as _];
+            if !head.is_null() {
+                (*Self::links(head))[1] = block
+            }
+            *self.head(order) = block;
+            self.set_free(block, true)
+        }
+    }
+
+    unsafe fn remove(&mut self, block: *mut u8, order: usize) {
+        unsafe {
+            let [next, prev, _] = *Self::links(block);
+            if prev.is_null() {
+                *self.head(order) = next
+            } else {
+                (*Self::links(prev))[0] = next
+            }
+            if !next.is_null() {
+                (*Self::links(next))[1] = prev
+            }
+            self.set_free(block, false)
+        }
+    }
+
+    unsafe fn alloc(&mut self, order: usize) -> *mut u8 {
+        let Some(mut block_order) =
+            (order..=self.top_order).find(|order| !unsafe { *self.head(*order) }.is_null())
+        else {
+            return null_mut();
+        };
+        let block = unsafe { *self.head(block_order) };
+        unsafe { self.remove(block, block_order) }
+        // keep the lower half and free the higher one, until the block is of the order
+        while block_order > order {
+            block_order -= 1;
+            unsafe { self.push(block.add(Self::MIN_BLOCK << block_order), block_order) }
+        }
+        block
+    }
+
+    unsafe fn dealloc(&mut self, mut block: *mut u8, mut order: usize) {
+        while order < self.top_order {
+            let offset = block as usize - self.arena as usize;
+            let buddy = unsafe { self.arena.add(offset ^ (Self::MIN_BLOCK << order)) };
+            // a free buddy may have been split, in which case only a part of it is free
+            if unsafe { !self.is_free(buddy) || (*Self::links(buddy))[2] as usize != order } {
+                break;
+            }
+            unsafe { self.remove(buddy, order) }
+            block = usize::min(block as usize, buddy as usize) as _;
+            order += 1;
+        }
+        unsafe { self.push(block, order) }
+    }
+
+    fn free_bytes(&self) -> usize {
+        (0..=self.top_order)
+            .map(|order| {
+                let mut count = 0;
+                let mut block = unsafe { *self.head(order) };
+                while !block.is_null() {
+                    count += 1;
+                    block = unsafe { (*Self::links(block))[0] };
+                }
+                count * (Self::MIN_BLOCK << order)
+            })
+            .sum()
+    }
+}
+
+pub struct Allocator<S>(Mutex<S>);
+
+impl<S> Allocator<S>
+where
+    S: Space,
+{
+    // the space must be at least a few kilobytes for the header, and aligned to 8 bytes
+    pub fn new(mut space: S) -> Self {
+        unsafe { Overlay::init(&mut space) };
+        Self(Mutex::new(space))
+    }
+
+    fn acquire_space(&self) -> MutexGuard<'_, S> {
+        loop {
+            if let Some(space) = self.0.try_lock() {
+                break space;
+            }
+        }
+    }
+
+    // the size of the arena, which is all that can be allocated
+    pub fn capacity(&self) -> usize {
+        Overlay::new(&mut *self.acquire_space()).arena_size()
+    }
+
+    pub fn free_bytes(&self) -> usize {
+        Overlay::new(&mut *self.acquire_space()).free_bytes()
+    }
+}
+
+impl<S> Owns for Allocator<S>
+where
+    S: Space,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        self.acquire_space().as_ptr_range().contains(&ptr)
+    }
+}
+
+unsafe impl<S> GlobalAlloc for Allocator<S>
+where
+    S: Space,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut space = self.acquire_space();
+        debug_assert_eq!(space.first(), Some(&Overlay::MARKER));
+        let mut overlay = Overlay::new(&mut *space);
+        match overlay.order_of(layout) {
+            Some(order) => unsafe { overlay.alloc(order) },
+            None => null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let mut space = self.acquire_space();
+        let mut overlay = Overlay::new(&mut *space);
+        let order = overlay.order_of(layout).unwrap();
+        unsafe { overlay.dealloc(ptr, order) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align()) else {
+            return null_mut();
+        };
+        let mut space = self.acquire_space();
+        let mut overlay = Overlay::new(&mut *space);
+        let order = overlay.order_of(layout).unwrap();
+        let Some(new_order) = overlay.order_of(new_layout) else {
+            return null_mut();
+        };
+        if new_order == order {
+            return ptr;
+        }
+        // the old block is freed after copying, so it cannot merge with the new one
+        let new_ptr = unsafe { overlay.alloc(new_order) };
+        if !new_ptr.is_null() {
+            unsafe {
+                copy_nonoverlapping(ptr, new_ptr, usize::min(layout.size(), new_size));
+                overlay.dealloc(ptr, order)
+            }
+        }
+        new_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fuzz::Method, space::SystemBuffer};
+
+    use super::*;
+
+    fn space(pages: usize) -> SystemBuffer {
+        SystemBuffer::new(pages * 4096, 4096)
+    }
+
+    #[test]
+    fn split_and_merge() {
+        let alloc = Allocator::new(space(17));
+        let capacity = alloc.capacity();
+        assert_eq!(capacity, 64 << 10);
+        assert_eq!(alloc.free_bytes(), capacity);
+
+        let small = Layout::from_size_align(1, 1).unwrap();
+        let large = Layout::from_size_align(5000, 8).unwrap();
+        let ptrs = [small, large, small].map(|layout| unsafe { alloc.alloc(layout) });
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        assert_eq!(ptrs[1] as usize % 4096, 0);
+        assert_eq!(alloc.free_bytes(), capacity - 8192 - 2 * 32);
+        // the two small blocks are buddies
+        assert_eq!(ptrs[2] as usize - ptrs[0] as usize, 32);
+
+        unsafe { alloc.dealloc(ptrs[0], small) };
+        assert!(unsafe { alloc.alloc(Layout::from_size_align(64, 1).unwrap()) } != ptrs[0]);
+        unsafe {
+            alloc.dealloc(ptrs[2], small);
+            alloc.dealloc(ptrs[1], large)
+        }
+        assert_eq!(alloc.free_bytes(), capacity - 64);
+        assert!(unsafe { alloc.alloc(Layout::from_size_align(capacity, 1).unwrap()) }.is_null());
+    }
+
+    #[test]
+    fn aligned_and_realloc() {
+        let alloc = Allocator::new(space(5));
+        let layout = Layout::from_size_align(16, 1024).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert_eq!(ptr as usize % 1024, 0);
+        assert!(unsafe { alloc.alloc(Layout::from_size_align(16, 8192).unwrap()) }.is_null());
+
+        unsafe { ptr.write_bytes(0xa5, 16) };
+        // same order, stays in place
+        assert_eq!(unsafe { alloc.realloc(ptr, layout, 1000) }, ptr);
+        let moved = unsafe { alloc.realloc(ptr, layout, 3000) };
+        assert_ne!(moved, ptr);
+        assert!((0..16).all(|index| unsafe { *moved.add(index) } == 0xa5));
+        unsafe { alloc.dealloc(moved, Layout::from_size_align(3000, 1024).unwrap()) };
+        assert_eq!(alloc.free_bytes(), alloc.capacity());
+    }
+
+    #[test]
+    fn fuzz_sequences() {
+        for methods in Method::padded_sequences() {
+            let alloc = Allocator::new(space(33));
+            Method::run_fuzz_with(methods.into_iter(), &alloc, |_| {});
+            assert_eq!(alloc.free_bytes(), alloc.capacity());
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use lock_api::RawMutex;
+
+#[cfg(feature = "switchable")]
+use crate::Switchable;
+use crate::{
+    linked::{Allocator, Bins, Config, ForeignPolicy, Placement, TopPolicy, ZeroSize},
+    Space,
+};
+
+// one place to configure a heap and the wrappers around it, each `build*` returns a plain nested
+// type, e.g. `Switchable<Allocator<Mmap>>`, so it can be named in a static
+pub struct Builder<S> {
+    space: S,
+    size: Option<usize>,
+    config: Config,
+    name: Option<&'static str>,
+    foreign_policy: ForeignPolicy,
+}
+
+impl<S> Builder<S>
+where
+    S: Space,
+{
+    pub fn new(space: S) -> Self {
+        Self {
+            space,
+            size: None,
+            config: Config::default(),
+            name: None,
+            foreign_policy: ForeignPolicy::default(),
+        }
+    }
+
+    // resize the space before initializing the heap, which is needed for spaces that start empty
+    // such as `Mmap`
+    pub fn size(mut self, bytes: usize) -> Self {
+        self.size = Some(bytes);
+        self
+    }
+
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn top_policy(mut self, top_policy: TopPolicy) -> Self {
+        self.config.top_policy = top_policy;
+        self
+    }
+
+    pub fn placement(mut self, placement: Placement) -> Self {
+        self.config.placement = placement;
+        self
+    }
+
+    pub fn start_align(mut self, start_align: usize) -> Self {
+        self.config.start_align = start_align;
+        self
+    }
+
+    pub fn bins(mut self, bins: Bins) -> Self {
+        self.config.bins = bins;
+        self
+    }
+
+    pub fn fast_bins(mut self, fast_bins: bool) -> Self {
+        self.config.fast_bins = fast_bins;
+        self
+    }
+
+    pub fn deferred_coalescing(mut self, deferred_coalescing: bool) -> Self {
+        self.config.deferred_coalescing = deferred_coalescing;
+        self
+    }
+
+    pub fn zero_size(mut self, zero_size: ZeroSize) -> Self {
+        self.config.zero_size = zero_size;
+        self
+    }
+
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn foreign_policy(mut self, foreign_policy: ForeignPolicy) -> Self {
+        self.foreign_policy = foreign_policy;
+        self
+    }
+
+    // panic if the space cannot be resized
+    pub fn build(self) -> Allocator<S> {
+        self.build_with_lock()
+    }
+
+    // e.g. `builder.build_with_lock::<parking_lot::RawMutex>()`
+    pub fn build_with_lock<R>(mut self) -> Allocator<S, R>
+    where
+        R: RawMutex,
+    {
+        if let Some(bytes) = self.size {
+            assert!(
+                self.space.set_size(bytes),
+                "cannot resize the space to {bytes} bytes"
+            );
+        }
+        Allocator::try_with_lock(self.space, self.config)
+            .unwrap_or_else(|error| panic!("cannot create a heap: {error:?}"))
+            .with_name(self.name)
+            .with_foreign_policy(self.foreign_policy)
+    }
+
+    #[cfg(feature = "switchable")]
+    pub fn build_switchable(self) -> Switchable<Allocator<S>> {
+        Switchable::new(self.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+    use std::vec;
+
+    use crate::{space::Fixed, Owns};
+
+    use super::*;
+
+    #[test]
+    fn build() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Builder::new(Fixed::from(data))
+            .top_policy(TopPolicy::KeepWilderness)
+            .start_align(256)
+            .bins(Bins::Compact)
+            .fast_bins(false)
+            .name("built")
+            .foreign_policy(ForeignPolicy::Ignore)
+            .build();
+        assert_eq!(alloc.name(), Some("built"));
+        assert_eq!(alloc.overhead().bins, 272);
+        let mut foreign = 0u64;
+        let foreign = (&mut foreign as *mut u64).cast();
+        unsafe { alloc.dealloc(foreign, Layout::new::<u64>()) };
+        assert_eq!(alloc.stats().ops.foreign, 1);
+        let ptr = unsafe { alloc.alloc(Layout::from_size_align(16, 8).unwrap()) };
+        assert_eq!(ptr as usize & 255, 0);
+        assert!(alloc.owns(ptr));
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot resize")]
+    fn build_resizing() {
+        let data = &mut *vec![0; 4 << 10];
+        Builder::new(Fixed::from(data)).size(8 << 10).build();
+    }
+
+    #[cfg(feature = "switchable")]
+    #[test]
+    fn build_switchable() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Builder::new(Fixed::from(data)).build_switchable();
+        let layout = Layout::from_size_align(16, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert!(alloc.owns(ptr));
+        alloc.set_enable(false);
+        let system = unsafe { alloc.alloc(layout) };
+        assert!(!alloc.owns(system));
+        unsafe {
+            alloc.dealloc(system, layout);
+            alloc.dealloc(ptr, layout)
+        }
+    }
+}
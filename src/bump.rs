@@ -0,0 +1,175 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ptr::{copy_nonoverlapping, null_mut},
+};
+
+use spin::{Mutex, MutexGuard};
+
+use crate::{Owns, Space};
+
+// an arena for per-frame or per-request allocations, which bumps an offset into the space and
+// frees everything at once on `reset`. freeing or reallocating the latest allocation moves the
+// offset back, anything else is only released by `reset`. there is no metadata in the space, and
+// the space is not grown
+pub struct Allocator<S>(Mutex<Arena<S>>);
+
+struct Arena<S> {
+    space: S,
+    // bytes in use from the start of the space
+    offset: usize,
+}
+
+impl<S> Arena<S>
+where
+    S: Space,
+{
+    fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let base = self.space.as_mut_ptr();
+        let Some(start) = (base as usize)
+            .checked_add(self.offset)
+            .and_then(|addr| addr.checked_next_multiple_of(layout.align()))
+            .map(|addr| addr - base as usize)
+        else {
+            return null_mut();
+        };
+        match start.checked_add(layout.size()) {
+            Some(end) if end <= self.space.len() => {
+                self.offset = end;
+                unsafe { base.add(start) }
+            }
+            _ => null_mut(),
+        }
+    }
+
+    fn is_latest(&self, ptr: *mut u8, size: usize) -> bool {
+        ptr as usize + size == self.space.as_ptr() as usize + self.offset
+    }
+}
+
+impl<S> Allocator<S>
+where
+    S: Space,
+{
+    pub fn new(space: S) -> Self {
+        Self(Mutex::new(Arena { space, offset: 0 }))
+    }
+
+    fn acquire_arena(&self) -> MutexGuard<'_, Arena<S>> {
+        loop {
+            if let Some(arena) = self.0.try_lock() {
+                break arena;
+            }
+        }
+    }
+
+    // free every allocation at once
+    // none of the allocations may be used after this
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn reset(&self) {
+        self.acquire_arena().offset = 0
+    }
+
+    // bytes allocated since the last reset, including alignment padding
+    pub fn used(&self) -> usize {
+        self.acquire_arena().offset
+    }
+
+    pub fn remaining(&self) -> usize {
+        let arena = self.acquire_arena();
+        arena.space.len() - arena.offset
+    }
+}
+
+impl<S> Owns for Allocator<S>
+where
+    S: Space,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        self.acquire_arena().space.as_ptr_range().contains(&ptr)
+    }
+}
+
+unsafe impl<S> GlobalAlloc for Allocator<S>
+where
+    S: Space,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.acquire_arena().alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let mut arena = self.acquire_arena();
+        if arena.is_latest(ptr, layout.size()) {
+            arena.offset = ptr as usize - arena.space.as_ptr() as usize
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align()) else {
+            return null_mut();
+        };
+        let mut arena = self.acquire_arena();
+        if arena.is_latest(ptr, layout.size()) {
+            let start = ptr as usize - arena.space.as_ptr() as usize;
+            if start + new_size <= arena.space.len() {
+                arena.offset = start + new_size;
+                return ptr;
+            }
+        } else if new_size <= layout.size() {
+            return ptr;
+        }
+        let new_ptr = arena.alloc(new_layout);
+        if !new_ptr.is_null() {
+            unsafe { copy_nonoverlapping(ptr, new_ptr, usize::min(layout.size(), new_size)) }
+        }
+        new_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use crate::{fuzz::Method, space::Fixed};
+
+    use super::*;
+
+    #[test]
+    fn bump_and_reset() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(10, 1).unwrap();
+        let aligned = Layout::from_size_align(16, 64).unwrap();
+        let ptrs = [layout, aligned, layout].map(|layout| unsafe { alloc.alloc(layout) });
+        assert_eq!(ptrs[1] as usize % 64, 0);
+        assert_eq!(ptrs[2] as usize, ptrs[1] as usize + 16);
+        assert!(alloc.owns(ptrs[2]));
+
+        // only the latest one is given back
+        let used = alloc.used();
+        unsafe { alloc.dealloc(ptrs[1], aligned) };
+        assert_eq!(alloc.used(), used);
+        unsafe { alloc.dealloc(ptrs[2], layout) };
+        assert_eq!(alloc.used(), used - 10);
+        unsafe { ptrs[1].write_bytes(0xa5, 16) };
+        assert_eq!(unsafe { alloc.realloc(ptrs[1], aligned, 100) }, ptrs[1]);
+        let moved = unsafe { alloc.realloc(ptrs[0], layout, 20) };
+        assert_eq!(moved, unsafe { ptrs[1].add(100) });
+
+        assert!(unsafe { alloc.alloc(Layout::from_size_align(4 << 10, 1).unwrap()) }.is_null());
+        unsafe { alloc.reset() };
+        assert_eq!(alloc.used(), 0);
+        assert_eq!(alloc.remaining(), 4 << 10);
+        assert!(!unsafe { alloc.alloc(Layout::from_size_align(4 << 10, 1).unwrap()) }.is_null());
+    }
+
+    #[test]
+    fn fuzz_sequences() {
+        let data = &mut *vec![0; 64 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        for methods in Method::padded_sequences() {
+            Method::run_fuzz_with(methods.into_iter(), &alloc, |_| {});
+            unsafe { alloc.reset() };
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    hint::spin_loop,
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+    thread,
+    time::Duration,
+};
+
+// random short delays inside the critical sections of the heaps and between the steps of the
+// wrappers, to shake out assumptions about how operations interleave. for tests only, the delays
+// are taken on every operation. nothing here allocates, so it is fine inside a global allocator
+static STATE: AtomicU64 = AtomicU64::new(0x9e37_79b9_7f4a_7c15);
+
+// the same seed gives the same sequence of delays, though with more than one thread the order the
+// threads draw from it still varies
+pub fn seed(seed: u64) {
+    // xorshift is stuck at zero
+    STATE.store(seed | 1, Relaxed)
+}
+
+fn next() -> u64 {
+    let mut x = STATE.load(Relaxed);
+    loop {
+        let mut next = x;
+        next ^= next << 13;
+        next ^= next >> 7;
+        next ^= next << 17;
+        match STATE.compare_exchange_weak(x, next, Relaxed, Relaxed) {
+            Ok(_) => break next,
+            Err(current) => x = current,
+        }
+    }
+}
+
+pub(crate) fn delay() {
+    let random = next();
+    match random & 3 {
+        0 => {}
+        1 => {
+            for _ in 0..(random >> 8) % 256 {
+                spin_loop()
+            }
+        }
+        2 => thread::yield_now(),
+        _ => thread::sleep(Duration::from_micros((random >> 8) % 50)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+    use std::{sync::Barrier, vec::Vec};
+
+    use crate::{linked::Allocator, space::SystemBuffer};
+
+    use super::*;
+
+    #[test]
+    fn interleaved_threads() {
+        seed(42);
+        let alloc = Allocator::new(SystemBuffer::new(64 << 10, 4096));
+        let barrier = Barrier::new(4);
+        thread::scope(|scope| {
+            for index in 0..4u8 {
+                let (alloc, barrier) = (&alloc, &barrier);
+                scope.spawn(move || {
+                    barrier.wait();
+                    let mut ptrs = Vec::new();
+                    for round in 0..200 {
+                        let layout = Layout::from_size_align(8 + round % 64, 8).unwrap();
+                        let ptr = unsafe { alloc.alloc(layout) };
+                        unsafe { ptr.write_bytes(index, layout.size()) };
+                        ptrs.push((ptr, layout));
+                        if round % 3 == 0 {
+                            let (ptr, layout) = ptrs.swap_remove(round % ptrs.len());
+                            assert!((0..layout.size()).all(|i| unsafe { *ptr.add(i) } == index));
+                            unsafe { alloc.dealloc(ptr, layout) }
+                        }
+                    }
+                    for (ptr, layout) in ptrs {
+                        unsafe { alloc.dealloc(ptr, layout) }
+                    }
+                });
+            }
+        });
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.check(), Ok(()));
+    }
+}
@@ -0,0 +1,55 @@
+// where the features counting time read it from, so they work the same on targets without a clock:
+// any monotonic counter does, e.g. a tick interrupt or a cycle counter, in whatever unit it counts.
+// it must not go backward, while wrapping around is up to the user to avoid
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+// e.g. `&|| TICKS.load(Relaxed)`
+impl<F> Clock for F
+where
+    F: Fn() -> u64,
+{
+    fn now(&self) -> u64 {
+        self()
+    }
+}
+
+// nanoseconds since the first reading in the process, from `Instant`
+#[cfg(feature = "std")]
+pub struct StdClock;
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now(&self) -> u64 {
+        use std::{sync::OnceLock, time::Instant};
+
+        static START: OnceLock<Instant> = OnceLock::new();
+        let start = START.get_or_init(Instant::now);
+        start.elapsed().as_nanos() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn clocks() {
+        let ticks = Cell::new(0);
+        let clock = || {
+            ticks.set(ticks.get() + 1);
+            ticks.get()
+        };
+        assert_eq!(clock.now(), 1);
+        assert_eq!(clock.now(), 2);
+        #[cfg(feature = "std")]
+        {
+            let now = StdClock.now();
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            assert!(StdClock.now() >= now + 1_000_000);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{linked::Allocator, Space};
+
+// what an ideal compaction would give, i.e. every allocation moved down next to each other so all
+// free chunks merge into one, without moving anything. sizes are chunk sizes as in `Stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    pub free_bytes: usize,
+    pub largest_free: usize,
+    // the allocations above the lowest free chunk, which would have to move
+    pub moved_count: usize,
+    pub moved_bytes: usize,
+}
+
+impl CompactionReport {
+    // the largest free chunk after compacting, which is all the free bytes
+    pub fn compacted_free(&self) -> usize {
+        self.free_bytes
+    }
+
+    // how much larger the largest free chunk would get, zero means failing allocations are due to
+    // genuine usage rather than fragmentation
+    pub fn recoverable(&self) -> usize {
+        self.free_bytes - self.largest_free
+    }
+
+    // whether a chunk of `size` bytes fits now, and after compacting
+    pub fn fits(&self, size: usize) -> (bool, bool) {
+        (size <= self.largest_free, size <= self.compacted_free())
+    }
+}
+
+impl<S, R> Allocator<S, R>
+where
+    S: Space,
+    R: lock_api::RawMutex,
+{
+    pub fn compaction_report(&self) -> CompactionReport {
+        let mut report = CompactionReport::default();
+        let mut hole = false;
+        for info in self.chunks() {
+            if info.in_use {
+                if hole {
+                    report.moved_count += 1;
+                    report.moved_bytes += info.size
+                }
+            } else {
+                hole = true;
+                report.free_bytes += info.size;
+                report.largest_free = usize::max(report.largest_free, info.size)
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+    use std::vec;
+
+    use crate::{
+        linked::{Config, TopPolicy},
+        space::Fixed,
+    };
+
+    use super::*;
+
+    #[test]
+    fn report() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                top_policy: TopPolicy::UseWilderness,
+                ..Default::default()
+            },
+        );
+        let fresh = alloc.compaction_report();
+        assert_eq!(fresh.moved_count, 0);
+        assert_eq!(fresh.recoverable(), 0);
+
+        let layout = Layout::from_size_align(256, 8).unwrap();
+        let mut ptrs = vec![];
+        while let Some(ptr) = Some(unsafe { alloc.alloc(layout) }).filter(|ptr| !ptr.is_null()) {
+            ptrs.push(ptr)
+        }
+        for ptr in ptrs.iter().step_by(2) {
+            unsafe { alloc.dealloc(*ptr, layout) }
+        }
+        let report = alloc.compaction_report();
+        let stats = alloc.stats();
+        assert_eq!(report.free_bytes, stats.free_bytes);
+        assert_eq!(report.largest_free, stats.largest_free);
+        assert_eq!(report.moved_count, ptrs.len() / 2);
+        assert!(report.recoverable() > 0);
+        // larger than any free chunk, however much the allocations left at the end
+        assert_eq!(report.fits(report.largest_free + 1), (false, true));
+        assert_eq!(report.fits(4 << 10), (false, false));
+        for ptr in ptrs.iter().skip(1).step_by(2) {
+            unsafe { alloc.dealloc(*ptr, layout) }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use core::{
+    cell::Cell,
+    hint::spin_loop,
+    sync::atomic::{
+        AtomicBool,
+        Ordering::{Acquire, Relaxed, Release},
+    },
+};
+
+use critical_section::RestoreState;
+use lock_api::{GuardNoSend, RawMutex};
+
+// a lock that holds a critical section, e.g. with interrupts masked on a single core MCU, so the
+// heap can be used from both thread and interrupt context: an interrupt never preempts the holder,
+// where it would spin forever on a spin lock. the flag keeps out the other holders of the same
+// critical section, i.e. `try_lock` of the grower lock while holding the heap, and the other cores
+// for implementations that do not exclude them. the default lock of `linked::Allocator` with the
+// `critical-section` feature
+pub struct RawCriticalSection {
+    locked: AtomicBool,
+    restore: Cell<RestoreState>,
+}
+
+// the restore state is only touched by the holder
+unsafe impl Sync for RawCriticalSection {}
+
+unsafe impl RawMutex for RawCriticalSection {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        locked: AtomicBool::new(false),
+        restore: Cell::new(RestoreState::invalid()),
+    };
+
+    // released in the context that acquired it, and in the reverse order of acquiring, which the
+    // heap does with its two locks
+    type GuardMarker = GuardNoSend;
+
+    fn lock(&self) {
+        // interrupts get in between the attempts
+        while !self.try_lock() {
+            spin_loop()
+        }
+    }
+
+    fn try_lock(&self) -> bool {
+        let restore = unsafe { critical_section::acquire() };
+        if self.locked.swap(true, Acquire) {
+            unsafe { critical_section::release(restore) };
+            return false;
+        }
+        self.restore.set(restore);
+        true
+    }
+
+    unsafe fn unlock(&self) {
+        let restore = self.restore.replace(RestoreState::invalid());
+        self.locked.store(false, Release);
+        unsafe { critical_section::release(restore) }
+    }
+
+    fn is_locked(&self) -> bool {
+        self.locked.load(Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+    use std::{thread, vec};
+
+    use crate::{linked::Allocator, space::Fixed};
+
+    use super::*;
+
+    #[test]
+    fn nested() {
+        let lock = RawCriticalSection::INIT;
+        let inner = RawCriticalSection::INIT;
+        lock.lock();
+        assert!(!lock.try_lock());
+        assert!(inner.try_lock());
+        unsafe {
+            inner.unlock();
+            lock.unlock()
+        }
+        assert!(!lock.is_locked());
+        assert!(critical_section::with(|_| lock.try_lock()));
+        unsafe { lock.unlock() }
+    }
+
+    #[test]
+    fn heap() {
+        let data = &mut *vec![0; 16 << 10];
+        let alloc = Allocator::<_, RawCriticalSection>::try_with_lock(
+            Fixed::from(data),
+            Default::default(),
+        )
+        .unwrap();
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        let ptr = unsafe { alloc.alloc(layout) };
+                        assert!(!ptr.is_null());
+                        unsafe { alloc.dealloc(ptr, layout) }
+                    }
+                });
+            }
+        });
+        assert_eq!(alloc.check(), Ok(()));
+        assert_eq!(alloc.stats().allocation_count, 0);
+    }
+}
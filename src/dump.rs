@@ -0,0 +1,83 @@
+use core::{fmt, str};
+
+// a sink of `N` bytes on the stack, for formatting inside a global allocator or with the heap
+// locked, where a `String` would allocate from the heap being formatted. the formatting of this
+// crate, e.g. `Allocator::dump` or the `Debug` of the stats, never allocates on its own, so this is
+// all it takes. text past the end is cut at a char boundary and the write fails
+pub struct Buffer<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    truncated: bool,
+}
+
+impl<const N: usize> Buffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only whole chars are written
+        unsafe { str::from_utf8_unchecked(&self.buf[..self.len]) }
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false
+    }
+}
+
+impl<const N: usize> Default for Buffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> fmt::Write for Buffer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut len = usize::min(s.len(), N - self.len);
+        while !s.is_char_boundary(len) {
+            len -= 1
+        }
+        self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        if len != s.len() {
+            self.truncated = true;
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> fmt::Display for Buffer<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::*;
+
+    #[test]
+    fn buffer() {
+        let mut buf = Buffer::<8>::new();
+        write!(buf, "{}", 1234).unwrap();
+        assert_eq!(buf.as_str(), "1234");
+        assert!(write!(buf, "-é-é").is_err());
+        assert_eq!(buf.as_str(), "1234-é-");
+        assert!(buf.is_truncated());
+        buf.clear();
+        assert_eq!(buf.as_str(), "");
+        assert!(!buf.is_truncated());
+    }
+}
@@ -0,0 +1,84 @@
+// where hardening features take their randomness from, the crate has no source of its own without
+// std, so embedded targets wire in their hardware generator by implementing this
+pub trait EntropySource {
+    // fill all of `bytes`, return false if the source fails, in which case the feature asking for
+    // them fails instead of going on with weak values
+    fn fill(&self, bytes: &mut [u8]) -> bool;
+
+    fn next_u32(&self) -> Option<u32> {
+        let mut bytes = [0; 4];
+        self.fill(&mut bytes).then(|| u32::from_le_bytes(bytes))
+    }
+}
+
+impl<E> EntropySource for &E
+where
+    E: EntropySource + ?Sized,
+{
+    fn fill(&self, bytes: &mut [u8]) -> bool {
+        E::fill(self, bytes)
+    }
+}
+
+// the random keys std seeds its hash maps with, from the operating system once per thread and
+// then derived, good enough against guessing from outside but not for key material
+#[cfg(feature = "std")]
+pub struct StdEntropy;
+
+#[cfg(feature = "std")]
+impl EntropySource for StdEntropy {
+    fn fill(&self, bytes: &mut [u8]) -> bool {
+        use std::{
+            collections::hash_map::RandomState,
+            hash::{BuildHasher, Hasher},
+        };
+
+        for chunk in bytes.chunks_mut(8) {
+            let word = RandomState::new().build_hasher().finish().to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()])
+        }
+        true
+    }
+}
+
+// the system generator through the `getrandom` crate, which also covers some targets without std
+#[cfg(feature = "getrandom")]
+pub struct GetRandom;
+
+#[cfg(feature = "getrandom")]
+impl EntropySource for GetRandom {
+    fn fill(&self, bytes: &mut [u8]) -> bool {
+        getrandom::getrandom(bytes).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Failing;
+
+    impl EntropySource for Failing {
+        fn fill(&self, _: &mut [u8]) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn sources() {
+        assert_eq!(Failing.next_u32(), None);
+        #[cfg(feature = "std")]
+        {
+            let mut bytes = [0; 13];
+            assert!(StdEntropy.fill(&mut bytes));
+            assert_ne!(bytes, [0; 13]);
+            assert_ne!(StdEntropy.next_u32(), StdEntropy.next_u32());
+        }
+        #[cfg(feature = "getrandom")]
+        {
+            let mut bytes = [0; 13];
+            assert!(GetRandom.fill(&mut bytes));
+            assert_ne!(bytes, [0; 13]);
+        }
+    }
+}
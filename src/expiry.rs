@@ -0,0 +1,254 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    mem::{align_of, size_of},
+    ptr::{null_mut, NonNull},
+};
+
+use spin::Mutex;
+
+use crate::{clock::Clock, linked::Allocator, Space};
+
+// every allocation is linked through a header right before its user data, the list is sorted by
+// deadline so reclaiming only visits the expired ones
+#[repr(C)]
+struct Header {
+    prev: *mut Header,
+    next: *mut Header,
+    deadline: u64,
+    layout: Layout,
+}
+
+struct List {
+    head: *mut Header,
+    tail: *mut Header,
+}
+
+unsafe impl Send for List {}
+
+// an arena whose allocations expire, e.g. cache entries or reassembly buffers, `reclaim` frees
+// every allocation whose deadline is not after `now`. the time is whatever the caller counts in,
+// such as ticks or milliseconds since some epoch, as long as it does not go backward, or the one of
+// a `Clock` for `alloc_ttl` and `reclaim_expired`
+pub struct ExpiryArena<S> {
+    alloc: Allocator<S>,
+    list: Mutex<List>,
+}
+
+impl<S> ExpiryArena<S>
+where
+    S: Space,
+{
+    pub fn new(alloc: Allocator<S>) -> Self {
+        Self {
+            alloc,
+            list: Mutex::new(List {
+                head: null_mut(),
+                tail: null_mut(),
+            }),
+        }
+    }
+
+    pub fn allocator(&self) -> &Allocator<S> {
+        &self.alloc
+    }
+
+    // the header goes right before the user data, so the offset of the user data in the block is
+    // the header size rounded up to the alignment
+    fn block_layout(layout: Layout) -> Option<(Layout, usize)> {
+        let offset = size_of::<Header>().checked_next_multiple_of(layout.align())?;
+        let block = Layout::from_size_align(
+            offset.checked_add(layout.size())?,
+            usize::max(layout.align(), align_of::<Header>()),
+        )
+        .ok()?;
+        Some((block, offset))
+    }
+
+    // `None` if out of memory
+    pub fn alloc(&self, layout: Layout, deadline: u64) -> Option<NonNull<u8>> {
+        let (block, offset) = Self::block_layout(layout)?;
+        let user_data = NonNull::new(unsafe { self.alloc.alloc(block) })?;
+        let user_data = unsafe { user_data.add(offset) };
+        let header = unsafe { user_data.cast::<Header>().as_ptr().sub(1) };
+
+        let mut list = self.list.lock();
+        // deadlines mostly come in order, so search from the latest one
+        let mut prev = list.tail;
+        while !prev.is_null() && unsafe { (*prev).deadline } > deadline {
+            prev = unsafe { (*prev).prev }
+        }
+        let next = if prev.is_null() {
+            list.head
+        } else {
+            unsafe { (*prev).next }
+        };
+        unsafe {
+            header.write(Header {
+                prev,
+                next,
+                deadline,
+                layout,
+            })
+        }
+        Self::link(&mut list, header);
+        Some(user_data)
+    }
+
+    // expire `ttl` after the current time of `clock`
+    pub fn alloc_ttl(&self, layout: Layout, ttl: u64, clock: &impl Clock) -> Option<NonNull<u8>> {
+        self.alloc(layout, clock.now().saturating_add(ttl))
+    }
+
+    fn link(list: &mut List, header: *mut Header) {
+        let Header { prev, next, .. } = unsafe { &*header };
+        if prev.is_null() {
+            list.head = header
+        } else {
+            unsafe { (**prev).next = header }
+        }
+        if next.is_null() {
+            list.tail = header
+        } else {
+            unsafe { (**next).prev = header }
+        }
+    }
+
+    fn unlink(list: &mut List, header: *mut Header) {
+        let Header { prev, next, .. } = unsafe { &*header };
+        if prev.is_null() {
+            list.head = *next
+        } else {
+            unsafe { (**prev).next = *next }
+        }
+        if next.is_null() {
+            list.tail = *prev
+        } else {
+            unsafe { (**next).prev = *prev }
+        }
+    }
+
+    unsafe fn free_block(&self, header: *mut Header) {
+        let layout = unsafe { (*header).layout };
+        let (block, offset) = Self::block_layout(layout).unwrap();
+        unsafe {
+            self.alloc
+                .dealloc(header.add(1).cast::<u8>().sub(offset), block)
+        }
+    }
+
+    // free before the deadline
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn dealloc(&self, ptr: NonNull<u8>) {
+        // safety: `ptr` is allocated by this arena and not reclaimed yet
+        let header = unsafe { ptr.cast::<Header>().as_ptr().sub(1) };
+        Self::unlink(&mut self.list.lock(), header);
+        unsafe { self.free_block(header) }
+    }
+
+    // move the deadline of `ptr`, either earlier or later
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn extend(&self, ptr: NonNull<u8>, deadline: u64) {
+        // safety: `ptr` is allocated by this arena and not reclaimed yet
+        let header = unsafe { ptr.cast::<Header>().as_ptr().sub(1) };
+        let mut list = self.list.lock();
+        Self::unlink(&mut list, header);
+        let mut prev = list.tail;
+        while !prev.is_null() && unsafe { (*prev).deadline } > deadline {
+            prev = unsafe { (*prev).prev }
+        }
+        unsafe {
+            (*header).prev = prev;
+            (*header).next = if prev.is_null() {
+                list.head
+            } else {
+                (*prev).next
+            };
+            (*header).deadline = deadline
+        }
+        Self::link(&mut list, header)
+    }
+
+    // free every allocation with a deadline up to `now`, return the number of them
+    pub fn reclaim(&self, now: u64) -> usize {
+        let mut count = 0;
+        loop {
+            let mut list = self.list.lock();
+            let header = list.head;
+            if header.is_null() || unsafe { (*header).deadline } > now {
+                break count;
+            }
+            Self::unlink(&mut list, header);
+            // the list is released before freeing, so allocating on other threads goes on
+            drop(list);
+            unsafe { self.free_block(header) }
+            count += 1
+        }
+    }
+
+    pub fn reclaim_expired(&self, clock: &impl Clock) -> usize {
+        self.reclaim(clock.now())
+    }
+
+    // the earliest deadline of all allocations, to tell when to reclaim next
+    pub fn next_deadline(&self) -> Option<u64> {
+        let list = self.list.lock();
+        (!list.head.is_null()).then(|| unsafe { (*list.head).deadline })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use std::vec;
+
+    use crate::space::Fixed;
+
+    use super::*;
+
+    #[test]
+    fn reclaim() {
+        let data = &mut *vec![0; 4 << 10];
+        let arena = ExpiryArena::new(Allocator::new(Fixed::from(data)));
+        let layout = Layout::from_size_align(32, 8).unwrap();
+        let ptrs = [30, 10, 20, 40].map(|deadline| {
+            let ptr = arena.alloc(layout, deadline).unwrap();
+            unsafe { ptr.write_bytes(deadline as u8, layout.size()) };
+            ptr
+        });
+        let aligned = arena
+            .alloc(Layout::from_size_align(16, 256).unwrap(), 25)
+            .unwrap();
+        assert_eq!(aligned.as_ptr() as usize & 255, 0);
+        assert_eq!(arena.next_deadline(), Some(10));
+
+        assert_eq!(arena.reclaim(15), 1);
+        assert_eq!(arena.next_deadline(), Some(20));
+        assert_eq!(arena.allocator().stats().allocation_count, 4);
+        unsafe {
+            arena.dealloc(ptrs[2]);
+            arena.extend(ptrs[0], 50)
+        }
+        assert_eq!(arena.reclaim(45), 2);
+        assert_eq!(unsafe { *ptrs[0].as_ptr() }, 30);
+        assert_eq!(arena.reclaim(100), 1);
+        assert_eq!(arena.next_deadline(), None);
+        assert_eq!(arena.allocator().stats().allocation_count, 0);
+        assert_eq!(arena.allocator().check(), Ok(()));
+    }
+
+    #[test]
+    fn ttl() {
+        let data = &mut *vec![0; 4 << 10];
+        let arena = ExpiryArena::new(Allocator::new(Fixed::from(data)));
+        let ticks = Cell::new(100);
+        let clock = || ticks.get();
+        let layout = Layout::from_size_align(32, 8).unwrap();
+        arena.alloc_ttl(layout, 10, &clock).unwrap();
+        arena.alloc_ttl(layout, u64::MAX, &clock).unwrap();
+        assert_eq!(arena.next_deadline(), Some(110));
+        assert_eq!(arena.reclaim_expired(&clock), 0);
+        ticks.set(110);
+        assert_eq!(arena.reclaim_expired(&clock), 1);
+        assert_eq!(arena.next_deadline(), Some(u64::MAX));
+    }
+}
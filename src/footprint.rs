@@ -0,0 +1,111 @@
+use crate::{
+    linked::{Allocator, Overhead},
+    Space,
+};
+
+// where the memory of a heap goes, from the address range of the space down to the chunks. chunk
+// sizes are as in `Stats`, so the used and free bytes and the overhead before the lowest chunk add
+// up to the space. the parts are taken one after another, so a heap used meanwhile by other
+// threads gives a report that does not add up exactly
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryFootprint {
+    // including the reservation beyond the space, if any
+    pub virtual_bytes: usize,
+    pub space_bytes: usize,
+    // `None` if the space cannot tell, see `Space::resident_bytes`
+    pub resident_bytes: Option<usize>,
+    pub used_bytes: usize,
+    pub free_bytes: usize,
+    pub overhead: Overhead,
+    // the free bytes outside of the largest free chunk, which an allocation larger than that chunk
+    // cannot use
+    pub fragmented_bytes: usize,
+}
+
+impl<S, R> Allocator<S, R>
+where
+    S: Space,
+    R: lock_api::RawMutex,
+{
+    pub fn footprint(&self) -> MemoryFootprint {
+        let (space_bytes, virtual_bytes, resident_bytes) = {
+            let space = self.acquire_space();
+            let reserved = space.reservation().map_or(0, |reservation| reservation.len);
+            (
+                space.len(),
+                usize::max(space.len(), reserved),
+                space.resident_bytes(),
+            )
+        };
+        let stats = self.stats();
+        MemoryFootprint {
+            virtual_bytes,
+            space_bytes,
+            resident_bytes,
+            used_bytes: stats.allocated_bytes,
+            free_bytes: stats.free_bytes,
+            overhead: self.overhead(),
+            fragmented_bytes: stats.free_bytes - stats.largest_free,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+    use std::vec;
+
+    use crate::{
+        linked::{Config, TopPolicy},
+        space::Fixed,
+    };
+
+    use super::*;
+
+    #[test]
+    fn footprint() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                top_policy: TopPolicy::UseWilderness,
+                ..Default::default()
+            },
+        );
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptrs = [0; 4].map(|_| unsafe { alloc.alloc(layout) });
+        unsafe { alloc.dealloc(ptrs[1], layout) }
+
+        let footprint = alloc.footprint();
+        assert_eq!(footprint.virtual_bytes, 4 << 10);
+        assert_eq!(footprint.space_bytes, 4 << 10);
+        assert_eq!(footprint.resident_bytes, None);
+        assert_eq!(
+            footprint.overhead.bins
+                + footprint.overhead.padding
+                + footprint.used_bytes
+                + footprint.free_bytes,
+            4 << 10
+        );
+        // the freed chunk is apart from the top chunk
+        let chunk = alloc.find_chunk(ptrs[1]).unwrap();
+        assert_eq!(footprint.fragmented_bytes, chunk.size);
+    }
+
+    #[cfg(all(feature = "nix", target_os = "linux"))]
+    #[test]
+    fn mmap_footprint() {
+        use crate::space::Mmap;
+
+        let mut space = Mmap::new();
+        space.set_size(64 << 10);
+        let alloc = Allocator::new(space);
+        let layout = Layout::from_size_align(16 << 10, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        unsafe { ptr.write_bytes(0xa5, layout.size()) };
+        let footprint = alloc.footprint();
+        assert!(footprint.resident_bytes.unwrap() >= 16 << 10);
+        assert!(footprint.virtual_bytes >= footprint.space_bytes);
+        unsafe { alloc.dealloc(ptr, layout) }
+    }
+}
@@ -1,8 +1,15 @@
-use std::{
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{
     alloc::{GlobalAlloc, Layout},
-    io::{Read, Write},
+    fmt::{self, Debug},
     mem::size_of,
-    vec::Vec,
+};
+#[cfg(any(feature = "std", test))]
+use std::eprintln;
+
+use crate::{
+    linked::{Allocator, CheckError},
+    Space,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,126 +19,498 @@ pub enum Method {
     Realloc { index: usize, new_size: usize },
 }
 
+// decoding parameters, so the fuzzer spends more time on realistic workloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    // relative weights of alloc, dealloc and realloc
+    pub weights: [u8; 3],
+    // decoded sizes are taken modulo this, 0 for unbounded
+    pub max_size: usize,
+    // encode sizes and indices as LEB128 instead of full little endian words, so small values
+    // take fewer bytes and random mutations tend to produce small values
+    pub varint: bool,
+}
+
+impl Profile {
+    pub const UNIFORM: Self = Self {
+        weights: [1, 1, 1],
+        max_size: 0,
+        varint: false,
+    };
+    pub const COMPACT: Self = Self {
+        weights: [1, 1, 1],
+        max_size: 0,
+        varint: true,
+    };
+    pub const ALLOC_HEAVY: Self = Self {
+        weights: [4, 1, 1],
+        max_size: 1 << 10,
+        varint: true,
+    };
+    pub const REALLOC_HEAVY: Self = Self {
+        weights: [2, 1, 4],
+        max_size: 4 << 10,
+        varint: true,
+    };
+    pub const SMALL_OBJECTS: Self = Self {
+        weights: [3, 2, 1],
+        max_size: 128,
+        varint: true,
+    };
+
+    // selected by the first byte of profiled input
+    pub const ALL: [Self; 5] = [
+        Self::UNIFORM,
+        Self::COMPACT,
+        Self::ALLOC_HEAVY,
+        Self::REALLOC_HEAVY,
+        Self::SMALL_OBJECTS,
+    ];
+
+    fn kind(&self, byte: u8) -> u8 {
+        let [alloc, dealloc, realloc] = self.weights.map(u32::from);
+        let byte = byte as u32 % (alloc + dealloc + realloc);
+        if byte < alloc {
+            0
+        } else if byte < alloc + dealloc {
+            1
+        } else {
+            2
+        }
+    }
+
+    // the smallest byte that decodes to `kind`
+    fn kind_byte(&self, kind: u8) -> u8 {
+        self.weights[..kind as usize].iter().sum()
+    }
+
+    fn size(&self, size: usize) -> usize {
+        if self.max_size == 0 {
+            size
+        } else {
+            size % self.max_size
+        }
+    }
+
+    fn read_word(&self, bytes: &mut &[u8]) -> Option<usize> {
+        if !self.varint {
+            let (word, rest) = bytes.split_first_chunk::<{ size_of::<usize>() }>()?;
+            *bytes = rest;
+            return Some(usize::from_le_bytes(*word));
+        }
+        let mut word = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = read_byte(bytes)?;
+            word |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        // otherwise too many continuation bytes, the word is truncated and the rest bytes are
+        // decoded as following methods
+        Some(word)
+    }
+
+    fn write_word(&self, bytes: &mut Vec<u8>, mut word: usize) {
+        if !self.varint {
+            bytes.extend(word.to_le_bytes());
+            return;
+        }
+        while word >= 0x80 {
+            bytes.push((word & 0x7f) as u8 | 0x80);
+            word >>= 7;
+        }
+        bytes.push(word as u8)
+    }
+}
+
+fn read_byte(bytes: &mut &[u8]) -> Option<u8> {
+    let (byte, rest) = bytes.split_first()?;
+    *bytes = rest;
+    Some(*byte)
+}
+
+type Object = Option<(*mut u8, Layout)>;
+
+// every object is filled with this, so the allocator writing metadata into user range, or
+// metadata placed where user writes go, gets caught
+const FILL: u8 = 0xa5;
+
+// sizes this close to the maximum can never be allocated, and are expected to fail gracefully
+const HUGE_SIZE: usize = isize::MAX as usize - (64 << 10);
+
+unsafe fn verify_fill(ptr: *mut u8, size: usize) {
+    let data = unsafe { core::slice::from_raw_parts(ptr, size) };
+    if let Some(offset) = data.iter().position(|byte| *byte != FILL) {
+        panic!("{ptr:?} of {size} bytes is overwritten at offset {offset}")
+    }
+}
+
 impl Method {
-    pub fn from_bytes(mut bytes: &[u8]) -> Vec<Self> {
+    pub fn from_bytes(bytes: &[u8]) -> Vec<Self> {
+        Self::from_bytes_with(bytes, &Profile::UNIFORM)
+    }
+
+    // the first byte selects the profile from `Profile::ALL`
+    pub fn from_bytes_profiled(bytes: &[u8]) -> Vec<Self> {
+        let Some((profile, bytes)) = bytes.split_first() else {
+            return Vec::new();
+        };
+        Self::from_bytes_with(bytes, &Profile::ALL[*profile as usize % Profile::ALL.len()])
+    }
+
+    pub fn from_bytes_with(mut bytes: &[u8], profile: &Profile) -> Vec<Self> {
         let mut methods = Vec::new();
-        const N: usize = size_of::<usize>();
-        let mut kind = [0; 1];
         let mut read = || {
-            bytes.read_exact(&mut kind)?;
-            match kind[0] % 3 {
+            match profile.kind(read_byte(&mut bytes)?) {
                 0 => {
-                    let mut size = [0; N];
-                    bytes.read_exact(&mut size)?;
-                    let mut log_align = [0; 1];
-                    bytes.read_exact(&mut log_align)?;
+                    let size = profile.read_word(&mut bytes)?;
+                    let log_align = read_byte(&mut bytes)?;
                     methods.push(Self::Alloc {
-                        size: usize::from_le_bytes(size),
+                        size: profile.size(size),
                         // fuzz with align up to 2048 bytes, so a 4096 block can always allocate at least once
-                        align: 1 << (log_align[0] % 11),
+                        align: 1 << (log_align % 11),
                     });
                 }
                 1 => {
-                    let mut index = [0; N];
-                    bytes.read_exact(&mut index)?;
-                    methods.push(Self::Dealloc {
-                        index: usize::from_le_bytes(index),
-                    });
+                    let index = profile.read_word(&mut bytes)?;
+                    methods.push(Self::Dealloc { index });
                 }
                 2 => {
-                    let mut index = [0; N];
-                    let mut new_size = [0; N];
-                    bytes.read_exact(&mut index)?;
-                    bytes.read_exact(&mut new_size)?;
+                    let index = profile.read_word(&mut bytes)?;
+                    let new_size = profile.read_word(&mut bytes)?;
                     methods.push(Self::Realloc {
-                        index: usize::from_le_bytes(index),
-                        new_size: usize::from_le_bytes(new_size),
+                        index,
+                        new_size: profile.size(new_size),
                     });
                 }
                 _ => unreachable!(),
             }
-            std::io::Result::Ok(())
+            Some(())
         };
-        while read().is_ok() {}
+        while read().is_some() {}
         methods
     }
 
     pub fn to_bytes(methods: &[Self]) -> Vec<u8> {
+        Self::to_bytes_with(methods, &Profile::UNIFORM)
+    }
+
+    pub fn to_bytes_profiled(methods: &[Self], profile_index: usize) -> Vec<u8> {
+        let mut bytes = alloc::vec![profile_index as u8];
+        bytes.extend(Self::to_bytes_with(methods, &Profile::ALL[profile_index]));
+        bytes
+    }
+
+    // sizes that are out of the profile's bound will not be decoded back as is
+    pub fn to_bytes_with(methods: &[Self], profile: &Profile) -> Vec<u8> {
         let mut bytes = Vec::new();
         for method in methods {
             match method {
                 Self::Alloc { size, align } => {
-                    bytes.write_all(&[0]).unwrap();
-                    bytes.write_all(&size.to_le_bytes()).unwrap();
-                    bytes.write_all(&[align.trailing_zeros() as u8]).unwrap();
+                    bytes.push(profile.kind_byte(0));
+                    profile.write_word(&mut bytes, *size);
+                    bytes.push(align.trailing_zeros() as u8);
                 }
                 Self::Dealloc { index } => {
-                    bytes.write_all(&[1]).unwrap();
-                    bytes.write_all(&index.to_le_bytes()).unwrap();
+                    bytes.push(profile.kind_byte(1));
+                    profile.write_word(&mut bytes, *index);
                 }
                 Self::Realloc { index, new_size } => {
-                    bytes.write_all(&[2]).unwrap();
-                    bytes.write_all(&index.to_le_bytes()).unwrap();
-                    bytes.write_all(&new_size.to_le_bytes()).unwrap();
+                    bytes.push(profile.kind_byte(2));
+                    profile.write_word(&mut bytes, *index);
+                    profile.write_word(&mut bytes, *new_size);
                 }
             }
         }
         bytes
     }
 
+    // allocate with alignment padding then reallocate and free the padded pointers, so the padding
+    // indicator of every alignment gets decoded, a leading allocation shifts the padding size
+    pub fn padded_sequences() -> Vec<Vec<Self>> {
+        let mut sequences = Vec::new();
+        for log_align in 4..11 {
+            let align = 1 << log_align;
+            for shift in [0, 1, 24, 40, 72] {
+                let mut methods = Vec::new();
+                if shift != 0 {
+                    methods.push(Self::Alloc {
+                        size: shift,
+                        align: 1,
+                    });
+                }
+                let index = methods.len();
+                methods.extend([
+                    Self::Alloc { size: 1, align },
+                    Self::Alloc { size: 100, align },
+                    Self::Realloc {
+                        index,
+                        new_size: 200,
+                    },
+                    Self::Dealloc { index: index + 1 },
+                    Self::Realloc { index, new_size: 8 },
+                    Self::Alloc { size: 8, align },
+                    Self::Dealloc { index },
+                ]);
+                sequences.push(methods);
+            }
+        }
+        sequences
+    }
+
     pub fn run_fuzz(methods: impl Iterator<Item = Self>, alloc: impl GlobalAlloc) {
+        Self::run_fuzz_with(methods, &alloc, |_| {})
+    }
+
+    // `after_step` is called with the index of each method after applying it
+    pub fn run_fuzz_with<A: GlobalAlloc>(
+        methods: impl Iterator<Item = Self>,
+        alloc: &A,
+        after_step: impl FnMut(usize),
+    ) {
+        Self::run_fuzz_inner(methods, alloc, false, after_step)
+    }
+
+    fn run_fuzz_inner<A: GlobalAlloc>(
+        methods: impl Iterator<Item = Self>,
+        alloc: &A,
+        zero_size: bool,
+        mut after_step: impl FnMut(usize),
+    ) {
         let mut objects = Vec::new();
 
-        for method in methods {
+        for (index, method) in methods.enumerate() {
             // println!("{method:?},");
-            match method {
-                Self::Alloc { size, align } => {
-                    let Ok(layout) = Layout::from_size_align(size, align) else {
-                        continue;
-                    };
-                    if !(1..4 << 10).contains(&size) {
-                        continue;
-                    }
-                    let ptr = unsafe { alloc.alloc(layout) };
-                    if !ptr.is_null() {
-                        objects.push(Some((ptr, layout)));
-                    }
+            if let Some(object) = method.apply(&mut objects, alloc, zero_size) {
+                objects.push(Some(object))
+            }
+            after_step(index)
+        }
+
+        // Free any remaining allocations.
+        for mut object in objects {
+            if let Some((ptr, layout)) = object.take() {
+                unsafe { alloc.dealloc(ptr, layout) }
+            }
+        }
+    }
+
+    // same as `run_fuzz_with` but without allocating, so it can run on a target whose only heap is
+    // the one under test. at most `N` objects are kept, an allocation beyond that is freed
+    // immediately, so sequences that stay within `N` objects replay exactly as with `run_fuzz_with`
+    pub fn run_fuzz_bounded<const N: usize, A: GlobalAlloc>(
+        methods: impl Iterator<Item = Self>,
+        alloc: &A,
+        mut after_step: impl FnMut(usize),
+    ) {
+        let mut objects = [None; N];
+        let mut len = 0;
+
+        for (index, method) in methods.enumerate() {
+            if let Some((ptr, layout)) = method.apply(&mut objects[..len], alloc, false) {
+                if len < N {
+                    objects[len] = Some((ptr, layout));
+                    len += 1;
+                } else {
+                    unsafe { alloc.dealloc(ptr, layout) }
                 }
-                Self::Dealloc { index } => match objects.get_mut(index).and_then(Option::take) {
-                    Some((ptr, layout)) if !ptr.is_null() => unsafe { alloc.dealloc(ptr, layout) },
-                    _ => {}
+            }
+            after_step(index)
+        }
+
+        for object in &mut objects[..len] {
+            if let Some((ptr, layout)) = object.take() {
+                unsafe { alloc.dealloc(ptr, layout) }
+            }
+        }
+    }
+
+    // fully check the heap after every method, and on failure print the methods and the heap
+    // layout to stderr before panicking, so a crash found by the fuzzer comes with a diagnosis
+    #[cfg(any(feature = "std", test))]
+    pub fn run_fuzz_checked<A: GlobalAlloc + Check>(methods: &[Self], alloc: A) {
+        let check = |step: Option<usize>| {
+            let Err(err) = alloc.check() else {
+                return;
+            };
+            match step {
+                Some(index) => eprintln!("check failed after method {index}: {err:?}"),
+                None => eprintln!("check failed after freeing remaining allocations: {err:?}"),
+            }
+            eprintln!("methods:");
+            for (index, method) in methods.iter().enumerate() {
+                eprintln!("{index:>6} {method:?},");
+            }
+            let mut layout = String::new();
+            if alloc.dump(&mut layout).is_ok() {
+                eprintln!("heap:\n{layout}");
+            }
+            panic!("{err:?}")
+        };
+        Self::run_fuzz_inner(
+            methods.iter().copied(),
+            &alloc,
+            alloc.zero_size(),
+            |index| check(Some(index)),
+        );
+        check(None)
+    }
+
+    // return the new object if one is allocated, for the caller to keep. zero sizes are skipped
+    // unless `zero_size`, and then a null or dangling pointer, which is at the address of the
+    // alignment, is not kept
+    fn apply(
+        self,
+        objects: &mut [Object],
+        alloc: &impl GlobalAlloc,
+        zero_size: bool,
+    ) -> Option<(*mut u8, Layout)> {
+        match self {
+            Self::Alloc { size, align } => {
+                let layout = Layout::from_size_align(size, align).ok()?;
+                if size >= HUGE_SIZE {
+                    assert!(unsafe { alloc.alloc(layout) }.is_null());
+                    return None;
+                }
+                if size >= 4 << 10 || size == 0 && !zero_size {
+                    return None;
+                }
+                let ptr = unsafe { alloc.alloc(layout) };
+                assert_eq!(ptr as usize % align, 0);
+                let dangling = size == 0 && ptr as usize == align;
+                if !ptr.is_null() && !dangling {
+                    unsafe { ptr.write_bytes(FILL, size) }
+                    return Some((ptr, layout));
+                }
+            }
+            Self::Dealloc { index } => match objects.get_mut(index).and_then(Option::take) {
+                Some((ptr, layout)) if !ptr.is_null() => unsafe {
+                    verify_fill(ptr, layout.size());
+                    alloc.dealloc(ptr, layout)
                 },
-                Self::Realloc { index, new_size } => {
-                    if let Some(object) = objects.get_mut(index) {
-                        match object {
-                            Some((ptr, layout)) if !ptr.is_null() => {
-                                let Ok(new_layout) = Layout::from_size_align(new_size, layout.align()) else {
-                                    continue;
-                                };
-                                if !(1..4 << 10).contains(&new_size) {
-                                    continue;
-                                }
-                                let new_ptr = unsafe { alloc.realloc(*ptr, *layout, new_size) };
-                                if !new_ptr.is_null() {
-                                    *ptr = new_ptr;
-                                    *layout = new_layout;
-                                }
+                _ => {}
+            },
+            Self::Realloc { index, new_size } => {
+                if let Some(Some((ptr, layout))) = objects.get_mut(index) {
+                    if ptr.is_null() {
+                        return None;
+                    }
+                    let new_layout = Layout::from_size_align(new_size, layout.align()).ok()?;
+                    if new_size >= HUGE_SIZE {
+                        assert!(unsafe { alloc.realloc(*ptr, *layout, new_size) }.is_null());
+                        return None;
+                    }
+                    if !(1..4 << 10).contains(&new_size) {
+                        return None;
+                    }
+                    let new_ptr = unsafe { alloc.realloc(*ptr, *layout, new_size) };
+                    if !new_ptr.is_null() {
+                        unsafe {
+                            verify_fill(new_ptr, layout.size().min(new_size));
+                            if new_size > layout.size() {
+                                new_ptr
+                                    .add(layout.size())
+                                    .write_bytes(FILL, new_size - layout.size())
                             }
-                            _ => {}
                         }
+                        *ptr = new_ptr;
+                        *layout = new_layout;
                     }
                 }
             }
         }
+        None
+    }
+}
 
-        // Free any remaining allocations.
-        for (_index, mut object) in objects.into_iter().enumerate() {
-            if let Some((ptr, layout)) = object.take() {
-                // println!("{:?}", Self::Dealloc { index });
-                unsafe { alloc.dealloc(ptr, layout) }
-            }
+type Target<'a> = Box<dyn Fn(&[Method]) + 'a>;
+
+// replay the same methods against a set of allocators, each one constructed fresh by its factory for
+// every run, so new allocators and wrapper stacks share the fuzz corpus by adding a target
+#[derive(Default)]
+pub struct Harness<'a> {
+    targets: Vec<(&'static str, Target<'a>)>,
+}
+
+impl<'a> Harness<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn target<A: GlobalAlloc>(
+        mut self,
+        name: &'static str,
+        factory: impl Fn() -> A + 'a,
+    ) -> Self {
+        self.targets.push((
+            name,
+            Box::new(move |methods| Method::run_fuzz(methods.iter().copied(), factory())),
+        ));
+        self
+    }
+
+    #[cfg(any(feature = "std", test))]
+    pub fn checked_target<A: GlobalAlloc + Check>(
+        mut self,
+        name: &'static str,
+        factory: impl Fn() -> A + 'a,
+    ) -> Self {
+        self.targets.push((
+            name,
+            Box::new(move |methods| Method::run_fuzz_checked(methods, factory())),
+        ));
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.targets.iter().map(|(name, _)| *name)
+    }
+
+    pub fn run(&self, methods: &[Method]) {
+        for (_, run) in &self.targets {
+            run(methods)
         }
     }
+
+    pub fn run_bytes(&self, bytes: &[u8]) {
+        self.run(&Method::from_bytes_profiled(bytes))
+    }
+}
+
+// allocators that can be inspected between fuzzing steps
+pub trait Check {
+    type Error: Debug;
+
+    fn check(&self) -> Result<(), Self::Error>;
+
+    fn dump(&self, out: &mut String) -> fmt::Result;
+
+    // whether allocating zero bytes is defined, so zero sizes are fuzzed too
+    fn zero_size(&self) -> bool {
+        false
+    }
+}
+
+impl<S, R> Check for Allocator<S, R>
+where
+    S: Space,
+    R: lock_api::RawMutex,
+{
+    type Error = CheckError;
+
+    fn check(&self) -> Result<(), Self::Error> {
+        Allocator::check(self)
+    }
+
+    fn dump(&self, out: &mut String) -> fmt::Result {
+        Allocator::dump(self, out)
+    }
+
+    // under any `ZeroSize`
+    fn zero_size(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -150,4 +529,130 @@ mod tests {
         ];
         assert_eq!(Method::from_bytes(&Method::to_bytes(&methods)), methods);
     }
+
+    #[test]
+    fn identity_serialization_profiled() {
+        let methods = std::vec![
+            Method::Alloc { size: 1, align: 1 },
+            Method::Alloc { size: 64, align: 8 },
+            Method::Realloc {
+                index: 0,
+                new_size: 2,
+            },
+            Method::Dealloc { index: 0 },
+            Method::Dealloc { index: 1 },
+        ];
+        for index in 0..Profile::ALL.len() {
+            assert_eq!(
+                Method::from_bytes_profiled(&Method::to_bytes_profiled(&methods, index)),
+                methods
+            );
+        }
+    }
+
+    #[test]
+    fn varint_words() {
+        let profile = Profile::COMPACT;
+        for word in [0, 1, 0x7f, 0x80, 300, 4 << 10, usize::MAX] {
+            let mut bytes = Vec::new();
+            profile.write_word(&mut bytes, word);
+            assert_eq!(profile.read_word(&mut &bytes[..]).unwrap(), word);
+        }
+        let mut bytes = Vec::new();
+        profile.write_word(&mut bytes, 100);
+        assert_eq!(bytes.len(), 1);
+        assert_eq!(
+            Method::to_bytes_with(&[Method::Dealloc { index: 1 }], &profile).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn checked_fuzz() {
+        let data = &mut *std::vec![0; 4 << 10];
+        let alloc = Allocator::new(crate::space::Fixed::from(data));
+        Method::run_fuzz_checked(&Method::padded_sequences()[0], alloc);
+    }
+
+    #[test]
+    fn zero_size_fuzz() {
+        use crate::linked::{Config, ZeroSize};
+
+        let methods = [
+            Method::Alloc { size: 0, align: 1 },
+            Method::Alloc { size: 0, align: 64 },
+            Method::Alloc { size: 8, align: 8 },
+            Method::Realloc {
+                index: 0,
+                new_size: 24,
+            },
+            Method::Dealloc { index: 1 },
+            Method::Alloc { size: 0, align: 8 },
+            Method::Dealloc { index: 0 },
+        ];
+        for zero_size in [ZeroSize::Dangling, ZeroSize::MinChunk, ZeroSize::Null] {
+            let data = &mut *std::vec![0; 4 << 10];
+            let config = Config {
+                zero_size,
+                ..Default::default()
+            };
+            let alloc = Allocator::with_config(crate::space::Fixed::from(data), config);
+            Method::run_fuzz_checked(&methods, alloc);
+        }
+    }
+
+    #[test]
+    fn bounded_fuzz() {
+        let data = &mut *std::vec![0; 4 << 10];
+        let alloc = Allocator::new(crate::space::Fixed::from(data));
+        for methods in Method::padded_sequences() {
+            Method::run_fuzz_bounded::<4, _>(methods.into_iter(), &alloc, |_| alloc.sanity_check());
+            assert_eq!(alloc.stats().allocation_count, 0);
+        }
+        let methods = [
+            Method::Alloc { size: 8, align: 1 },
+            Method::Alloc { size: 8, align: 1 },
+            Method::Dealloc { index: 1 },
+        ];
+        Method::run_fuzz_bounded::<1, _>(methods.into_iter(), &alloc, |index| {
+            assert_eq!(alloc.stats().allocation_count, 1, "after method {index}")
+        });
+        assert_eq!(alloc.stats().allocation_count, 0);
+    }
+
+    #[test]
+    fn harness() {
+        use std::{alloc::System, cell::Cell};
+
+        let runs = Cell::new(0);
+        let harness = Harness::new()
+            .target("system", || {
+                runs.set(runs.get() + 1);
+                System
+            })
+            .checked_target("linked", || {
+                runs.set(runs.get() + 1);
+                let data = std::vec![0; 4 << 10].leak();
+                Allocator::new(crate::space::Fixed::from(data))
+            });
+        assert_eq!(Vec::from_iter(harness.names()), ["system", "linked"]);
+        for methods in Method::padded_sequences() {
+            harness.run(&methods);
+        }
+        harness.run_bytes(&Method::to_bytes_profiled(
+            &Method::padded_sequences()[1],
+            2,
+        ));
+        assert_eq!(runs.get(), 2 * (Method::padded_sequences().len() + 1));
+    }
+
+    #[test]
+    fn weighted_kinds() {
+        let profile = Profile::ALLOC_HEAVY;
+        let kinds = std::vec::Vec::from_iter((0..=255).map(|byte| profile.kind(byte)));
+        let count = |kind| kinds.iter().filter(|k| **k == kind).count();
+        assert!(count(0) > 3 * count(1));
+        assert!(count(0) > 3 * count(2));
+        assert_eq!(profile.size(usize::MAX), usize::MAX % (1 << 10));
+    }
 }
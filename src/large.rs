@@ -0,0 +1,230 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    num::NonZeroUsize,
+    ptr::{copy_nonoverlapping, null_mut},
+    sync::atomic::{AtomicUsize, Ordering::Relaxed},
+};
+
+use lock_api::RawMutex;
+use nix::{
+    libc::{sysconf, _SC_PAGESIZE},
+    sys::mman::{mmap, munmap, MapFlags, ProtFlags},
+};
+
+use crate::{
+    linked::{self, DefaultLock},
+    Owns, Space,
+};
+
+// a front end mapping every allocation of `threshold` bytes or more on its own, and unmapping it
+// once freed, so a huge allocation does not grow the heap for good. the mappings are whole pages
+// and take a system call each, so the threshold is best well above a page, e.g. 128 KB as in
+// glibc. the smaller allocations go to the heap, and so do the larger ones aligned to more than a
+// page or that cannot be mapped
+pub struct Allocator<S, R = DefaultLock> {
+    heap: linked::Allocator<S, R>,
+    threshold: usize,
+    mapped_count: AtomicUsize,
+    mapped_bytes: AtomicUsize,
+}
+
+fn page_size() -> usize {
+    usize::try_from(unsafe { sysconf(_SC_PAGESIZE) }).unwrap()
+}
+
+// the length of the mapping of `size` bytes
+fn mapping_len(size: usize) -> usize {
+    size.next_multiple_of(page_size())
+}
+
+impl<S, R> Allocator<S, R>
+where
+    R: RawMutex,
+{
+    // e.g. `static GLOBAL: large::Allocator<Mmap> = large::Allocator::new(Allocator::lazy(..), ..)`
+    pub const fn new(heap: linked::Allocator<S, R>, threshold: usize) -> Self {
+        assert!(threshold != 0);
+        Self {
+            heap,
+            threshold,
+            mapped_count: AtomicUsize::new(0),
+            mapped_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn heap(&self) -> &linked::Allocator<S, R> {
+        &self.heap
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    // the allocations mapped on their own, and the bytes of their mappings
+    pub fn mapped(&self) -> (usize, usize) {
+        (
+            self.mapped_count.load(Relaxed),
+            self.mapped_bytes.load(Relaxed),
+        )
+    }
+
+    fn is_large(&self, layout: Layout) -> bool {
+        layout.size() >= self.threshold && layout.align() <= page_size()
+    }
+
+    // null if it cannot be mapped
+    fn map(&self, size: usize) -> *mut u8 {
+        let len = mapping_len(size);
+        let Ok(addr) = (unsafe {
+            mmap(
+                None,
+                NonZeroUsize::new(len).unwrap(),
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        }) else {
+            return null_mut();
+        };
+        self.mapped_count.fetch_add(1, Relaxed);
+        self.mapped_bytes.fetch_add(len, Relaxed);
+        addr.cast()
+    }
+
+    unsafe fn unmap(&self, ptr: *mut u8, size: usize) {
+        let len = mapping_len(size);
+        unsafe { munmap(ptr.cast(), len) }.unwrap();
+        self.mapped_count.fetch_sub(1, Relaxed);
+        self.mapped_bytes.fetch_sub(len, Relaxed);
+    }
+
+    // moving the pages instead of copying them if there is no room after the mapping
+    #[cfg(target_os = "linux")]
+    unsafe fn remap(&self, ptr: *mut u8, size: usize, new_size: usize) -> *mut u8 {
+        use nix::sys::mman::{mremap, MRemapFlags};
+
+        let (len, new_len) = (mapping_len(size), mapping_len(new_size));
+        let Ok(addr) =
+            (unsafe { mremap(ptr.cast(), len, new_len, MRemapFlags::MREMAP_MAYMOVE, None) })
+        else {
+            return null_mut();
+        };
+        self.mapped_bytes.fetch_add(new_len, Relaxed);
+        self.mapped_bytes.fetch_sub(len, Relaxed);
+        addr.cast()
+    }
+
+    // without `mremap` the allocation is copied into a new mapping instead
+    #[cfg(not(target_os = "linux"))]
+    unsafe fn remap(&self, _: *mut u8, _: usize, _: usize) -> *mut u8 {
+        null_mut()
+    }
+}
+
+impl<S, R> Allocator<S, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    // whether a large allocation is mapped on its own rather than in the heap, which it falls back
+    // to when mapping fails
+    fn is_mapped(&self, ptr: *mut u8, layout: Layout) -> bool {
+        self.is_large(layout) && !self.heap.owns(ptr)
+    }
+}
+
+unsafe impl<S, R> GlobalAlloc for Allocator<S, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if self.is_large(layout) {
+            let ptr = self.map(layout.size());
+            if !ptr.is_null() {
+                return ptr;
+            }
+        }
+        unsafe { self.heap.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if self.is_mapped(ptr, layout) {
+            unsafe { self.unmap(ptr, layout.size()) }
+        } else {
+            unsafe { self.heap.dealloc(ptr, layout) }
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align()) else {
+            return null_mut();
+        };
+        match (self.is_mapped(ptr, layout), self.is_large(new_layout)) {
+            (false, false) => return unsafe { self.heap.realloc(ptr, layout, new_size) },
+            (true, true) => {
+                let new_ptr = unsafe { self.remap(ptr, layout.size(), new_size) };
+                if !new_ptr.is_null() {
+                    return new_ptr;
+                }
+            }
+            _ => {}
+        }
+        let new_ptr = unsafe { self.alloc(new_layout) };
+        if !new_ptr.is_null() {
+            unsafe {
+                copy_nonoverlapping(ptr, new_ptr, usize::min(layout.size(), new_size));
+                self.dealloc(ptr, layout)
+            }
+        }
+        new_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use crate::space::Fixed;
+
+    use super::*;
+
+    #[test]
+    fn mapped() {
+        let data = &mut *vec![0; 16 << 10];
+        let alloc = Allocator::new(linked::Allocator::new(Fixed::from(data)), 64 << 10);
+        let layout = Layout::from_size_align(1 << 20, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert!(!ptr.is_null());
+        assert!(!alloc.heap().owns(ptr));
+        assert_eq!(alloc.mapped(), (1, 1 << 20));
+        unsafe { ptr.write_bytes(0xa5, 1 << 20) };
+
+        // remapped while large, into the heap once small
+        let ptr = unsafe { alloc.realloc(ptr, layout, 3 << 20) };
+        assert!(!ptr.is_null());
+        assert_eq!(alloc.mapped(), (1, 3 << 20));
+        assert!((0..1 << 20)
+            .step_by(4096)
+            .all(|offset| unsafe { *ptr.add(offset) } == 0xa5));
+        let layout = Layout::from_size_align(3 << 20, 8).unwrap();
+        let ptr = unsafe { alloc.realloc(ptr, layout, 100) };
+        assert!(alloc.heap().owns(ptr));
+        assert_eq!(alloc.mapped(), (0, 0));
+        assert!((0..100).all(|offset| unsafe { *ptr.add(offset) } == 0xa5));
+
+        // and mapped again
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptr = unsafe { alloc.realloc(ptr, layout, 64 << 10) };
+        assert!(!alloc.heap().owns(ptr));
+        assert_eq!(alloc.heap().stats().allocation_count, 0);
+        unsafe { alloc.dealloc(ptr, Layout::from_size_align(64 << 10, 8).unwrap()) };
+        assert_eq!(alloc.mapped(), (0, 0));
+
+        // more aligned than a page
+        let layout = Layout::from_size_align(64 << 10, 2 * page_size()).unwrap();
+        assert!(unsafe { alloc.alloc(layout) }.is_null());
+        assert_eq!(alloc.heap().check(), Ok(()));
+    }
+}
@@ -1,18 +1,53 @@
 #![no_std]
 #![warn(unsafe_op_in_unsafe_fn)]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 
-
+pub mod arenas;
+pub mod buddy;
+pub mod builder;
+pub mod bump;
+pub mod clock;
+pub mod compaction;
+pub mod dump;
+pub mod entropy;
+pub mod expiry;
+pub mod footprint;
 pub mod linked;
+pub mod owns;
+pub mod prelude;
+pub mod queue;
+pub mod registry;
+pub mod seal;
+pub mod single;
+pub mod small;
 pub mod space;
+pub mod testing;
+pub mod tlsf;
 
+pub use builder::Builder;
+pub use owns::Owns;
 pub use space::Space;
 
-#[cfg(any(feature = "std", test))]
+#[cfg(any(feature = "allocator-api", feature = "allocator-api2"))]
+mod allocator_api;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(feature = "critical-section")]
+pub mod critical;
+#[cfg(any(feature = "alloc", test))]
 pub mod fuzz;
+#[cfg(feature = "nix")]
+pub mod large;
+#[cfg(any(feature = "std", test))]
+pub mod snapshot;
 #[cfg(feature = "switchable")]
 pub mod switchable;
+#[cfg(feature = "std")]
+pub mod trimmer;
 #[cfg(feature = "switchable")]
 pub use switchable::Switchable;
 
+#[cfg(any(feature = "alloc", test))]
+extern crate alloc;
 #[cfg(any(feature = "std", feature = "switchable", test))]
 extern crate std;
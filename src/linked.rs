@@ -1,23 +1,87 @@
 #![cfg_attr(
-    not(any(dev, test, feature = "paranoid")),
+    not(any(dev, test, feature = "check-bounds")),
     allow(clippy::unit_arg, clippy::unit_cmp)
 )]
 
 use core::{
     alloc::{GlobalAlloc, Layout},
     fmt::Debug,
-    ptr::{copy_nonoverlapping, null_mut, NonNull},
+    iter::FusedIterator,
+    ptr::{copy, copy_nonoverlapping, null_mut, without_provenance_mut, NonNull},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed},
 };
 
-use spin::{Mutex, MutexGuard};
+use lock_api::{Mutex, MutexGuard, RawMutex};
 
-use crate::Space;
+#[cfg(feature = "mmap-stats")]
+use crate::space::SyscallStats;
+use crate::{space::Segments, Owns, Space};
 
-#[cfg(any(dev, test, feature = "paranoid"))]
+// debug assertions that read the heap, each kind is compiled out without its `check-*` feature
+// even if debug assertions are on, so the hot path of such a build does not touch any memory for
+// checking. `paranoid` turns on all of them
+// bounds: chunks stay inside the heap they come from, cheap since no memory is read
+// chunks: header bits and the initialization marker, read on every operation
+// poison: freed chunks are filled and the filling is verified when they are reused
+macro_rules! check_assert {
+    (bounds, $($arg:tt)*) => {
+        #[cfg(any(dev, test, feature = "check-bounds"))]
+        debug_assert!($($arg)*)
+    };
+    (chunks, $($arg:tt)*) => {
+        #[cfg(any(dev, test, feature = "check-chunks"))]
+        debug_assert!($($arg)*)
+    };
+    (poison, $($arg:tt)*) => {
+        #[cfg(any(dev, test, feature = "check-poison"))]
+        debug_assert!($($arg)*)
+    };
+}
+
+macro_rules! check_assert_eq {
+    (bounds, $($arg:tt)*) => {
+        #[cfg(any(dev, test, feature = "check-bounds"))]
+        debug_assert_eq!($($arg)*)
+    };
+    (chunks, $($arg:tt)*) => {
+        #[cfg(any(dev, test, feature = "check-chunks"))]
+        debug_assert_eq!($($arg)*)
+    };
+}
+
+#[cfg(any(dev, test, feature = "check-bounds"))]
 type ChunkLimit = NonNull<u8>;
-#[cfg(not(any(dev, test, feature = "paranoid")))]
+#[cfg(not(any(dev, test, feature = "check-bounds")))]
 type ChunkLimit = ();
 
+// bytes reserved for the embedder next to every allocation, see `Allocator::metadata_of`. the
+// larger one is taken if more than one `metadata-*` feature is selected
+#[cfg(feature = "metadata-16")]
+pub const METADATA_SIZE: usize = 16;
+#[cfg(all(feature = "metadata-8", not(feature = "metadata-16")))]
+pub const METADATA_SIZE: usize = 8;
+#[cfg(not(any(feature = "metadata-8", feature = "metadata-16")))]
+pub const METADATA_SIZE: usize = 0;
+
+// every link in the heap, i.e. the bins and the links of free and cached chunks, is kept as the
+// offset of its target from the link itself, so the heap stays valid wherever its space is, e.g.
+// after moving to grow or copied. zero is null, as nothing links to its own link
+unsafe fn read_link(link: *const *mut u8) -> *mut u8 {
+    match unsafe { link.cast::<usize>().read() } {
+        0 => null_mut(),
+        offset => link.cast::<u8>().cast_mut().wrapping_add(offset),
+    }
+}
+
+unsafe fn write_link(link: *mut *mut u8, target: *mut u8) {
+    let offset = if target.is_null() {
+        0
+    } else {
+        (target as usize).wrapping_sub(link as usize)
+    };
+    unsafe { link.cast::<usize>().write(offset) }
+}
+
 // invariants:
 // chunk.ptr < chunk.limit (to be exact, chunk.ptr + CHUNK::MIN_SIZE <= chunk.limit)
 // if chunk1 and chunk2 belong to the same heap, then chunk1.limit == chunk2.limit
@@ -31,15 +95,30 @@ impl Chunk {
     const META_MASK: u64 = 0x7;
     const IN_USE_BIT: u32 = 0;
     const LOWER_IN_USE_BIT: u32 = 1;
+    // a free chunk is clean if its bytes besides the header, the links and the footer are zero,
+    // e.g. carved from what the space grew with, and the remain of splitting it is clean too. an
+    // in-use chunk keeps the bit of the free one it is taken from, which is only meaningful right
+    // after the allocation, and every chunk that is freed is not clean
+    const CLEAN_BIT: u32 = 2;
+
+    // the id of an in-use chunk lives in its last 8 bytes, where the size footer goes after the
+    // chunk is freed, and the embedder's metadata right before it
+    #[cfg(feature = "alloc-id")]
+    const ID_SIZE: usize = 8;
+    #[cfg(not(feature = "alloc-id"))]
+    const ID_SIZE: usize = 0;
+    const TRAILER_SIZE: usize = Self::ID_SIZE + METADATA_SIZE;
 
     // overhead of in-use chunk
-    const META_SIZE: usize = 8;
+    const META_SIZE: usize = 8 + Self::TRAILER_SIZE;
     // 8 bytes prev, 8 bytes next, 8 bytes size
     const MIN_SIZE: usize = Self::META_SIZE + 24;
 
+    #[cfg(any(dev, test, feature = "check-poison"))]
+    const POISON: u8 = 0xdf;
+
     fn new(data: NonNull<u8>, limit: ChunkLimit) -> Self {
-        #[cfg(any(dev, test, feature = "paranoid"))]
-        debug_assert!(data < limit, "expect {data:?} < {limit:?}");
+        check_assert!(bounds, data < limit, "expect {data:?} < {limit:?}");
         Self { data, limit }
     }
 
@@ -59,47 +138,74 @@ impl Chunk {
             | ((lower_in_use as u64) << Self::LOWER_IN_USE_BIT);
     }
 
+    unsafe fn get_clean(&self) -> bool {
+        let meta = unsafe { *self.data.cast::<u64>().as_ref() };
+        meta & (1 << Self::CLEAN_BIT) != 0
+    }
+
+    unsafe fn set_clean(&mut self, clean: bool) {
+        let meta = unsafe { self.data.cast::<u64>().as_mut() };
+        *meta = (*meta & !(1 << Self::CLEAN_BIT)) | ((clean as u64) << Self::CLEAN_BIT);
+    }
+
     unsafe fn get_size(&self) -> usize {
         let meta = unsafe { *self.data.cast::<u64>().as_ref() };
         (meta & !Self::META_MASK) as _
     }
 
     unsafe fn set_in_use_and_size(&mut self, in_use: bool, size: usize) {
+        let prev_in_use = unsafe { self.get_in_use() };
+        unsafe { self.write_in_use_and_size(in_use, size) }
+        if prev_in_use || in_use || unsafe { !self.is_top() } {
+            unsafe { self.get_higher_chunk().set_lower_in_use(in_use) }
+        }
+    }
+
+    // without updating the higher chunk
+    unsafe fn write_in_use_and_size(&mut self, in_use: bool, size: usize) {
         debug_assert!(size >= Self::MIN_SIZE);
         debug_assert_eq!(size as u64 & Self::META_MASK, 0);
-        let prev_in_use = unsafe { self.get_in_use() };
         let meta = unsafe { self.data.cast::<u64>().as_mut() };
         *meta = (*meta & !(1 << Self::IN_USE_BIT)) | ((in_use as u64) << Self::IN_USE_BIT);
         *meta = (*meta & Self::META_MASK) | (size as u64);
-        if prev_in_use || in_use || unsafe { !self.is_top() } {
-            unsafe { self.get_higher_chunk().set_lower_in_use(in_use) }
-        }
         if !in_use {
             // not necessary for a chunk that is about to be allocated, hope not too expensive
             unsafe { *self.data.as_ptr().add(size - 8).cast::<u64>() = size as _ }
         }
     }
 
+    // write metadata for a free chunk carved out from memory that does not belong to any chunk yet,
+    // so nothing is read from it and the higher chunk is left untouched
+    // the lower chunk is considered free, `set_lower_in_use` afterwards if it is not
+    unsafe fn init_free(&mut self, size: usize) {
+        debug_assert!(size >= Self::MIN_SIZE);
+        debug_assert_eq!(size as u64 & Self::META_MASK, 0);
+        unsafe {
+            *self.data.cast::<u64>().as_mut() = size as _;
+            *self.data.as_ptr().add(size - 8).cast::<u64>() = size as _;
+        }
+    }
+
     unsafe fn get_prev(&self) -> Option<Self> {
-        debug_assert!(unsafe { !self.get_in_use() });
-        NonNull::new(unsafe { *(self.data.as_ptr().offset(8).cast::<*mut u8>()) })
+        check_assert!(chunks, unsafe { !self.get_in_use() });
+        NonNull::new(unsafe { read_link(self.data.as_ptr().add(8).cast()) })
             .map(|data| Self::new(data, self.limit))
     }
 
     unsafe fn set_prev(&mut self, prev: Option<Self>) {
-        debug_assert!(unsafe { !self.get_in_use() });
+        check_assert!(chunks, unsafe { !self.get_in_use() });
         let prev = prev
             .map(|chunk| {
-                debug_assert_eq!(chunk.limit, self.limit);
+                check_assert_eq!(bounds, chunk.limit, self.limit);
                 chunk.data.as_ptr()
             })
             .unwrap_or_else(null_mut);
-        unsafe { *(self.data.as_ptr().offset(8).cast::<*mut u8>()) = prev }
+        unsafe { write_link(self.data.as_ptr().add(8).cast(), prev) }
     }
 
     unsafe fn get_next(&self) -> Option<Self> {
-        debug_assert!(unsafe { !self.get_in_use() });
-        NonNull::new(unsafe { *(self.data.as_ptr().offset(16).cast::<*mut u8>()) })
+        check_assert!(chunks, unsafe { !self.get_in_use() });
+        NonNull::new(unsafe { read_link(self.data.as_ptr().add(16).cast()) })
             .map(|data| Self::new(data, self.limit))
     }
 
@@ -110,29 +216,61 @@ impl Chunk {
     // `Chunk` does not have necessary state to sanity check on this, consider to find a way for
     // this if necessary
     unsafe fn set_next(&mut self, next: Option<Self>) {
-        debug_assert!(unsafe { !self.get_in_use() });
+        check_assert!(chunks, unsafe { !self.get_in_use() });
         let next = next
             .map(|chunk| {
-                debug_assert_eq!(chunk.limit, self.limit);
+                check_assert_eq!(bounds, chunk.limit, self.limit);
                 chunk.data.as_ptr()
             })
             .unwrap_or_else(null_mut);
-        unsafe { *(self.data.as_ptr().offset(16).cast::<*mut u8>()) = next }
+        unsafe { write_link(self.data.as_ptr().add(16).cast(), next) }
+    }
+
+    // the bytes of a free chunk between the links and the footer
+    #[cfg(any(dev, test, feature = "check-poison"))]
+    unsafe fn poison_range(&self) -> *mut [u8] {
+        core::ptr::slice_from_raw_parts_mut(unsafe { self.data.as_ptr().add(24) }, unsafe {
+            self.get_size() - 32
+        })
+    }
+
+    // a clean chunk is filled with zero instead, which it holds already
+    #[cfg(any(dev, test, feature = "check-poison"))]
+    unsafe fn poison_byte(&self) -> u8 {
+        if unsafe { self.get_clean() } {
+            0
+        } else {
+            Self::POISON
+        }
+    }
+
+    #[cfg(any(dev, test, feature = "check-poison"))]
+    unsafe fn poison(&mut self) {
+        unsafe { (*self.poison_range()).fill(self.poison_byte()) }
+    }
+
+    #[cfg(any(dev, test, feature = "check-poison"))]
+    unsafe fn is_poisoned(&self) -> bool {
+        let byte = unsafe { self.poison_byte() };
+        unsafe { (*self.poison_range()).iter().all(|b| *b == byte) }
     }
 
     unsafe fn is_top(&self) -> bool {
         // may be a little bit limited, but always safe first
-        debug_assert!(unsafe { !self.get_in_use() });
+        check_assert!(chunks, unsafe { !self.get_in_use() });
         // define the top (i.e. highest) chunk to have the largest size, so it is always also the
         // last chunk and has no next chunk
         unsafe { self.get_next().is_none() }
     }
 
     unsafe fn get_user_data(&self, layout: Layout) -> Option<NonNull<u8>> {
-        // or .add(Self::META_SIZE)
         let addr = unsafe { self.data.as_ptr().offset(8) };
         let align_offset = addr.align_offset(layout.align());
-        if layout.size() + align_offset > unsafe { self.get_size() } - Self::META_SIZE {
+        if layout
+            .size()
+            .checked_add(align_offset)
+            .is_none_or(|size| size > unsafe { self.get_size() } - Self::META_SIZE)
+        {
             None
         } else {
             Some(NonNull::new(unsafe { addr.add(align_offset) }).unwrap())
@@ -140,17 +278,26 @@ impl Chunk {
     }
 
     unsafe fn from_user_data(user_data: *mut u8, layout: Layout, limit: ChunkLimit) -> Self {
+        if layout.align() <= 8 {
+            let chunk = Self::new(
+                NonNull::new(unsafe { user_data.offset(-8) }).unwrap(),
+                limit,
+            );
+            check_assert!(chunks, unsafe { chunk.get_in_use() });
+            return chunk;
+        }
+        unsafe { Self::from_any_user_data(user_data, limit) }
+    }
+
+    // without knowing whether there is alignment padding
+    unsafe fn from_any_user_data(user_data: *mut u8, limit: ChunkLimit) -> Self {
         let mut chunk = Self::new(
             NonNull::new(unsafe { user_data.offset(-8) }).unwrap(),
             limit,
         );
-        if layout.align() <= 8 {
-            debug_assert!(unsafe { chunk.get_in_use() });
-            return chunk;
-        }
         if unsafe { !chunk.get_in_use() } {
             // alignment padding indicator, which should set all meta bits to 0
-            debug_assert!(!unsafe { chunk.get_lower_in_use() });
+            check_assert!(chunks, !unsafe { chunk.get_lower_in_use() });
             // data can only decrease so will not be over limit after this
             chunk.data =
                 NonNull::new(unsafe { chunk.data.as_ptr().sub(chunk.get_size()) }).unwrap();
@@ -180,7 +327,7 @@ impl Chunk {
         }
         // println!("new size {new_size}");
         let remain_size = unsafe {
-            debug_assert!(self.get_size() >= new_size);
+            check_assert!(chunks, self.get_size() >= new_size);
             self.get_size() - new_size
         };
 
@@ -192,13 +339,30 @@ impl Chunk {
                 self.limit,
             );
             unsafe {
-                remain.set_in_use_and_size(false, remain_size);
+                // splitting the top chunk, the remain is the new top chunk and has no higher chunk
+                let top = !self.get_in_use() && self.is_top();
+                remain.init_free(remain_size);
+                remain.set_clean(!self.get_in_use() && self.get_clean());
+                if !top {
+                    remain.get_higher_chunk().set_lower_in_use(false);
+                }
                 self.set_in_use_and_size(self.get_in_use(), new_size);
             }
             Some(remain)
         }
     }
 
+    // a little duplication to `split`
+    unsafe fn set_padding(&self, user_data: NonNull<u8>) {
+        let padding = unsafe { user_data.as_ptr().offset(-8) };
+        let padding_size = unsafe { padding.offset_from(self.data.as_ptr()) } as usize;
+        if padding_size != 0 {
+            // println!("padding size {padding_size}");
+            debug_assert_eq!(padding_size as u64 & Self::META_MASK, 0); // so the line below also clear meta bits
+            unsafe { *padding.cast::<u64>() = padding_size as _ }
+        }
+    }
+
     unsafe fn get_free_lower_chunk(&self) -> Option<Self> {
         if unsafe { self.get_lower_in_use() } {
             None
@@ -229,12 +393,449 @@ impl Chunk {
     }
 
     unsafe fn coalesce(&mut self, chunk: Self) {
-        debug_assert_eq!(unsafe { self.get_free_higher_chunk() }, Some(chunk));
-        unsafe { self.set_in_use_and_size(self.get_in_use(), self.get_size() + chunk.get_size()) }
+        check_assert_eq!(chunks, unsafe { self.get_free_higher_chunk() }, Some(chunk));
+        // a just freed `self` is not linked yet so its next pointer cannot tell whether it is the
+        // top chunk, but neither of the two chunks can be the top chunk here, so always update the
+        // higher chunk
+        unsafe {
+            let in_use = self.get_in_use();
+            self.write_in_use_and_size(in_use, self.get_size() + chunk.get_size());
+            self.set_clean(false);
+            self.get_higher_chunk().set_lower_in_use(in_use)
+        }
+    }
+
+    #[cfg(feature = "alloc-id")]
+    unsafe fn get_id(&self) -> u64 {
+        check_assert!(chunks, unsafe { self.get_in_use() });
+        unsafe { *self.data.as_ptr().add(self.get_size() - 8).cast() }
+    }
+
+    #[cfg(feature = "alloc-id")]
+    unsafe fn set_id(&mut self, id: u64) {
+        check_assert!(chunks, unsafe { self.get_in_use() });
+        unsafe { *self.data.as_ptr().add(self.get_size() - 8).cast() = id }
+    }
+
+    unsafe fn get_metadata(&self) -> NonNull<[u8; METADATA_SIZE]> {
+        check_assert!(chunks, unsafe { self.get_in_use() });
+        let size = unsafe { self.get_size() };
+        unsafe { self.data.add(size - Self::TRAILER_SIZE) }.cast()
+    }
+
+    // the trailer goes with an in-use chunk that is resized or moved
+    unsafe fn get_trailer(&self) -> [u8; Self::TRAILER_SIZE] {
+        check_assert!(chunks, unsafe { self.get_in_use() });
+        unsafe {
+            *self
+                .data
+                .as_ptr()
+                .add(self.get_size() - Self::TRAILER_SIZE)
+                .cast()
+        }
+    }
+
+    unsafe fn set_trailer(&mut self, trailer: [u8; Self::TRAILER_SIZE]) {
+        check_assert!(chunks, unsafe { self.get_in_use() });
+        unsafe {
+            *self
+                .data
+                .as_ptr()
+                .add(self.get_size() - Self::TRAILER_SIZE)
+                .cast() = trailer
+        }
+    }
+
+    // the next chunk in address order, `None` for the top chunk
+    unsafe fn get_walk_next(&self) -> Option<Self> {
+        if unsafe { self.get_in_use() || !self.is_top() } {
+            Some(unsafe { self.get_higher_chunk() })
+        } else {
+            None
+        }
+    }
+
+    unsafe fn info(&self) -> ChunkInfo {
+        let in_use = unsafe { self.get_in_use() };
+        ChunkInfo {
+            addr: self.data.as_ptr(),
+            size: unsafe { self.get_size() },
+            in_use,
+            #[cfg(feature = "alloc-id")]
+            id: if in_use {
+                Some(unsafe { self.get_id() })
+            } else {
+                None
+            },
+        }
+    }
+}
+
+// public view of a chunk, the bounds include the chunk's metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkInfo {
+    pub addr: *mut u8,
+    pub size: usize,
+    pub in_use: bool,
+    // allocation ids are assigned in allocating order and kept across reallocation
+    #[cfg(feature = "alloc-id")]
+    pub id: Option<u64>,
+}
+
+impl ChunkInfo {
+    pub fn contains(&self, addr: *const u8) -> bool {
+        (self.addr as *const u8..self.addr.wrapping_add(self.size)).contains(&addr)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TopPolicy {
+    // the top chunk stays minimal and is never allocated, growing appends a new top chunk
+    #[cfg_attr(not(feature = "profile-tiny"), default)]
+    KeepWilderness,
+    // allocate from the top chunk and coalesce freed chunks into it, growing extends it in place
+    #[cfg_attr(feature = "profile-tiny", default)]
+    UseWilderness,
+}
+
+// which free chunk an allocation is placed in. the bins are maintained the same under both, they
+// are just not searched by `FirstFit`, so freeing, coalescing, stats and checks do not change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Placement {
+    // the smallest fitting chunk found through the bins, exact bins first then the sorted list
+    #[default]
+    BestFit,
+    // the lowest fitting chunk by address, which tends to keep long-lived allocations low and the
+    // free space together at the top. allocating walks the heap from the start, in-use chunks
+    // included, so it costs more the more chunks there are
+    FirstFit,
+}
+
+// how the free chunks too large for the exact bins are binned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Bins {
+    // a bin per size class into the sorted list, so allocating a large chunk skips the smaller ones
+    #[cfg_attr(not(any(feature = "minimal", feature = "profile-tiny")), default)]
+    Sorted,
+    // a single bin for the whole sorted list, for heaps of a few KB where the sorted list is short
+    // anyway. the bins take 272 bytes instead of 784
+    #[cfg_attr(any(feature = "minimal", feature = "profile-tiny"), default)]
+    Compact,
+}
+
+// selected at construction and kept in the heap, so it survives `clone_heap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub top_policy: TopPolicy,
+    pub placement: Placement,
+    // the user data of the lowest chunk starts at an address aligned to this, by padding after the
+    // bins. a power of two, a cache line by default so the first allocations do not share lines
+    // with the bins
+    pub start_align: usize,
+    pub bins: Bins,
+    // keep freed chunks of up to about 100 bytes aside per size, uncoalesced and still in use, to
+    // hand them out again first, like the fast bins of dlmalloc. they go to the bins once more than
+    // a few dozen are kept, before the heap grows, and before walking or trimming the heap. off by
+    // default unless `profile-fast`, and compiled out with `minimal`
+    pub fast_bins: bool,
+    // keep every other freed chunk aside in use too, until allocating runs out of free chunks or
+    // `Allocator::consolidate`, so freeing does not look at the neighbors. the heap is coalesced
+    // in one go instead, and only the last freed chunk is reused before, by an allocation of its
+    // exact size. off by default, and compiled out with `minimal`
+    pub deferred_coalescing: bool,
+    pub zero_size: ZeroSize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            top_policy: Default::default(),
+            placement: Default::default(),
+            bins: Default::default(),
+            fast_bins: cfg!(feature = "profile-fast") && !cfg!(feature = "profile-tiny"),
+            deferred_coalescing: false,
+            zero_size: Default::default(),
+            start_align: if cfg!(feature = "profile-tiny") {
+                8
+            } else {
+                64
+            },
+        }
+    }
+}
+
+// what allocating zero bytes returns, which `GlobalAlloc` leaves undefined while callers across
+// FFI expect one thing or another, e.g. `malloc(0)` may return null or a pointer to free
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZeroSize {
+    // the address of the alignment, which is never in the heap and must not be freed
+    #[default]
+    Dangling,
+    // a chunk of the minimum size, so every pointer is distinct and can be freed and reallocated
+    MinChunk,
+    // null, as if the heap were out of memory
+    Null,
+}
+
+// limits of a single operation for real-time callers, `scan` is the number of free chunks to look
+// into after the first one, and `copy` the bytes to copy when reallocating cannot stay in place.
+// growing the space is not limited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub scan: usize,
+    pub copy: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetError {
+    // nothing is changed, the operation can be retried with a larger budget or later
+    Exceeded,
+    OutOfMemory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTestError {
+    Check(CheckError),
+    // the content of an allocation is changed by operations on other allocations
+    Pattern(*mut u8),
+}
+
+// why a heap cannot be created in a space, by `Allocator::try_new`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewError {
+    // the space does not start at a multiple of 8
+    Misaligned(*mut u8),
+    // the length of the space is not a multiple of 8
+    Length(usize),
+    // no room for the bins and two chunks, `min` is the least length at the address of the space
+    TooSmall { len: usize, min: usize },
+    // `Config::start_align` is not a power of two
+    StartAlign(usize),
+}
+
+// the first broken invariant found by `Allocator::check`, with the address of the chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckError {
+    // the initialization marker is overwritten
+    Marker,
+    // the heap is created by a simpile version or with features of another layout
+    Version { version: u8, flags: u8 },
+    // the configuration slot holds an unknown or compiled out policy, or the start chunk out of the
+    // heap
+    Config,
+    // the size is not aligned, too small, or overflows the heap
+    ChunkSize(*mut u8),
+    // the lower in use bit disagrees with the lower chunk
+    LowerInUse(*mut u8),
+    // the size footer of a free chunk disagrees with its size
+    Footer(*mut u8),
+    // two free chunks next to each other, the top chunk excluded
+    Uncoalesced(*mut u8),
+    // the highest chunk is not a minimum sized free chunk without next chunk
+    Top(*mut u8),
+    // dangling or inconsistent link, or the list does not contain exactly all the free chunks
+    FreeList(*mut u8),
+    // the bin does not point to the first chunk of its size class
+    Bin(usize),
+    // a free chunk is written between its links and its footer, after it is freed or by a flipped
+    // bit, only found by `Allocator::scrub` when the free chunks are poisoned
+    Poison(*mut u8),
+}
+
+// where `Allocator::scrub` goes on, the start of the heap first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScrubCursor(usize);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub allocated_bytes: usize,
+    pub allocation_count: usize,
+    pub free_bytes: usize,
+    pub free_count: usize,
+    pub largest_free: usize,
+    // the sizes requested by the live allocations, and the most they have summed to, counted as
+    // the operations go instead of walking the heap, so a heap taken over by `attach` starts from 0
+    pub requested_bytes: usize,
+    pub peak_requested_bytes: usize,
+    pub ops: OpCounts,
+    #[cfg(feature = "mmap-stats")]
+    pub syscalls: SyscallStats,
+}
+
+impl Stats {
+    fn add_chunk(&mut self, info: ChunkInfo) {
+        if info.in_use {
+            self.allocated_bytes += info.size;
+            self.allocation_count += 1;
+        } else {
+            self.free_bytes += info.size;
+            self.free_count += 1;
+            self.largest_free = usize::max(self.largest_free, info.size);
+        }
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        self.allocated_bytes += other.allocated_bytes;
+        self.allocation_count += other.allocation_count;
+        self.free_bytes += other.free_bytes;
+        self.free_count += other.free_count;
+        self.largest_free = usize::max(self.largest_free, other.largest_free);
+        self.requested_bytes += other.requested_bytes;
+        // the heaps do not peak at the same time, so this is an upper bound
+        self.peak_requested_bytes += other.peak_requested_bytes;
+        self.ops.alloc += other.ops.alloc;
+        self.ops.dealloc += other.ops.dealloc;
+        self.ops.realloc += other.ops.realloc;
+        self.ops.failed += other.ops.failed;
+        self.ops.foreign += other.ops.foreign;
+        #[cfg(feature = "mmap-stats")]
+        self.syscalls.merge(other.syscalls);
+    }
+}
+
+// operations through `GlobalAlloc` and the budgeted ones since the heap is created, a failed
+// operation is counted in its kind and in `failed`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpCounts {
+    pub alloc: usize,
+    pub dealloc: usize,
+    pub realloc: usize,
+    pub failed: usize,
+    // pointers from elsewhere given to `dealloc` or `realloc`, counted in neither, see
+    // `ForeignPolicy`
+    pub foreign: usize,
+}
+
+#[derive(Debug)]
+struct Counters {
+    alloc: AtomicUsize,
+    dealloc: AtomicUsize,
+    realloc: AtomicUsize,
+    failed: AtomicUsize,
+    foreign: AtomicUsize,
+    requested_bytes: AtomicUsize,
+    peak_requested_bytes: AtomicUsize,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            alloc: AtomicUsize::new(0),
+            dealloc: AtomicUsize::new(0),
+            realloc: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            foreign: AtomicUsize::new(0),
+            requested_bytes: AtomicUsize::new(0),
+            peak_requested_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    fn request(&self, size: usize) {
+        let requested = self.requested_bytes.fetch_add(size, Relaxed) + size;
+        self.peak_requested_bytes.fetch_max(requested, Relaxed);
+    }
+
+    fn alloc(&self, size: usize, user_data: *mut u8) {
+        self.alloc.fetch_add(1, Relaxed);
+        if user_data.is_null() {
+            self.failed.fetch_add(1, Relaxed);
+        } else {
+            self.request(size)
+        }
+    }
+
+    fn dealloc(&self, size: usize) {
+        self.dealloc.fetch_add(1, Relaxed);
+        self.requested_bytes.fetch_sub(size, Relaxed);
+    }
+
+    fn realloc(&self, size: usize, new_size: usize, user_data: *mut u8) {
+        self.realloc.fetch_add(1, Relaxed);
+        if user_data.is_null() {
+            self.failed.fetch_add(1, Relaxed);
+        } else {
+            self.resize(size, new_size)
+        }
+    }
+
+    fn resize(&self, size: usize, new_size: usize) {
+        self.requested_bytes.fetch_sub(size, Relaxed);
+        self.request(new_size)
     }
+
+    fn load(&self, stats: &mut Stats) {
+        stats.requested_bytes = self.requested_bytes.load(Relaxed);
+        stats.peak_requested_bytes = self.peak_requested_bytes.load(Relaxed);
+        stats.ops = OpCounts {
+            alloc: self.alloc.load(Relaxed),
+            dealloc: self.dealloc.load(Relaxed),
+            realloc: self.realloc.load(Relaxed),
+            failed: self.failed.load(Relaxed),
+            foreign: self.foreign.load(Relaxed),
+        }
+    }
+
+    fn snapshot(&self) -> Self {
+        let load = |counter: &AtomicUsize| AtomicUsize::new(counter.load(Relaxed));
+        Self {
+            alloc: load(&self.alloc),
+            dealloc: load(&self.dealloc),
+            realloc: load(&self.realloc),
+            failed: load(&self.failed),
+            foreign: load(&self.foreign),
+            requested_bytes: load(&self.requested_bytes),
+            peak_requested_bytes: load(&self.peak_requested_bytes),
+        }
+    }
+}
+
+// the space taken by the allocator itself, in bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Overhead {
+    pub bins: usize,
+    // between the bins and the lowest chunk, to align it to `Config::start_align`
+    pub padding: usize,
+    // the header, and the trailer with alloc-id or metadata, of every chunk but the top one, either
+    // in use or free. the padding inside a chunk for over-aligned or odd sized allocations is not
+    // included, as the requested layouts are not kept, and is counted as allocated in `Stats`. for
+    // a live `ptr` its alignment padding is `ptr` less the address of `find_chunk(ptr)` and 8
+    pub headers: usize,
+    // the whole top chunk if it is kept as the wilderness, otherwise its header
+    pub top: usize,
+}
+
+impl Overhead {
+    pub fn total(&self) -> usize {
+        self.bins + self.padding + self.headers + self.top
+    }
+}
+
+// walk all chunks in address order, the heap is locked until the walk is dropped, so allocating
+// from the same heap while walking it deadlocks
+pub struct Chunks<'a, S, R = DefaultLock>
+where
+    R: RawMutex,
+{
+    _space: MutexGuard<'a, R, S>,
+    chunk: Option<Chunk>,
 }
 
-// consider implement it as allocation-free?
+impl<S, R> Iterator for Chunks<'_, S, R>
+where
+    R: RawMutex,
+{
+    type Item = ChunkInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.chunk?;
+        self.chunk = unsafe { chunk.get_walk_next() };
+        Some(unsafe { chunk.info() })
+    }
+}
+
+impl<S, R> FusedIterator for Chunks<'_, S, R> where R: RawMutex {}
+
+// never allocates, as it is formatted inside the heap checks with the heap locked
 impl Debug for Chunk {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut b = f.debug_tuple("Chunk");
@@ -264,32 +865,180 @@ impl Overlay {
     const EXACT_BINS_LEN: usize = 32;
     const SORTED_BINS_LEN: usize = 64;
     const BINS_LEN: usize = Self::EXACT_BINS_LEN + Self::SORTED_BINS_LEN;
+    const COMPACT_BINS_LEN: usize = Self::EXACT_BINS_LEN + 1;
+    const FAST_BINS_LEN: usize = 13;
+    // cached chunks of all sizes together before flushing them into the bins
+    const FAST_LIMIT: usize = 64;
+    const DEFERRED_INDEX: usize = Self::FAST_BINS_LEN + 1;
 
     const MIN_USER_SIZE: usize = Chunk::MIN_SIZE - Chunk::META_SIZE;
+    // free bytes at the end of the heap beyond the headroom before giving them back after freeing
+    const SHRINK_THRESHOLD: usize = 128 << 10;
+    // the least free chunk to give the pages of back to the space, so a system call frees a few
+    // pages at least
+    const DISCARD_THRESHOLD: usize = 64 << 10;
+    // an empty space is resized to this before laying out a lazy heap. generous, as untouched pages
+    // of a mapping cost nothing while growing a mapping in place fails once something is mapped
+    // right after it
+    const LAZY_SIZE: usize = 128 << 10;
+
+    // bin slots below `bin_index_of_size(MIN_USER_SIZE)` are never used by bins, slot 0 starts
+    // with the header, which is stable across versions so a heap left in a persistent or shared
+    // space by one version is recognized, instead of misinterpreted, by another
+    // byte 0: magic, also the initialization marker checked on every operation
+    // byte 1: layout version, bumped whenever anything else in the space changes meaning
+    // byte 2: layout flags, the features changing the layout that the heap was created with
+    // byte 3: zero
+    // bytes 4..8: not part of the header, owned by sealing
+    const MAGIC: u8 = 0x82;
+    const LAYOUT_VERSION: u8 = 3;
+    const FLAG_ALLOC_ID: u8 = 1 << 0;
+    // the metadata size in words
+    const FLAG_METADATA_SHIFT: u32 = 1;
+    const LAYOUT_FLAGS: u8 = if cfg!(feature = "alloc-id") {
+        Self::FLAG_ALLOC_ID
+    } else {
+        0
+    } | ((METADATA_SIZE / 8) as u8) << Self::FLAG_METADATA_SHIFT;
+    #[cfg(feature = "alloc-id")]
+    const NEXT_ID_INDEX: usize = 1;
+    const CONFIG_INDEX: usize = 2;
+
+    // the config slot keeps the top policy in the lowest bit, the placement in the next one, the
+    // bins in the third, the fast bins in the fourth, deferred coalescing in the fifth, the zero
+    // size policy in the two after and the offset of the start chunk from the second byte on
+    unsafe fn config_word(&self) -> u64 {
+        unsafe {
+            *self
+                .space
+                .as_ptr()
+                .add(8 * Self::CONFIG_INDEX)
+                .cast::<u64>()
+        }
+    }
+
+    unsafe fn start_offset(&self) -> usize {
+        (unsafe { self.config_word() } >> 8) as usize
+    }
 
     unsafe fn start_chunk(&self) -> Chunk {
         Chunk::new(
-            NonNull::new(unsafe { self.space.as_ptr().add(8 * Self::BINS_LEN) }).unwrap(),
+            NonNull::new(unsafe { self.space.as_ptr().add(self.start_offset()) }).unwrap(),
             self.limit,
         )
     }
 
     unsafe fn get_bin_chunk(&self, index: usize) -> Option<Chunk> {
-        let chunk_addr = unsafe { *(self.space.as_ptr().add(8 * index).cast()) };
+        let chunk_addr = unsafe { read_link(self.space.as_ptr().add(8 * index).cast()) };
         NonNull::new(chunk_addr).map(|data| Chunk::new(data, self.limit))
     }
 
     unsafe fn set_bin_chunk(&mut self, index: usize, chunk: Option<Chunk>) {
         let chunk = chunk
             .map(|chunk| {
-                debug_assert_eq!(chunk.limit, self.limit);
+                check_assert_eq!(bounds, chunk.limit, self.limit);
                 chunk.data.as_ptr()
             })
             .unwrap_or_else(null_mut);
-        unsafe { *(self.space.as_ptr().add(8 * index).cast()) = chunk }
+        unsafe { write_link(self.space.as_ptr().add(8 * index).cast(), chunk) }
+        let bit = 1 << (index % 64);
+        let word = unsafe { &mut *self.bitmap().add(index / 64) };
+        if chunk.is_null() {
+            *word &= !bit
+        } else {
+            *word |= bit
+        }
+    }
+
+    // the bins are followed by a bit per bin, set if the bin is not empty, so finding the next
+    // non-empty bin does not read the empty ones
+    unsafe fn bitmap(&self) -> *mut u64 {
+        unsafe { self.space.as_ptr().add(8 * self.bins_len()).cast() }
+    }
+
+    // the first non-empty bin from `index` on, or `bins_len()` if there is none
+    unsafe fn next_bin(&self, index: usize) -> usize {
+        let bins_len = self.bins_len();
+        if index >= bins_len {
+            return bins_len;
+        }
+        let bitmap = unsafe { self.bitmap() };
+        let mut word = index / 64;
+        let mut bits = unsafe { *bitmap.add(word) } & (!0 << (index % 64));
+        while bits == 0 {
+            word += 1;
+            if 64 * word >= bins_len {
+                return bins_len;
+            }
+            bits = unsafe { *bitmap.add(word) };
+        }
+        usize::min(64 * word + bits.trailing_zeros() as usize, bins_len)
+    }
+
+    unsafe fn next_bin_chunk(&self, index: usize) -> Option<Chunk> {
+        let index = unsafe { self.next_bin(index) };
+        if index < self.bins_len() {
+            unsafe { self.get_bin_chunk(index) }
+        } else {
+            None
+        }
+    }
+
+    fn bins_len(&self) -> usize {
+        Self::bins_len_of(unsafe { self.bins() })
+    }
+
+    fn bins_len_of(bins: Bins) -> usize {
+        match Self::supported(bins) {
+            Bins::Sorted => Self::BINS_LEN,
+            Bins::Compact => Self::COMPACT_BINS_LEN,
+        }
+    }
+
+    // the slots taken by the bins, their bitmap and the cached chunks, which the start chunk
+    // follows
+    fn bins_slots(&self) -> usize {
+        Self::bins_slots_of(unsafe { self.bins() }, unsafe { self.caching() })
+    }
+
+    fn bins_slots_of(bins: Bins, caching: bool) -> usize {
+        Self::fast_offset_of(bins) + if caching { Self::DEFERRED_INDEX + 1 } else { 0 }
+    }
+
+    fn fast_offset_of(bins: Bins) -> usize {
+        let bins_len = Self::bins_len_of(bins);
+        bins_len + bins_len.div_ceil(64)
+    }
+
+    // a head per chunk size from the minimum one on, then the number of chunks in all of them, and
+    // the head of the deferred chunks. a cached chunk links to the next one with the first word of
+    // its user data
+    unsafe fn fast_slot(&self, index: usize) -> *mut *mut u8 {
+        let offset = Self::fast_offset_of(unsafe { self.bins() });
+        unsafe { self.space.as_ptr().add(8 * (offset + index)).cast() }
+    }
+
+    unsafe fn fast_count(&self) -> *mut usize {
+        unsafe { self.fast_slot(Self::FAST_BINS_LEN).cast() }
+    }
+
+    // `None` if chunks of `size` are not cached
+    unsafe fn fast_index(&self, size: usize) -> Option<usize> {
+        let index = (size - Chunk::MIN_SIZE) / 8;
+        (unsafe { self.fast_bins() } && index < Self::FAST_BINS_LEN).then_some(index)
+    }
+
+    // compact bins put every size class of the sorted list into the first of them
+    fn bin_index_of_size(&self, size: usize) -> usize {
+        let index = Self::size_class(size);
+        if index >= Self::EXACT_BINS_LEN && unsafe { self.bins() } == Bins::Compact {
+            Self::EXACT_BINS_LEN
+        } else {
+            index
+        }
     }
 
-    fn bin_index_of_size(size: usize) -> usize {
+    fn size_class(size: usize) -> usize {
         let size = usize::max(size, Self::MIN_USER_SIZE);
         // TODO: currently relaying on exact value of constants
         if (size >> 8) == 0 {
@@ -304,20 +1053,51 @@ impl Overlay {
         }
     }
 
+    // an exact bin holds chunks of a single size, so instead of joining the sorted list it is a
+    // LIFO stack of its own, which ends with a pointer to the bin slot instead of null, so that
+    // the top chunk stays the only one without next chunk
+    fn bin_sentinel(&self, index: usize) -> Chunk {
+        Chunk::new(
+            NonNull::new(unsafe { self.space.as_ptr().add(8 * index) }).unwrap(),
+            self.limit,
+        )
+    }
+
+    fn filter_sentinel(&self, chunk: Chunk) -> Option<Chunk> {
+        Some(chunk).filter(|chunk| {
+            chunk.data.as_ptr() as usize >= self.space.as_ptr() as usize + 8 * self.bins_len()
+        })
+    }
+
     // add a chunk that is not the last chunk, i.e. the new top chunk
     // updating top chunk goes into `update_top_chunk`
     unsafe fn add_chunk(&mut self, mut chunk: Chunk) {
         let chunk_size = unsafe { chunk.get_size() };
+        // the top chunk is not poisoned, it would be filled again on every growth and split
+        #[cfg(any(dev, test, feature = "check-poison"))]
+        unsafe {
+            chunk.poison()
+        }
         // bin is indexed by maximum possible available size for user data
-        let index = Self::bin_index_of_size(chunk_size - Chunk::META_SIZE);
+        let index = self.bin_index_of_size(chunk_size - Chunk::META_SIZE);
+        if index < Self::EXACT_BINS_LEN {
+            unsafe {
+                chunk.set_prev(None);
+                if let Some(mut head) = self.get_bin_chunk(index) {
+                    chunk.set_next(Some(head));
+                    head.set_prev(Some(chunk));
+                } else {
+                    chunk.set_next(Some(self.bin_sentinel(index)));
+                }
+                self.set_bin_chunk(index, Some(chunk));
+            }
+            return;
+        }
         let mut bin_chunk = unsafe { self.get_bin_chunk(index) };
         if bin_chunk.is_none() {
-            unsafe { self.set_bin_chunk(index, Some(chunk)) }
-            for index in index + 1..Self::BINS_LEN {
-                bin_chunk = unsafe { self.get_bin_chunk(index) };
-                if bin_chunk.is_some() {
-                    break;
-                }
+            unsafe {
+                self.set_bin_chunk(index, Some(chunk));
+                bin_chunk = self.next_bin_chunk(index + 1)
             }
         }
         let mut bin_chunk = bin_chunk.expect("top chunk always reachable from bins");
@@ -328,7 +1108,8 @@ impl Overlay {
             } else {
                 // even if `chunk` has larger size, `bin_chunk`, which is the top chunk, will always
                 // be the last chunk
-                debug_assert!(
+                check_assert!(
+                    chunks,
                     chunk < bin_chunk,
                     "adding {chunk:?} that is not lower than the top {bin_chunk:?}"
                 );
@@ -336,6 +1117,10 @@ impl Overlay {
             };
         }
         // println!("{bin_chunk:?}");
+        if unsafe { self.get_bin_chunk(index) } == Some(bin_chunk) {
+            // smaller than every chunk in the (sorted) bin
+            unsafe { self.set_bin_chunk(index, Some(chunk)) }
+        }
         unsafe {
             chunk.set_next(Some(bin_chunk));
             chunk.set_prev(bin_chunk.get_prev());
@@ -352,6 +1137,28 @@ impl Overlay {
     // additionally, the old top chunk is also not removed here
     // updating top chunk goes into `update_top_chunk`
     unsafe fn remove_chunk(&mut self, chunk: Chunk) {
+        check_assert!(
+            poison,
+            unsafe { chunk.is_poisoned() },
+            "free {chunk:?} written after freed"
+        );
+        let index = self.bin_index_of_size(unsafe { chunk.get_size() } - Chunk::META_SIZE);
+        if index < Self::EXACT_BINS_LEN {
+            unsafe {
+                let next_chunk =
+                    self.filter_sentinel(chunk.get_next().expect("exact bin ends with sentinel"));
+                if let Some(mut next_chunk) = next_chunk {
+                    next_chunk.set_prev(chunk.get_prev());
+                }
+                if let Some(mut prev_chunk) = chunk.get_prev() {
+                    prev_chunk.set_next(chunk.get_next());
+                } else {
+                    self.set_bin_chunk(index, next_chunk);
+                }
+            }
+            return;
+        }
+
         let mut next_chunk;
         unsafe {
             next_chunk = chunk.get_next().expect("top chunk never get removed");
@@ -360,16 +1167,17 @@ impl Overlay {
                 prev_chunk.set_next(Some(next_chunk));
             }
         }
-        let index = Self::bin_index_of_size(unsafe { chunk.get_size() } - Chunk::META_SIZE);
         if unsafe { self.get_bin_chunk(index) } == Some(chunk) {
+            // the top chunk only belongs to the last bin, whatever its size
+            let next_size = if unsafe { next_chunk.is_top() } {
+                usize::MAX
+            } else {
+                unsafe { next_chunk.get_size() - Chunk::META_SIZE }
+            };
             unsafe {
                 self.set_bin_chunk(
                     index,
-                    if Self::bin_index_of_size(next_chunk.get_size()) == index {
-                        Some(next_chunk)
-                    } else {
-                        None
-                    },
+                    Some(next_chunk).filter(|_| self.bin_index_of_size(next_size) == index),
                 )
             }
         }
@@ -386,80 +1194,685 @@ impl Overlay {
                 prev_chunk.set_next(Some(new_top));
             }
         }
-        let index = Self::bin_index_of_size(usize::MAX);
+        let index = self.bin_index_of_size(usize::MAX);
         if unsafe { self.get_bin_chunk(index) } == Some(top) {
             unsafe { self.set_bin_chunk(index, Some(new_top)) }
         }
     }
 
-    unsafe fn init(&mut self, len: usize) {
-        assert!(len >= 8 * Self::BINS_LEN + Chunk::MIN_SIZE * 2);
-        assert_eq!(len % 8, 0);
+    // nothing is written if the heap does not fit
+    // `zeroed` if the space is all zero, e.g. just grown from empty
+    unsafe fn init(&mut self, len: usize, config: Config, zeroed: bool) -> Result<(), NewError> {
+        let addr = self.space.as_ptr();
+        if !config.start_align.is_power_of_two() {
+            return Err(NewError::StartAlign(config.start_align));
+        }
+        if addr as usize & 7 != 0 {
+            return Err(NewError::Misaligned(addr));
+        }
+        if len & 7 != 0 {
+            return Err(NewError::Length(len));
+        }
+        let bins_len = Self::bins_len_of(config.bins);
+        let fast_bins = config.fast_bins && !cfg!(feature = "minimal");
+        let deferred = config.deferred_coalescing && !cfg!(feature = "minimal");
+        let bins_slots = Self::bins_slots_of(config.bins, fast_bins || deferred);
+        // the user data is right after the header
+        let start_offset = (addr as usize + 8 * bins_slots + 8)
+            .next_multiple_of(usize::max(config.start_align, 8))
+            - 8
+            - addr as usize;
+        let min = start_offset + Chunk::MIN_SIZE * 2;
+        if len < min {
+            return Err(NewError::TooSmall { len, min });
+        }
 
-        for index in Self::bin_index_of_size(Self::MIN_USER_SIZE)..Self::BINS_LEN {
+        unsafe {
+            *self
+                .space
+                .as_ptr()
+                .add(8 * Self::CONFIG_INDEX)
+                .cast::<u64>() = config.top_policy as u64
+                | (config.placement as u64) << 1
+                | (Self::supported(config.bins) as u64) << 2
+                | (fast_bins as u64) << 3
+                | (deferred as u64) << 4
+                | (config.zero_size as u64) << 5
+                | (start_offset as u64) << 8
+        }
+        for slot in bins_len..bins_slots {
+            unsafe { *self.space.as_ptr().add(8 * slot).cast::<u64>() = 0 }
+        }
+        for index in self.bin_index_of_size(Self::MIN_USER_SIZE)..bins_len {
             unsafe { self.set_bin_chunk(index, None) }
         }
+        #[cfg(feature = "alloc-id")]
+        unsafe {
+            *self
+                .space
+                .as_ptr()
+                .add(8 * Self::NEXT_ID_INDEX)
+                .cast::<u64>() = 0
+        }
         unsafe {
             let mut chunk = self.start_chunk();
-            let chunk_size = self
+            let mut chunk_size = self
                 .space
                 .as_ptr()
                 .add(len)
-                .offset_from(chunk.data.as_ptr()) as usize
+                .offset_from(chunk.data.as_ptr()) as usize;
+            if config.top_policy == TopPolicy::KeepWilderness {
                 // save space for the top chunk
-                - Chunk::MIN_SIZE;
-            chunk.set_in_use_and_size(false, chunk_size);
+                chunk_size -= Chunk::MIN_SIZE;
+            }
+            chunk.init_free(chunk_size);
             chunk.set_lower_in_use(true); // because there's no lower chunk
+            chunk.set_clean(zeroed);
 
-            let mut top_chunk = chunk.get_higher_chunk();
-            top_chunk.set_in_use_and_size(false, Chunk::MIN_SIZE);
-            top_chunk.set_next(None);
-            top_chunk.set_prev(None);
-            self.set_bin_chunk(Self::bin_index_of_size(usize::MAX), Some(top_chunk));
+            if config.top_policy == TopPolicy::UseWilderness {
+                // the whole heap starts as the top chunk
+                chunk.set_next(None);
+                chunk.set_prev(None);
+                self.set_bin_chunk(self.bin_index_of_size(usize::MAX), Some(chunk));
+            } else {
+                let mut top_chunk = chunk.get_higher_chunk();
+                top_chunk.init_free(Chunk::MIN_SIZE);
+                top_chunk.set_next(None);
+                top_chunk.set_prev(None);
+                self.set_bin_chunk(self.bin_index_of_size(usize::MAX), Some(top_chunk));
 
-            self.add_chunk(chunk);
+                self.add_chunk(chunk);
+            }
         }
 
         unsafe {
-            // a little bit of best-effort sanity marker for initialized space
-            *self.space.as_mut() = 0x82;
+            // the magic goes last as the marker of initialized space
+            *self.space.as_ptr().add(1) = Self::LAYOUT_VERSION;
+            *self.space.as_ptr().add(2) = Self::LAYOUT_FLAGS;
+            *self.space.as_ptr().add(3) = 0;
+            *self.space.as_mut() = Self::MAGIC;
             self.sanity_check()
         }
+        Ok(())
     }
 
-    // extract this subroutine for reusing in test helper
-    unsafe fn find_smallest(&self, min_size: usize) -> Chunk {
+    // from the lowest chunk up to the top chunk
+    unsafe fn iter_all_chunk(&self) -> impl Iterator<Item = Chunk> {
+        use core::iter::from_fn;
+
+        check_assert_eq!(chunks, unsafe { self.space.as_ref() }, &Self::MAGIC);
+        let mut chunk = Some(unsafe { self.start_chunk() });
+        from_fn(move || {
+            let item = chunk;
+            chunk = item.and_then(|item| unsafe { item.get_walk_next() });
+            item
+        })
+    }
+
+    // free chunks that may fit `min_size`, from the exact bins of it on, then the sorted list which
+    // ends with the top chunk
+    unsafe fn iter_free_chunk_from(&self, min_size: usize) -> impl Iterator<Item = Chunk> + '_ {
+        use core::iter::from_fn;
+
+        let mut index = self.bin_index_of_size(min_size);
         let mut chunk = None;
-        for index in Self::bin_index_of_size(min_size)..Self::BINS_LEN {
-            chunk = unsafe { self.get_bin_chunk(index) };
-            if chunk.is_some() {
+        from_fn(move || {
+            if chunk.is_none() && index < Self::EXACT_BINS_LEN {
+                index = usize::min(unsafe { self.next_bin(index) }, Self::EXACT_BINS_LEN);
+                if index < Self::EXACT_BINS_LEN {
+                    chunk = unsafe { self.get_bin_chunk(index) };
+                    index += 1;
+                }
+            }
+            if chunk.is_none() && index != Self::BINS_LEN {
+                index = Self::BINS_LEN;
+                chunk = Some(unsafe { self.find_smallest(min_size) });
+            }
+            let item = chunk?;
+            chunk = if index == Self::BINS_LEN {
+                unsafe { item.get_next() }
+            } else {
+                self.filter_sentinel(unsafe { item.get_next() }.unwrap())
+            };
+            Some(item)
+        })
+    }
+
+    // free chunks to place an allocation of `min_size` in, in the order of the placement
+    unsafe fn iter_candidate_chunk(&self, min_size: usize) -> impl Iterator<Item = Chunk> + '_ {
+        let first_fit = unsafe { self.placement() } == Placement::FirstFit;
+        let best_fit = (!first_fit).then(|| unsafe { self.iter_free_chunk_from(min_size) });
+        let first_fit = first_fit.then(|| {
+            unsafe { self.iter_all_chunk() }.filter(|chunk| unsafe { !chunk.get_in_use() })
+        });
+        best_fit
+            .into_iter()
+            .flatten()
+            .chain(first_fit.into_iter().flatten())
+    }
+
+    unsafe fn iter_free_chunk(&self) -> impl Iterator<Item = Chunk> + '_ {
+        check_assert_eq!(chunks, unsafe { self.space.as_ref() }, &Self::MAGIC);
+        unsafe { self.iter_free_chunk_from(0) }
+    }
+
+    // the least length to grow to so that `layout` fits after merging the new space into the free
+    // tail of the heap, and the preferred length that also leaves free space for half of the bytes
+    // in use, so a ramping heap grows geometrically instead of once per failing allocation
+    // `None` if the length overflows, i.e. the allocation can never succeed
+    unsafe fn growth(&self, top: Chunk, layout: Layout, len: usize) -> Option<(usize, usize)> {
+        let chunk_size = usize::max(
+            Chunk::META_SIZE.checked_add(layout.size().checked_next_multiple_of(8)?)?,
+            Chunk::MIN_SIZE,
+        );
+        // the top chunk is left after allocating
+        let need = chunk_size
+            .checked_add(layout.align())?
+            .checked_add(Chunk::MIN_SIZE)?;
+        let mut tail = unsafe { top.get_size() };
+        if unsafe { self.top_policy() } == TopPolicy::KeepWilderness {
+            if let Some(free_lower) = unsafe { top.get_free_lower_chunk() } {
+                tail += unsafe { free_lower.get_size() }
+            }
+        }
+        let min_growth = usize::max(need.saturating_sub(tail).next_multiple_of(8), 8);
+
+        let min_len = len.checked_add(min_growth)?;
+        let headroom = unsafe { self.headroom(len) };
+        let len = usize::max(min_growth, headroom)
+            .checked_next_multiple_of(4096)
+            .and_then(|growth| len.checked_add(growth))
+            .unwrap_or(min_len);
+        Some((min_len, len))
+    }
+
+    // free bytes for the following allocations, relative to the bytes in use, which growing adds
+    // and shrinking keeps
+    unsafe fn headroom(&self, len: usize) -> usize {
+        if cfg!(feature = "profile-tiny") {
+            return 0;
+        }
+        let free_bytes = unsafe { self.iter_free_chunk() }
+            .map(|chunk| unsafe { chunk.get_size() })
+            .sum::<usize>();
+        let used_bytes = len - unsafe { self.start_offset() } - free_bytes;
+        if cfg!(feature = "profile-fast") {
+            used_bytes
+        } else {
+            used_bytes / 2
+        }
+    }
+
+    unsafe fn top_policy(&self) -> TopPolicy {
+        match unsafe { self.config_word() } & 1 {
+            0 => TopPolicy::KeepWilderness,
+            _ => TopPolicy::UseWilderness,
+        }
+    }
+
+    unsafe fn placement(&self) -> Placement {
+        match unsafe { self.config_word() } >> 1 & 1 {
+            0 => Placement::BestFit,
+            _ => Placement::FirstFit,
+        }
+    }
+
+    unsafe fn bins(&self) -> Bins {
+        match unsafe { self.config_word() } >> 2 & 1 {
+            0 => Self::supported(Bins::Sorted),
+            _ => Bins::Compact,
+        }
+    }
+
+    unsafe fn fast_bins(&self) -> bool {
+        !cfg!(feature = "minimal") && unsafe { self.config_word() } >> 3 & 1 != 0
+    }
+
+    unsafe fn zero_size(&self) -> ZeroSize {
+        match unsafe { self.config_word() } >> 5 & 0b11 {
+            0 => ZeroSize::Dangling,
+            1 => ZeroSize::MinChunk,
+            _ => ZeroSize::Null,
+        }
+    }
+
+    unsafe fn deferred_coalescing(&self) -> bool {
+        !cfg!(feature = "minimal") && unsafe { self.config_word() } >> 4 & 1 != 0
+    }
+
+    // whether freed chunks may be kept in use, in either way
+    unsafe fn caching(&self) -> bool {
+        unsafe { self.fast_bins() || self.deferred_coalescing() }
+    }
+
+    // the sorted bins are compiled out with `minimal`, so the bins are known without reading the
+    // config slot
+    const fn supported(bins: Bins) -> Bins {
+        if cfg!(feature = "minimal") {
+            Bins::Compact
+        } else {
+            bins
+        }
+    }
+
+    #[cfg(feature = "alloc-id")]
+    unsafe fn next_id(&mut self) -> u64 {
+        let next_id = unsafe {
+            &mut *self
+                .space
+                .as_ptr()
+                .add(8 * Self::NEXT_ID_INDEX)
+                .cast::<u64>()
+        };
+        let id = *next_id;
+        *next_id += 1;
+        id
+    }
+
+    unsafe fn check_header(&self, end: *mut u8) -> Result<(), CheckError> {
+        let header = unsafe { self.space.cast::<[u8; 4]>().read() };
+        if header[0] != Self::MAGIC {
+            return Err(CheckError::Marker);
+        }
+        if header[1..] != [Self::LAYOUT_VERSION, Self::LAYOUT_FLAGS, 0] {
+            return Err(CheckError::Version {
+                version: header[1],
+                flags: header[2],
+            });
+        }
+        let config = unsafe { self.config_word() };
+        let start_offset = (config >> 8) as usize;
+        if config & 0xff >= 0b110_0000
+            || cfg!(feature = "minimal") && config >> 2 & 0b111 != 0b001
+            || start_offset < 8 * self.bins_slots()
+            || start_offset & 7 != 0
+            || start_offset > end as usize - self.space.as_ptr() as usize - 2 * Chunk::MIN_SIZE
+        {
+            return Err(CheckError::Config);
+        }
+        Ok(())
+    }
+
+    // unlike `sanity_check`, this is always compiled and report instead of panic, and it does not
+    // trust any pointer or size read from the heap before bound checking it
+    unsafe fn check(&self, end: *mut u8) -> Result<(), CheckError> {
+        unsafe { self.check_header(end) }?;
+        // a bit is set for exactly the non-empty bins, before anything is found through them
+        let bins = self.bin_index_of_size(Self::MIN_USER_SIZE)..self.bins_len();
+        let bitmap_len = Self::fast_offset_of(unsafe { self.bins() }) - self.bins_len();
+        for index in 0..64 * bitmap_len {
+            let bit = unsafe { *self.bitmap().add(index / 64) } >> (index % 64) & 1 != 0;
+            let bin = bins.contains(&index)
+                && unsafe { !read_link(self.space.as_ptr().add(8 * index).cast()).is_null() };
+            if bit != bin {
+                return Err(CheckError::Bin(index));
+            }
+        }
+        let start = unsafe { self.start_chunk() }.data.as_ptr();
+        let class_of = |chunk: Chunk, size: usize| {
+            if chunk.data.as_ptr().wrapping_add(size) == end {
+                self.bins_len() - 1
+            } else {
+                self.bin_index_of_size(size - Chunk::META_SIZE)
+            }
+        };
+
+        let mut chunk = unsafe { self.start_chunk() };
+        let mut lower_in_use = true;
+        let mut lower_free = None;
+        let mut free_count = 0;
+        let mut in_use_count = 0;
+        loop {
+            let addr = chunk.data.as_ptr();
+            let (size, in_use) = unsafe { Self::check_tags(chunk, end, lower_in_use) }?;
+            if in_use {
+                in_use_count += 1;
+            } else {
+                free_count += 1;
+            }
+            if addr.wrapping_add(size) == end {
+                if in_use
+                    || (size != Chunk::MIN_SIZE
+                        && unsafe { self.top_policy() } == TopPolicy::KeepWilderness)
+                    || unsafe { chunk.get_next().is_some() }
+                {
+                    return Err(CheckError::Top(addr));
+                }
+                break;
+            }
+            if let (Some(lower), false) = (lower_free, in_use) {
+                return Err(CheckError::Uncoalesced(lower));
+            }
+            lower_in_use = in_use;
+            lower_free = if in_use { None } else { Some(addr) };
+            chunk = unsafe { chunk.get_higher_chunk() };
+        }
+
+        let valid = |addr: *mut u8| {
+            (start..end).contains(&addr) && (addr as usize - start as usize) & 7 == 0
+        };
+        let mut count = 0;
+        for index in self.bin_index_of_size(Self::MIN_USER_SIZE)..Self::EXACT_BINS_LEN {
+            let sentinel = self.bin_sentinel(index).data.as_ptr();
+            let mut addr = unsafe { read_link(sentinel.cast()) };
+            if addr.is_null() {
+                continue;
+            }
+            if !valid(addr) {
+                return Err(CheckError::Bin(index));
+            }
+            let mut prev = null_mut();
+            loop {
+                let chunk = Chunk::new(NonNull::new(addr).unwrap(), self.limit);
+                count += 1;
+                let size = unsafe { chunk.get_size() };
+                if count > free_count
+                    || unsafe { chunk.get_in_use() }
+                    || size < Chunk::MIN_SIZE
+                    || size > end as usize - addr as usize
+                    || unsafe { read_link(addr.add(8).cast()) } != prev
+                {
+                    return Err(CheckError::FreeList(addr));
+                }
+                if class_of(chunk, size) != index {
+                    return Err(if prev.is_null() {
+                        CheckError::Bin(index)
+                    } else {
+                        CheckError::FreeList(addr)
+                    });
+                }
+                let next_addr = unsafe { read_link(addr.add(16).cast()) };
+                if next_addr == sentinel {
+                    break;
+                }
+                if !valid(next_addr) {
+                    return Err(CheckError::FreeList(addr));
+                }
+                prev = addr;
+                addr = next_addr;
+            }
+        }
+
+        let mut heads = [None; Self::BINS_LEN];
+        let mut chunk = unsafe { self.find_smallest(0) };
+        let mut prev = None;
+        let mut class = Self::EXACT_BINS_LEN;
+        let free_count = free_count - count;
+        for count in 1.. {
+            let addr = chunk.data.as_ptr();
+            if count > free_count || unsafe { chunk.get_in_use() } {
+                return Err(CheckError::FreeList(addr));
+            }
+            let prev_addr = unsafe { read_link(addr.add(8).cast()) };
+            if prev_addr != prev.unwrap_or_else(null_mut) {
+                return Err(CheckError::FreeList(addr));
+            }
+            let chunk_class = class_of(chunk, unsafe { chunk.get_size() });
+            if chunk_class < class {
+                return Err(CheckError::FreeList(addr));
+            }
+            if prev.is_none() || chunk_class != class {
+                heads[chunk_class] = Some(chunk);
+            }
+            class = chunk_class;
+            let next_addr = unsafe { read_link(addr.add(16).cast()) };
+            if next_addr.is_null() {
+                if count != free_count {
+                    return Err(CheckError::FreeList(addr));
+                }
                 break;
             }
+            if !valid(next_addr) {
+                return Err(CheckError::FreeList(addr));
+            }
+            prev = Some(addr);
+            chunk = Chunk::new(NonNull::new(next_addr).unwrap(), self.limit);
+        }
+
+        let heads = heads.into_iter().take(self.bins_len());
+        for (index, head) in heads.enumerate().skip(Self::EXACT_BINS_LEN) {
+            let bin = unsafe { read_link(self.space.as_ptr().add(8 * index).cast()) };
+            if bin
+                != head
+                    .map(|chunk| chunk.data.as_ptr())
+                    .unwrap_or_else(null_mut)
+            {
+                return Err(CheckError::Bin(index));
+            }
+        }
+
+        if unsafe { self.fast_bins() } {
+            if unsafe { *self.fast_count() } > Self::FAST_LIMIT {
+                return Err(CheckError::Config);
+            }
+            let mut count = 0;
+            for index in 0..Self::FAST_BINS_LEN {
+                let mut addr = unsafe { read_link(self.fast_slot(index)) };
+                while !addr.is_null() {
+                    if !valid(addr) {
+                        return Err(CheckError::FreeList(addr));
+                    }
+                    let chunk = Chunk::new(NonNull::new(addr).unwrap(), self.limit);
+                    count += 1;
+                    if unsafe { !chunk.get_in_use() }
+                        || unsafe { chunk.get_size() } != Chunk::MIN_SIZE + 8 * index
+                        || count > unsafe { *self.fast_count() }
+                    {
+                        return Err(CheckError::FreeList(addr));
+                    }
+                    addr = unsafe { read_link(addr.add(8).cast()) }
+                }
+            }
+            if count != unsafe { *self.fast_count() } {
+                return Err(CheckError::Config);
+            }
+        }
+        if unsafe { self.deferred_coalescing() } {
+            let mut addr = unsafe { read_link(self.fast_slot(Self::DEFERRED_INDEX)) };
+            let mut count = 0;
+            while !addr.is_null() {
+                count += 1;
+                if !valid(addr)
+                    || unsafe { !Chunk::new(NonNull::new(addr).unwrap(), self.limit).get_in_use() }
+                    || count > in_use_count
+                {
+                    return Err(CheckError::FreeList(addr));
+                }
+                addr = unsafe { read_link(addr.add(8).cast()) }
+            }
         }
-        chunk.expect("top chunk always reachable from bins")
+        Ok(())
     }
 
-    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, Chunk> {
-        if layout.size() == 0 {
-            return Ok(NonNull::dangling()); // feels like better than null?
+    // the size and the in use bit of a chunk, after checking its boundary tags against the lower
+    // chunk and the end of the heap
+    unsafe fn check_tags(
+        chunk: Chunk,
+        end: *mut u8,
+        lower_in_use: bool,
+    ) -> Result<(usize, bool), CheckError> {
+        let addr = chunk.data.as_ptr();
+        if (end as usize - addr as usize) < Chunk::MIN_SIZE {
+            return Err(CheckError::ChunkSize(addr));
+        }
+        let size = unsafe { chunk.get_size() };
+        if size < Chunk::MIN_SIZE || size % 8 != 0 || size > end as usize - addr as usize {
+            return Err(CheckError::ChunkSize(addr));
+        }
+        if unsafe { chunk.get_lower_in_use() } != lower_in_use {
+            return Err(CheckError::LowerInUse(addr));
+        }
+        let in_use = unsafe { chunk.get_in_use() };
+        if !in_use && unsafe { *addr.add(size - 8).cast::<u64>() } != size as u64 {
+            return Err(CheckError::Footer(addr));
+        }
+        Ok((size, in_use))
+    }
+
+    // go on checking the chunks from `offset` into the heap, until the end or past `budget` bytes
+    // of chunks, and return the offset of the chunk where to go on next time, zero at the end. the
+    // chunks below `offset` are walked again, but only their boundary tags are checked, as their
+    // boundaries may have moved since. the filling of the free chunks is checked too if they are
+    // poisoned, the top chunk excluded
+    unsafe fn scrub(
+        &self,
+        end: *mut u8,
+        offset: usize,
+        budget: usize,
+    ) -> Result<usize, CheckError> {
+        unsafe { self.check_header(end) }?;
+        let mut chunk = unsafe { self.start_chunk() };
+        let mut lower_in_use = true;
+        let mut scrubbed = 0;
+        loop {
+            let addr = chunk.data.as_ptr();
+            let (size, in_use) = unsafe { Self::check_tags(chunk, end, lower_in_use) }?;
+            if addr.wrapping_add(size) == end {
+                return if in_use {
+                    Err(CheckError::Top(addr))
+                } else {
+                    Ok(0)
+                };
+            }
+            if addr as usize - self.space.as_ptr() as usize >= offset {
+                #[cfg(any(dev, test, feature = "check-poison"))]
+                if !in_use && unsafe { !chunk.is_poisoned() } {
+                    return Err(CheckError::Poison(addr));
+                }
+                scrubbed += size;
+                if scrubbed >= budget {
+                    return Ok(addr as usize + size - self.space.as_ptr() as usize);
+                }
+            }
+            lower_in_use = in_use;
+            chunk = unsafe { chunk.get_higher_chunk() };
+        }
+    }
+
+    // the first chunk of the sorted list that may fit `min_size`, exact bins are not included
+    unsafe fn find_smallest(&self, min_size: usize) -> Chunk {
+        let index = usize::max(self.bin_index_of_size(min_size), Self::EXACT_BINS_LEN);
+        unsafe { self.next_bin_chunk(index) }.expect("top chunk always reachable from bins")
+    }
+
+    // look into at most `scan` free chunks, `Err(None)` if none of them fits and the top chunk is
+    // not reached yet
+    unsafe fn alloc(&mut self, layout: Layout, scan: usize) -> Result<NonNull<u8>, Option<Chunk>> {
+        if let Some(user_data) = unsafe { self.alloc_cached(layout) } {
+            return Ok(user_data);
+        }
+        let result = unsafe { self.alloc_from_bins(layout, scan) };
+        // the cached chunks may coalesce into one that fits, rather than growing the heap. not when
+        // the scan ends early, which has a budget to keep
+        if matches!(result, Err(Some(_))) && unsafe { self.consolidate() } {
+            return unsafe { self.alloc_from_bins(layout, scan) };
+        }
+        result
+    }
+
+    // a cached chunk of the exact size, which is always aligned enough. from the fast bins, or
+    // the last deferred one, so freeing and allocating the same sizes in reverse order reuses them
+    unsafe fn alloc_cached(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        if layout.size() == 0 || layout.align() > 8 || unsafe { !self.caching() } {
+            return None;
+        }
+        let size = usize::max(
+            layout
+                .size()
+                .checked_next_multiple_of(8)?
+                .checked_add(Chunk::META_SIZE)?,
+            Chunk::MIN_SIZE,
+        );
+        let index = unsafe { self.fast_index(size) };
+        let head = match index {
+            Some(index) => unsafe { self.fast_slot(index) },
+            None if unsafe { self.deferred_coalescing() } => unsafe {
+                self.fast_slot(Self::DEFERRED_INDEX)
+            },
+            None => return None,
+        };
+        let chunk = Chunk::new(NonNull::new(unsafe { read_link(head) })?, self.limit);
+        if unsafe { chunk.get_size() } != size {
+            return None;
+        }
+        unsafe {
+            write_link(head, read_link(chunk.data.as_ptr().add(8).cast()));
+            if index.is_some() {
+                *self.fast_count() -= 1
+            }
+            let user_data = chunk.get_user_data(layout).unwrap();
+            Some(self.take(chunk, user_data))
+        }
+    }
+
+    // give every cached chunk to the bins
+    unsafe fn flush_fast_bins(&mut self) {
+        if unsafe { !self.fast_bins() } {
+            return;
+        }
+        for index in 0..Self::FAST_BINS_LEN {
+            let head = unsafe { self.fast_slot(index) };
+            while let Some(data) = NonNull::new(unsafe { read_link(head) }) {
+                unsafe {
+                    write_link(head, read_link(data.as_ptr().add(8).cast()));
+                    self.free_chunk(Chunk::new(data, self.limit));
+                }
+            }
+        }
+        unsafe { *self.fast_count() = 0 }
+    }
+
+    // give every cached chunk to the bins, return whether there is any
+    unsafe fn consolidate(&mut self) -> bool {
+        if unsafe { !self.caching() } {
+            return false;
+        }
+        let head = unsafe { self.fast_slot(Self::DEFERRED_INDEX) };
+        let cached = unsafe { *self.fast_count() != 0 || !read_link(head).is_null() };
+        unsafe { self.flush_fast_bins() }
+        while let Some(data) = NonNull::new(unsafe { read_link(head) }) {
+            unsafe {
+                write_link(head, read_link(data.as_ptr().add(8).cast()));
+                self.free_chunk(Chunk::new(data, self.limit));
+            }
         }
+        cached
+    }
 
-        let mut chunk = unsafe { self.find_smallest(layout.size()) };
+    unsafe fn alloc_from_bins(
+        &mut self,
+        layout: Layout,
+        mut scan: usize,
+    ) -> Result<NonNull<u8>, Option<Chunk>> {
+        let mut candidates = unsafe { self.iter_candidate_chunk(layout.size()) };
+        let mut chunk = candidates.next().unwrap();
         // println!("{layout:?} {chunk:?}");
         let mut user_data = unsafe { chunk.get_user_data(layout) };
         while user_data.is_none() {
-            chunk = if let Some(next_chunk) = unsafe { chunk.get_next() } {
+            chunk = if let Some(next_chunk) = candidates.next() {
                 next_chunk
             } else {
-                return Err(chunk);
+                return Err(Some(chunk));
             };
+            scan = scan.checked_sub(1).ok_or(None)?;
             user_data = unsafe { chunk.get_user_data(layout) };
         }
+        drop(candidates);
         // println!("{chunk:?} {user_data:?} {remain:?}");
-        debug_assert!(unsafe { chunk.get_size() } >= layout.size() + Chunk::META_SIZE);
+        check_assert!(
+            chunks,
+            unsafe { chunk.get_size() } >= layout.size() + Chunk::META_SIZE
+        );
 
         if unsafe { chunk.is_top() } {
-            return Err(chunk); // top chunk is never used
+            if unsafe { self.top_policy() } == TopPolicy::KeepWilderness {
+                return Err(Some(chunk)); // top chunk is never used
+            }
+            // the rest of the top chunk becomes the new top chunk, which cannot go below minimum
+            let Some(new_top) = (unsafe { chunk.split(layout) }) else {
+                return Err(Some(chunk));
+            };
+            unsafe { self.update_top_chunk(chunk, new_top) }
         } else {
             unsafe {
                 // println!("{chunk:?}");
@@ -470,23 +1883,71 @@ impl Overlay {
             }
         }
 
-        // println!("{chunk:?}");
+        Ok(unsafe { self.take(chunk, user_data.unwrap()) })
+    }
+
+    // mark `chunk` in use with its user data at `user_data`
+    unsafe fn take(&mut self, mut chunk: Chunk, user_data: NonNull<u8>) -> NonNull<u8> {
         unsafe { chunk.set_in_use_and_size(true, chunk.get_size()) }
+        #[cfg(feature = "alloc-id")]
+        unsafe {
+            chunk.set_id(self.next_id())
+        }
+        if METADATA_SIZE != 0 {
+            unsafe { chunk.get_metadata().write([0; METADATA_SIZE]) }
+        }
 
-        let user_data = user_data.unwrap();
-        // a little duplication to `split`
-        let padding = unsafe { user_data.as_ptr().offset(-8) };
-        let padding_size = unsafe { padding.offset_from(chunk.data.as_ptr()) } as usize;
-        if padding_size != 0 {
-            // println!("padding size {padding_size}");
-            debug_assert_eq!(padding_size as u64 & Chunk::META_MASK, 0); // so the line below also clear meta bits
-            unsafe { *padding.cast::<u64>() = padding_size as _ }
+        unsafe { chunk.set_padding(user_data) }
+        user_data
+    }
+
+    // the free chunk binned, see `free_chunk`, `None` if cached instead
+    unsafe fn dealloc(&mut self, user_data: *mut u8, layout: Layout) -> Option<Chunk> {
+        let chunk = unsafe { Chunk::from_user_data(user_data, layout, self.limit) };
+        if let Some(index) = unsafe { self.fast_index(chunk.get_size()) } {
+            unsafe {
+                self.cache(index, chunk);
+                *self.fast_count() += 1;
+                if *self.fast_count() > Self::FAST_LIMIT {
+                    self.flush_fast_bins()
+                }
+            }
+            None
+        } else if unsafe { self.deferred_coalescing() } {
+            unsafe { self.cache(Self::DEFERRED_INDEX, chunk) }
+            None
+        } else {
+            unsafe { self.free_chunk(chunk) }
         }
-        Ok(user_data)
     }
 
-    unsafe fn dealloc(&mut self, user_data: *mut u8, layout: Layout) {
-        let mut chunk = unsafe { Chunk::from_user_data(user_data, layout, self.limit) };
+    // push the freed `chunk` to the cached ones of slot `index`, keeping it in use
+    unsafe fn cache(&mut self, index: usize, chunk: Chunk) {
+        let head = unsafe { self.fast_slot(index) };
+        check_assert!(
+            chunks,
+            unsafe { read_link(head) } != chunk.data.as_ptr(),
+            "double free of {chunk:?}"
+        );
+        // tokens of the freed allocation fail while it is cached
+        #[cfg(feature = "alloc-id")]
+        unsafe {
+            let id = self.next_id();
+            let mut chunk = chunk;
+            chunk.set_id(id)
+        }
+        unsafe {
+            let mut chunk = chunk;
+            chunk.set_clean(false);
+            write_link(chunk.data.as_ptr().add(8).cast(), read_link(head));
+            write_link(head, chunk.data.as_ptr())
+        }
+    }
+
+    // coalesce the in-use `chunk` with the free chunks around and bin it. the binned chunk, `None`
+    // if it became the top chunk instead
+    unsafe fn free_chunk(&mut self, mut chunk: Chunk) -> Option<Chunk> {
+        unsafe { chunk.set_clean(false) }
         if let Some(mut free_lower) = unsafe { chunk.get_free_lower_chunk() } {
             unsafe {
                 self.remove_chunk(free_lower);
@@ -505,52 +1966,141 @@ impl Overlay {
                     self.remove_chunk(free_higher);
                     chunk.coalesce(free_higher);
                 }
+            } else if unsafe { self.top_policy() } == TopPolicy::UseWilderness {
+                // `chunk` becomes the top chunk, which has no higher chunk to update
+                unsafe {
+                    self.update_top_chunk(free_higher, chunk);
+                    chunk.write_in_use_and_size(false, chunk.get_size() + free_higher.get_size());
+                }
+                return None;
             } // otherwise do not coalesce with the top chunk so it remains minimum
         }
 
         unsafe { self.add_chunk(chunk) }
         // println!("{chunk:?}");
+        Some(chunk)
+    }
+
+    // the alignment padding of an in-use chunk becomes a free chunk if it is large enough for one,
+    // and the chunk starts right before `user_data` instead, where the padding indicator was. the
+    // chunk holding `user_data` is returned either way
+    unsafe fn free_padding(&mut self, mut chunk: Chunk, user_data: *mut u8) -> Chunk {
+        let padding_size = user_data as usize - 8 - chunk.data.as_ptr() as usize;
+        let size = unsafe { chunk.get_size() };
+        if padding_size < Chunk::MIN_SIZE || size - padding_size < Chunk::MIN_SIZE {
+            return chunk;
+        }
+        let mut aligned = Chunk::new(
+            NonNull::new(unsafe { user_data.sub(8) }).unwrap(),
+            self.limit,
+        );
+        unsafe {
+            // the padding indicator has all meta bits clear, so the lower chunk is free as below
+            aligned.write_in_use_and_size(true, size - padding_size);
+            chunk.write_in_use_and_size(true, padding_size);
+            self.free_chunk(chunk);
+        }
+        aligned
+    }
+
+    // the end of an in-use chunk past `layout` becomes a free chunk if it is large enough for one,
+    // coalesced with the higher chunk if that is free too
+    unsafe fn free_tail(&mut self, mut chunk: Chunk, layout: Layout) {
+        let trailer = unsafe { chunk.get_trailer() };
+        if let Some(mut remain) = unsafe { chunk.split(layout) } {
+            unsafe {
+                chunk.set_trailer(trailer);
+                // freed as an in-use chunk, as its links are still user data
+                remain.write_in_use_and_size(true, remain.get_size());
+                self.free_chunk(remain);
+            }
+        }
     }
 
+    // the user data moves within the chunk if `new_layout` is aligned otherwise, and down into a
+    // free lower chunk if it does not fit, either copying at most `copy_limit` bytes
     unsafe fn realloc(
         &mut self,
         user_data: *mut u8,
         layout: Layout,
-        new_size: usize,
+        new_layout: Layout,
+        copy_limit: usize,
     ) -> Option<NonNull<u8>> {
+        // moved by the caller instead
+        if cfg!(feature = "minimal") {
+            return None;
+        }
         let mut chunk = unsafe { Chunk::from_user_data(user_data, layout, self.limit) };
-        let new_layout = Layout::from_size_align(new_size, layout.align()).unwrap();
-        if let Some(user_data) = unsafe { chunk.get_user_data(new_layout) } {
-            return Some(user_data);
+        let new_size = new_layout.size();
+        let copy_size = usize::min(layout.size(), new_size);
+        let realigned = {
+            let addr = chunk.data.as_ptr().wrapping_add(8);
+            addr.wrapping_add(addr.align_offset(new_layout.align())) != user_data
+        };
+        if realigned && copy_size > copy_limit {
+            return None;
+        }
+        if let Some(new_user_data) = unsafe { chunk.get_user_data(new_layout) } {
+            if realigned {
+                unsafe {
+                    copy(user_data, new_user_data.as_ptr(), copy_size);
+                    chunk.set_padding(new_user_data)
+                }
+            }
+            if new_size < layout.size() || realigned {
+                unsafe {
+                    let chunk = self.free_padding(chunk, new_user_data.as_ptr());
+                    self.free_tail(chunk, new_layout)
+                }
+            }
+            return Some(new_user_data);
         }
 
         // println!("{chunk:?} {layout:?} -> {new_size}");
         // also falling back for the top chunk since it does not have higher chunk
-        let Some(free_higher) = (unsafe { chunk.get_free_higher_chunk() }) else {
-            return None;
-        };
-        if unsafe { free_higher.is_top() }
+        let free_higher = unsafe { chunk.get_free_higher_chunk() }
+            .filter(|free_higher| unsafe { !free_higher.is_top() });
+        let Some(free_higher) = free_higher.filter(|free_higher| {
             // best effort shortcut to fallback
             // it should be possible to "precisely" fallback if checking with `user_data` right?
-            || unsafe { chunk.get_size() + free_higher.get_size() } < new_size + Chunk::META_SIZE
-        {
-            return None;
-        }
+            new_size.saturating_add(Chunk::META_SIZE)
+                <= unsafe { chunk.get_size() + free_higher.get_size() }
+        }) else {
+            return unsafe {
+                self.realloc_lower(
+                    chunk,
+                    user_data,
+                    layout,
+                    new_layout,
+                    free_higher,
+                    copy_limit,
+                )
+            };
+        };
 
+        let trailer = unsafe { chunk.get_trailer() };
         unsafe {
             self.remove_chunk(free_higher);
             chunk.coalesce(free_higher);
         }
         // println!("{chunk:?}");
 
-        if let Some(user_data) = unsafe { chunk.get_user_data(new_layout) } {
+        if let Some(new_user_data) = unsafe { chunk.get_user_data(new_layout) } {
+            if realigned {
+                unsafe {
+                    copy(user_data, new_user_data.as_ptr(), copy_size);
+                    chunk.set_padding(new_user_data)
+                }
+            }
             let remain = unsafe { chunk.split(new_layout) };
             // println!("{chunk:?}");
             if let Some(remain) = remain {
                 unsafe { self.add_chunk(remain) }
             }
-            Some(user_data)
+            unsafe { chunk.set_trailer(trailer) }
+            Some(new_user_data)
         } else {
+            unsafe { chunk.set_trailer(trailer) }
             // feels like unnecessary to revert the coalescing
             // the chunk will be deallocated as a whole shortly, and the coalescing will happen
             // again if we revert it now
@@ -559,366 +2109,3774 @@ impl Overlay {
         }
     }
 
-    fn new(space: &mut impl Space) -> Self {
-        let ptr_range = space.as_mut_ptr_range();
-        Self {
+    // grow into the free lower chunk, and the free higher one if any, moving the user data down to
+    // the start of them, which is cheaper than moving it anywhere else. `None` if it does not fit
+    // there either, with nothing changed
+    unsafe fn realloc_lower(
+        &mut self,
+        chunk: Chunk,
+        user_data: *mut u8,
+        layout: Layout,
+        new_layout: Layout,
+        free_higher: Option<Chunk>,
+        copy_limit: usize,
+    ) -> Option<NonNull<u8>> {
+        let mut lower = unsafe { chunk.get_free_lower_chunk() }?;
+        let size = unsafe {
+            lower.get_size()
+                + chunk.get_size()
+                + free_higher.map_or(0, |free_higher| free_higher.get_size())
+        };
+        let new_user_data = lower.data.as_ptr().wrapping_add(8);
+        let align_offset = new_user_data.align_offset(new_layout.align());
+        let copy_size = usize::min(layout.size(), new_layout.size());
+        if copy_size > copy_limit
+            || new_layout
+                .size()
+                .checked_add(align_offset)
+                .is_none_or(|new_size| new_size > size - Chunk::META_SIZE)
+        {
+            return None;
+        }
+        let new_user_data = NonNull::new(new_user_data.wrapping_add(align_offset)).unwrap();
+
+        let trailer = unsafe { chunk.get_trailer() };
+        unsafe {
+            self.remove_chunk(lower);
+            if let Some(free_higher) = free_higher {
+                self.remove_chunk(free_higher)
+            }
+            // overlapping, and over the links of the lower chunk and the metadata of the chunk
+            copy(user_data, new_user_data.as_ptr(), copy_size);
+            lower.set_clean(false);
+            lower.set_in_use_and_size(true, size);
+            lower.set_padding(new_user_data);
+            lower.set_trailer(trailer);
+            self.free_tail(lower, new_layout)
+        }
+        Some(new_user_data)
+    }
+
+    // the largest size `realloc` can grow to without moving, keep in sync with the above
+    unsafe fn grow_hint(&self, user_data: *mut u8, layout: Layout) -> usize {
+        if cfg!(feature = "minimal") {
+            return layout.size();
+        }
+        let chunk = unsafe { Chunk::from_user_data(user_data, layout, self.limit) };
+        let mut end = unsafe { chunk.data.as_ptr().add(chunk.get_size()) };
+        if let Some(free_higher) = unsafe { chunk.get_free_higher_chunk() } {
+            if unsafe { !free_higher.is_top() } {
+                end = unsafe { end.add(free_higher.get_size()) }
+            }
+        }
+        end as usize - Chunk::TRAILER_SIZE - user_data as usize
+    }
+
+    // clear the links and the footer that an allocation taken from a clean chunk may have in its
+    // user data, and return whether the rest is zero, i.e. whether the chunk is clean
+    unsafe fn clear_clean(&self, user_data: *mut u8, size: usize) -> bool {
+        let chunk = unsafe { Chunk::from_any_user_data(user_data, self.limit) };
+        if unsafe { !chunk.get_clean() } {
+            return false;
+        }
+        let data = chunk.data.as_ptr();
+        let user_range = user_data..user_data.wrapping_add(size);
+        for word in unsafe { [data.add(8), data.add(16), data.add(chunk.get_size() - 8)] } {
+            if user_range.contains(&word) {
+                unsafe { word.cast::<u64>().write(0) }
+            }
+        }
+        true
+    }
+
+    unsafe fn usable_size(&self, user_data: *mut u8, layout: Layout) -> usize {
+        let chunk = unsafe { Chunk::from_user_data(user_data, layout, self.limit) };
+        let end = unsafe { chunk.data.as_ptr().add(chunk.get_size()) };
+        end as usize - Chunk::TRAILER_SIZE - user_data as usize
+    }
+
+    fn new(space: &mut impl Space) -> Self {
+        let ptr_range = space.as_mut_ptr_range();
+        Self {
             space: NonNull::new(ptr_range.start).unwrap(),
-            #[cfg(any(dev, test, feature = "paranoid"))]
+            #[cfg(any(dev, test, feature = "check-bounds"))]
             limit: NonNull::new(ptr_range.end).unwrap(),
-            #[cfg(not(any(dev, test, feature = "paranoid")))]
+            #[cfg(not(any(dev, test, feature = "check-bounds")))]
             limit: (),
         }
     }
 
+    // allocate without growing the space, the top chunk is returned on failure
+    unsafe fn try_alloc_in_space(space: &mut impl Space, layout: Layout) -> Result<*mut u8, Chunk> {
+        unsafe { Self::try_alloc_scanning(space, layout, usize::MAX) }
+            .map_err(|top| top.expect("top chunk always reached without scan limit"))
+    }
+
+    unsafe fn try_alloc_scanning(
+        space: &mut impl Space,
+        layout: Layout,
+        scan: usize,
+    ) -> Result<*mut u8, Option<Chunk>> {
+        check_assert_eq!(chunks, space.first(), Some(&Self::MAGIC));
+        let end = space.as_mut_ptr_range().end;
+        let mut overlay = Self::new(space);
+        let mut layout = layout;
+        if layout.size() == 0 {
+            match unsafe { overlay.zero_size() } {
+                ZeroSize::Dangling => return Ok(without_provenance_mut(layout.align())),
+                // a byte past the pointer, or a chunk padded for a large alignment ends right at
+                // it, which makes the pointer the header of the next chunk
+                ZeroSize::MinChunk => layout = Layout::from_size_align(1, layout.align()).unwrap(),
+                ZeroSize::Null => return Ok(null_mut()),
+            }
+        }
+        let user_data = unsafe { overlay.alloc(layout, scan) }?.as_ptr();
+        unsafe {
+            overlay.sanity_check();
+            overlay.audit(user_data, layout.size(), end);
+        }
+        Ok(user_data)
+    }
+
     unsafe fn alloc_in_space(space: &mut impl Space, layout: Layout) -> *mut u8 {
-        debug_assert_eq!(space.first(), Some(&0x82));
+        match unsafe { Self::try_alloc_in_space(space, layout) } {
+            Ok(user_data) => user_data,
+            Err(top) => unsafe { Self::grow_in_space(space, top, layout) },
+        }
+    }
+
+    // grow for `layout` which does not fit before the top chunk `top`, and allocate it. a space may
+    // grow to another length than asked, e.g. rounded to huge pages or capped, so whatever it grew
+    // is merged and growing is retried from there until the allocation fits
+    unsafe fn grow_in_space(space: &mut impl Space, mut top: Chunk, layout: Layout) -> *mut u8 {
+        loop {
+            let size = space.len();
+            let Some((min_len, len)) = (unsafe { Self::new(space).growth(top, layout, size) })
+            else {
+                return null_mut();
+            };
+            let base = space.as_ptr();
+            if !space.set_size(len) && !space.set_size(min_len) {
+                return null_mut();
+            }
+            // a space may move to grow, e.g. `Mmap::movable`, taking the whole heap along
+            if space.as_ptr() != base {
+                let offset = top.data.as_ptr() as usize - base as usize;
+                top = Chunk::new(
+                    NonNull::new(unsafe { space.as_mut_ptr().add(offset) }).unwrap(),
+                    Self::new(space).limit,
+                )
+            }
+            let mut new_size = space.len();
+            if !new_size.is_multiple_of(8) && space.set_size(new_size / 8 * 8) {
+                new_size = space.len()
+            }
+            // too little to hold a chunk, the heap must end where it did
+            if !new_size.is_multiple_of(8) || new_size < size + Chunk::MIN_SIZE {
+                space.set_size(size);
+                return null_mut();
+            }
+            unsafe { Self::merge_growth(space, top, size) };
+            match unsafe { Self::try_alloc_in_space(space, layout) } {
+                Ok(user_data) => return user_data,
+                Err(new_top) => top = new_top,
+            }
+        }
+    }
+
+    // merge the space grown from `size` bytes into the heap, `top` is the top chunk before growing.
+    // the merged chunk stays clean if the grown bytes are zero, with the metadata that ends up in
+    // the middle of it cleared
+    unsafe fn merge_growth(space: &mut impl Space, mut top: Chunk, size: usize) {
+        let zeroed = space.grows_zeroed();
+        let old_end = unsafe { space.as_mut_ptr().add(size) };
         let mut overlay = Self::new(space);
-        let user_data = match unsafe { overlay.alloc(layout) } {
-            Ok(user_data) => user_data.as_ptr(),
-            Err(mut top) => {
-                let size = space.len();
-                if !space.grow(size + layout.size() + layout.align() + Chunk::META_SIZE) {
-                    null_mut()
+        top.limit = overlay.limit; // the only `Chunk` we are keeping
+        let new_size = space.len();
+        assert_eq!(new_size % 8, 0);
+        unsafe {
+            if overlay.top_policy() == TopPolicy::UseWilderness {
+                top.write_in_use_and_size(false, top.get_size() + new_size - size);
+                if zeroed && top.get_clean() {
+                    *old_end.sub(8).cast::<u64>() = 0
                 } else {
-                    overlay = Self::new(space);
-                    top.limit = overlay.limit; // the only `Chunk` we are keeping
-                    let new_size = space.len();
-                    assert_eq!(new_size % 8, 0);
-                    unsafe {
-                        let mut new_top = Chunk::new(
-                            NonNull::new(space.as_mut_ptr_range().end.sub(Chunk::MIN_SIZE))
-                                .unwrap(),
-                            overlay.limit,
-                        );
-                        new_top.set_prev(None);
-                        new_top.set_next(None);
-                        new_top.set_in_use_and_size(false, Chunk::MIN_SIZE);
-                        overlay.update_top_chunk(top, new_top);
-                        top.set_in_use_and_size(false, new_size - size);
-                        if let Some(mut free_lower) = top.get_free_lower_chunk() {
-                            // not coalescing because `top` looks like a top chunk
-                            overlay.remove_chunk(free_lower);
-                            free_lower
-                                .set_in_use_and_size(false, free_lower.get_size() + top.get_size());
-                            overlay.add_chunk(free_lower);
-                        } else {
-                            overlay.add_chunk(top);
-                        }
-                        overlay.alloc(layout)
+                    top.set_clean(false)
+                }
+            } else {
+                let mut new_top = Chunk::new(
+                    NonNull::new(space.as_mut_ptr_range().end.sub(Chunk::MIN_SIZE)).unwrap(),
+                    overlay.limit,
+                );
+                new_top.init_free(Chunk::MIN_SIZE);
+                new_top.set_prev(None);
+                new_top.set_next(None);
+                overlay.update_top_chunk(top, new_top);
+                top.set_in_use_and_size(false, new_size - size);
+                // the old top chunk is metadata all over
+                let links = top.data.as_ptr().add(8);
+                if zeroed {
+                    links
+                        .add(16)
+                        .write_bytes(0, old_end.offset_from(links) as usize - 16)
+                }
+                top.set_clean(zeroed);
+                if let Some(mut free_lower) = top.get_free_lower_chunk() {
+                    // not coalescing because `top` looks like a top chunk
+                    overlay.remove_chunk(free_lower);
+                    let clean = zeroed && free_lower.get_clean();
+                    free_lower.set_in_use_and_size(false, free_lower.get_size() + top.get_size());
+                    if clean {
+                        // the footer of the lower chunk, and the header and links of the top one
+                        top.data.as_ptr().sub(8).write_bytes(0, 32)
                     }
-                    .expect("second allocating try always success")
-                    .as_ptr()
+                    free_lower.set_clean(clean);
+                    overlay.add_chunk(free_lower);
+                } else {
+                    overlay.add_chunk(top);
                 }
             }
-        };
-        unsafe { overlay.sanity_check() }
-        user_data
+        }
     }
 
     unsafe fn dealloc_in_space(space: &mut impl Space, user_data: *mut u8, layout: Layout) {
-        debug_assert_eq!(space.first(), Some(&0x82));
+        check_assert_eq!(chunks, space.first(), Some(&Self::MAGIC));
+        let end = space.as_mut_ptr_range().end;
+        let mut overlay = Self::new(space);
+        let freed = unsafe {
+            overlay.audit(user_data, layout.size(), end);
+            let freed = overlay.dealloc(user_data, layout);
+            overlay.sanity_check();
+            freed
+        };
+        if let Some(chunk) = freed {
+            unsafe { Self::discard_free(space, chunk) }
+        }
+    }
+
+    // give the pages of a large free chunk back to the space if it can, e.g. when it is in the
+    // middle of the heap so shrinking cannot, and the chunk is clean then. a clean chunk is zero
+    // already, given back or never touched, and the free end of a shrinking space is left to
+    // shrinking
+    unsafe fn discard_free(space: &mut impl Space, mut chunk: Chunk) {
+        let size = unsafe { chunk.get_size() };
+        if size < Self::DISCARD_THRESHOLD || unsafe { chunk.get_clean() } {
+            return;
+        }
+        // a free higher chunk is the top chunk, as it is not coalesced with
+        if space.can_shrink() && unsafe { !chunk.get_higher_chunk().get_in_use() } {
+            return;
+        }
+        // between the links and the footer, as in poisoning
+        let offset = chunk.data.as_ptr() as usize - space.as_ptr() as usize;
+        let range = offset + 24..offset + size - 8;
+        let Some(discarded) = space.discard(range.clone()) else {
+            return;
+        };
+        let base = space.as_mut_ptr();
+        unsafe {
+            base.add(range.start)
+                .write_bytes(0, discarded.start - range.start);
+            base.add(discarded.end)
+                .write_bytes(0, range.end - discarded.end);
+            chunk.set_clean(true)
+        }
+    }
+
+    // the highest chunk, which is always free and so can be found by its footer, and the free
+    // chunk right below it with `KeepWilderness`. together they are the free end of the heap
+    unsafe fn tail_chunks(&self, end: *mut u8) -> (Chunk, Option<Chunk>) {
+        let size = unsafe { *end.sub(8).cast::<u64>() } as usize;
+        let top = Chunk::new(NonNull::new(unsafe { end.sub(size) }).unwrap(), self.limit);
+        (top, unsafe { top.get_free_lower_chunk() })
+    }
+
+    // the bytes to keep at the free end of the heap when shrinking after freeing, `None` if it is
+    // not worth it. it keeps the headroom that growing would add, and only shrinks by at least
+    // `SHRINK_THRESHOLD` more than that, so a heap does not shrink and grow back and forth
+    unsafe fn shrink_pad(space: &mut impl Space) -> Option<usize> {
+        if !space.can_shrink() {
+            return None;
+        }
+        let len = space.len();
+        let end = space.as_mut_ptr_range().end;
+        let overlay = Self::new(space);
+        let (top, free_lower) = unsafe { overlay.tail_chunks(end) };
+        let tail = end as usize - free_lower.unwrap_or(top).data.as_ptr() as usize;
+        if tail < Self::SHRINK_THRESHOLD + Chunk::MIN_SIZE {
+            return None;
+        }
+        let headroom = unsafe { overlay.headroom(len) };
+        ((tail - Chunk::MIN_SIZE).saturating_sub(headroom) >= Self::SHRINK_THRESHOLD)
+            .then_some(headroom)
+    }
+
+    // give the free end of the heap back to the space but `pad` bytes besides the top chunk, the
+    // released bytes are returned
+    unsafe fn shrink_in_space(space: &mut impl Space, pad: usize) -> usize {
+        check_assert_eq!(chunks, space.first(), Some(&Self::MAGIC));
+        if !space.can_shrink() {
+            return 0;
+        }
+        let len = space.len();
+        let ptr_range = space.as_mut_ptr_range();
+        let mut overlay = Self::new(space);
+        let (top, free_lower) = unsafe { overlay.tail_chunks(ptr_range.end) };
+        let tail = free_lower.unwrap_or(top).data.as_ptr();
+        // with `KeepWilderness` the pad is a free chunk below the minimum top chunk
+        let top_policy = unsafe { overlay.top_policy() };
+        let Some(kept) = pad
+            .checked_next_multiple_of(8)
+            .map(|pad| match top_policy {
+                TopPolicy::KeepWilderness if pad != 0 => usize::max(pad, Chunk::MIN_SIZE),
+                _ => pad,
+            })
+            .and_then(|pad| pad.checked_add(Chunk::MIN_SIZE))
+        else {
+            return 0;
+        };
+        // a heap always has room for two chunks
+        let offset = tail as usize - ptr_range.start as usize;
+        let min_len = unsafe { overlay.start_offset() } + 2 * Chunk::MIN_SIZE;
+        let Some(new_len) = offset
+            .checked_add(kept)
+            .map(|new_len| usize::max(new_len, min_len))
+            .filter(|new_len| *new_len < len)
+        else {
+            return 0;
+        };
+        let kept = new_len - offset;
+
+        // nothing past the new end can be touched after shrinking, so the free chunk there is
+        // unlinked before, and the links to the top chunk are kept aside
+        if let Some(free_lower) = free_lower {
+            unsafe { overlay.remove_chunk(free_lower) }
+        }
+        let prev = unsafe { top.get_prev() }.map(|chunk| chunk.data);
+        let last_bin = overlay.bin_index_of_size(usize::MAX);
+        let bin_head = unsafe { overlay.get_bin_chunk(last_bin) } == Some(top);
+        if !space.set_size(new_len) {
+            if let Some(free_lower) = free_lower {
+                unsafe { overlay.add_chunk(free_lower) }
+            }
+            return 0;
+        }
+
         let mut overlay = Self::new(space);
+        let top_size = match top_policy {
+            TopPolicy::KeepWilderness => Chunk::MIN_SIZE,
+            TopPolicy::UseWilderness => kept,
+        };
+        let new_end = unsafe { tail.add(kept) };
+        let mut new_top = Chunk::new(
+            NonNull::new(unsafe { new_end.sub(top_size) }).unwrap(),
+            overlay.limit,
+        );
+        let prev = prev.map(|data| Chunk::new(data, overlay.limit));
         unsafe {
-            overlay.dealloc(user_data, layout);
+            // the chunk below the free end is in use, as free chunks are coalesced
+            new_top.init_free(top_size);
+            if kept == top_size {
+                new_top.set_lower_in_use(true)
+            }
+            new_top.set_next(None);
+            new_top.set_prev(prev);
+            if let Some(mut prev) = prev {
+                prev.set_next(Some(new_top))
+            }
+            if bin_head {
+                overlay.set_bin_chunk(last_bin, Some(new_top))
+            }
+            if kept != top_size {
+                let mut free = Chunk::new(NonNull::new(tail).unwrap(), overlay.limit);
+                free.init_free(kept - top_size);
+                free.set_lower_in_use(true);
+                overlay.add_chunk(free)
+            }
             overlay.sanity_check();
         }
+        len - new_len
+    }
 
-        // TODO do space shrinking
+    // reallocate without moving elsewhere than down into the free lower chunk, copying at most
+    // `copy_limit` bytes then, `None` if it has to move
+    unsafe fn try_realloc_in_space(
+        space: &mut impl Space,
+        user_data: *mut u8,
+        layout: Layout,
+        new_layout: Layout,
+        copy_limit: usize,
+    ) -> Option<*mut u8> {
+        check_assert_eq!(chunks, space.first(), Some(&Self::MAGIC));
+        let end = space.as_mut_ptr_range().end;
+        let mut overlay = Self::new(space);
+        unsafe { overlay.audit(user_data, layout.size(), end) }
+        let user_data =
+            unsafe { overlay.realloc(user_data, layout, new_layout, copy_limit) }?.as_ptr();
+        unsafe {
+            overlay.sanity_check();
+            overlay.audit(user_data, new_layout.size(), end);
+        }
+        Some(user_data)
     }
 
     unsafe fn realloc_in_space(
         space: &mut impl Space,
         user_data: *mut u8,
         layout: Layout,
-        new_size: usize,
+        new_layout: Layout,
     ) -> *mut u8 {
-        debug_assert_eq!(space.first(), Some(&0x82));
-        let mut overlay = Self::new(space);
-        if let Some(user_data) = unsafe { overlay.realloc(user_data, layout, new_size) } {
-            unsafe { overlay.sanity_check() }
-            return user_data.as_ptr();
+        if let Some(user_data) =
+            unsafe { Self::try_realloc_in_space(space, user_data, layout, new_layout, usize::MAX) }
+        {
+            return user_data;
         }
 
-        let new_user_data = unsafe {
-            Self::alloc_in_space(
-                space,
-                Layout::from_size_align(new_size, layout.align()).unwrap(),
-            )
-        };
+        let base = space.as_ptr();
+        let new_user_data = unsafe { Self::alloc_in_space(space, new_layout) };
         if new_user_data.is_null() {
             null_mut()
         } else {
+            let user_data = unsafe { space.as_mut_ptr().add(user_data as usize - base as usize) };
             unsafe {
-                copy_nonoverlapping(user_data, new_user_data, layout.size());
+                Self::move_in_space(space, user_data, layout, new_user_data, new_layout);
                 Self::dealloc_in_space(space, user_data, layout);
             }
-            new_user_data
+            new_user_data
+        }
+    }
+
+    // the part of reallocating by moving that happens before freeing the old allocation
+    unsafe fn move_in_space(
+        space: &mut impl Space,
+        user_data: *mut u8,
+        layout: Layout,
+        new_user_data: *mut u8,
+        new_layout: Layout,
+    ) {
+        if Chunk::TRAILER_SIZE != 0 {
+            unsafe {
+                let overlay = Self::new(space);
+                let trailer = Chunk::from_user_data(user_data, layout, overlay.limit).get_trailer();
+                Chunk::from_user_data(new_user_data, new_layout, overlay.limit).set_trailer(trailer)
+            }
+        }
+        // shrinking moves too with `minimal`
+        let size = usize::min(layout.size(), new_layout.size());
+        unsafe { copy_nonoverlapping(user_data, new_user_data, size) }
+    }
+
+    // the top chunk is the end of the free list
+    unsafe fn top_chunk(&self) -> Chunk {
+        let mut chunk = unsafe { self.find_smallest(usize::MAX) };
+        while let Some(next_chunk) = unsafe { chunk.get_next() } {
+            chunk = next_chunk
+        }
+        chunk
+    }
+}
+
+// what `GlobalAlloc::dealloc` and `realloc` do with a pointer outside of the space, which cannot
+// be from the heap. the pointer is never touched by the heap itself
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ForeignPolicy {
+    // panic, which aborts inside a global allocator
+    #[default]
+    Abort,
+    // leave it alone and count it in `OpCounts::foreign`, reallocating fails
+    Ignore,
+    // free it with the deallocator it is from, e.g. the system one when the heap is switched in
+    // after start or shares pointers across FFI. reallocating moves it into the heap first
+    Forward(unsafe fn(*mut u8, Layout)),
+}
+
+// a spin lock, or one holding a critical section with the `critical-section` feature
+#[cfg(not(feature = "critical-section"))]
+pub type DefaultLock = spin::Mutex<()>;
+#[cfg(feature = "critical-section")]
+pub type DefaultLock = crate::critical::RawCriticalSection;
+
+// both locks are of `R`, the default one unless another one is plugged in, e.g. a parking one
+// with std
+pub struct Allocator<S, R = DefaultLock> {
+    heap: Mutex<R, S>,
+    // held by the only thread that is growing the space with the heap released
+    grow_lock: Mutex<R, ()>,
+    // only for diagnostics, to tell apart heaps when there are many of them
+    name: Option<&'static str>,
+    counters: Counters,
+    // cleared until the heap is laid out in the space, see `lazy`
+    laid_out: AtomicBool,
+    foreign: ForeignPolicy,
+}
+
+// the constructors of the default lock, as a default type parameter does not settle the type of
+// `Allocator::new(space)`. the ones with another lock are below
+impl<S> Allocator<S> {
+    // panic if the heap does not fit in the space, see `try_new`
+    pub fn new(space: S) -> Self
+    where
+        S: Space,
+    {
+        Self::with_config(space, Config::default())
+    }
+
+    pub fn with_config(space: S, config: Config) -> Self
+    where
+        S: Space,
+    {
+        Self::try_with_config(space, config)
+            .unwrap_or_else(|error| panic!("cannot create a heap: {error:?}"))
+    }
+
+    pub fn try_new(space: S) -> Result<Self, NewError>
+    where
+        S: Space,
+    {
+        Self::try_with_config(space, Config::default())
+    }
+
+    pub fn try_with_config(space: S, config: Config) -> Result<Self, NewError>
+    where
+        S: Space,
+    {
+        Self::try_with_lock(space, config)
+    }
+
+    // take over a space that holds a heap already, e.g. one that persists across runs or is shared
+    // between processes, which is verified to be of this layout and intact
+    pub fn attach(space: S) -> Result<Self, CheckError>
+    where
+        S: Space,
+    {
+        Self::attach_with_lock(space)
+    }
+
+    pub fn new_named(space: S, name: &'static str) -> Self
+    where
+        S: Space,
+    {
+        Self::new(space).with_name(Some(name))
+    }
+
+    // the heap is laid out in the space with the default config on first use instead, so it can be
+    // a `static`, e.g. the global allocator over a `space::Static`, or over a `space::Mmap` which is
+    // resized on first use as it starts empty. the space must not move after that, which a `static`
+    // never does. panic on first use if the empty space cannot be resized.
+    pub const fn lazy(space: S) -> Self {
+        Self::lazy_with_lock(space)
+    }
+}
+
+impl<S, R> Allocator<S, R>
+where
+    R: RawMutex,
+{
+    // e.g. `Allocator::<_, parking_lot::RawMutex>::try_with_lock(space, config)`
+    pub fn try_with_lock(mut space: S, config: Config) -> Result<Self, NewError>
+    where
+        S: Space,
+    {
+        let len = space.len();
+        unsafe { Overlay::new(&mut space).init(len, config, false) }?;
+        Ok(Self {
+            heap: Mutex::new(space),
+            grow_lock: Mutex::new(()),
+            name: None,
+            counters: Counters::new(),
+            laid_out: AtomicBool::new(true),
+            foreign: ForeignPolicy::Abort,
+        })
+    }
+
+    pub fn attach_with_lock(mut space: S) -> Result<Self, CheckError>
+    where
+        S: Space,
+    {
+        if space.len() < 8 * Overlay::bins_slots_of(Bins::Compact, false) {
+            return Err(CheckError::Marker);
+        }
+        Self::check_space(&mut space)?;
+        Ok(Self {
+            heap: Mutex::new(space),
+            grow_lock: Mutex::new(()),
+            name: None,
+            counters: Counters::new(),
+            laid_out: AtomicBool::new(true),
+            foreign: ForeignPolicy::Abort,
+        })
+    }
+
+    pub const fn lazy_with_lock(space: S) -> Self {
+        Self {
+            heap: Mutex::new(space),
+            grow_lock: Mutex::new(()),
+            name: None,
+            counters: Counters::new(),
+            laid_out: AtomicBool::new(false),
+            foreign: ForeignPolicy::Abort,
+        }
+    }
+
+    pub(crate) fn with_name(mut self, name: Option<&'static str>) -> Self {
+        self.name = name;
+        self
+    }
+
+    // const, so a lazy heap in a `static` can have one too
+    pub const fn with_foreign_policy(mut self, policy: ForeignPolicy) -> Self {
+        self.foreign = policy;
+        self
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    unsafe fn dealloc_foreign(&self, ptr: *mut u8, layout: Layout) {
+        match self.foreign {
+            ForeignPolicy::Abort => panic!("{ptr:?} is not from the heap"),
+            ForeignPolicy::Ignore => {}
+            ForeignPolicy::Forward(dealloc) => unsafe { dealloc(ptr, layout) },
+        }
+        self.counters.foreign.fetch_add(1, Relaxed);
+    }
+
+    // the new allocation is counted as one
+    unsafe fn realloc_foreign(&self, ptr: *mut u8, layout: Layout, new_layout: Layout) -> *mut u8
+    where
+        Self: GlobalAlloc,
+    {
+        if let ForeignPolicy::Forward(_) = self.foreign {
+            let new_ptr = unsafe { self.alloc(new_layout) };
+            if !new_ptr.is_null() {
+                unsafe {
+                    let size = usize::min(layout.size(), new_layout.size());
+                    copy_nonoverlapping(ptr, new_ptr, size);
+                    self.dealloc_foreign(ptr, layout)
+                }
+            }
+            return new_ptr;
+        }
+        unsafe { self.dealloc_foreign(ptr, layout) };
+        null_mut()
+    }
+
+    pub(crate) fn acquire_space(&self) -> MutexGuard<'_, R, S>
+    where
+        S: Space,
+    {
+        let mut space = self.heap.lock();
+        // ordered by the lock
+        if !self.laid_out.load(Relaxed) {
+            let grown = space.is_empty();
+            if grown {
+                assert!(
+                    space.set_size(Overlay::LAZY_SIZE),
+                    "cannot resize the space"
+                );
+            }
+            let len = space.len();
+            let zeroed = grown && space.grows_zeroed();
+            if let Err(error) =
+                unsafe { Overlay::new(&mut *space).init(len, Config::default(), zeroed) }
+            {
+                panic!("cannot create a heap: {error:?}")
+            }
+            self.laid_out.store(true, Relaxed)
+        }
+        #[cfg(feature = "chaos")]
+        crate::chaos::delay();
+        space
+    }
+
+    pub fn sanity_check(&self)
+    where
+        S: Space,
+    {
+        // the checks below panic without telling which heap is broken
+        #[cfg(any(test, dev, feature = "check-bins"))]
+        if let (Some(name), Err(error)) = (self.name, self.check()) {
+            panic!("heap {name} is broken: {error:?}")
+        }
+        unsafe { Overlay::new(&mut *self.acquire_space()).sanity_check() }
+    }
+
+    pub fn chunks(&self) -> Chunks<'_, S, R>
+    where
+        S: Space,
+    {
+        let mut space = self.acquire_space();
+        let mut overlay = Overlay::new(&mut *space);
+        // the cached chunks would be walked as in use
+        unsafe { overlay.consolidate() };
+        let chunk = unsafe { overlay.start_chunk() };
+        Chunks {
+            _space: space,
+            chunk: Some(chunk),
+        }
+    }
+
+    pub fn check(&self) -> Result<(), CheckError>
+    where
+        S: Space,
+    {
+        Self::check_space(&mut *self.acquire_space())
+    }
+
+    // check about `budget` bytes of chunks from `cursor` on and move it past them, e.g. a few KB at
+    // a time from the idle loop of a long running device, so a bit flipped in the boundary tags or
+    // in the filling of a poisoned free chunk is found before the heap trips over it. true once
+    // the end of the heap is reached, where the cursor starts over
+    pub fn scrub(&self, cursor: &mut ScrubCursor, budget: usize) -> Result<bool, CheckError>
+    where
+        S: Space,
+    {
+        let mut space = self.acquire_space();
+        let end = space.as_mut_ptr_range().end;
+        cursor.0 = unsafe { Overlay::new(&mut *space).scrub(end, cursor.0, budget) }?;
+        Ok(cursor.0 == 0)
+    }
+
+    pub(crate) fn check_space(space: &mut S) -> Result<(), CheckError>
+    where
+        S: Space,
+    {
+        let end = space.as_mut_ptr_range().end;
+        unsafe { Overlay::new(space).check(end) }
+    }
+
+    // one line per chunk, after the name if there is one
+    pub fn dump(&self, out: &mut impl core::fmt::Write) -> core::fmt::Result
+    where
+        S: Space,
+    {
+        if let Some(name) = self.name {
+            writeln!(out, "heap {name}")?
+        }
+        for info in self.chunks() {
+            writeln!(
+                out,
+                "{:?} {} {}",
+                info.addr,
+                info.size,
+                if info.in_use { "in_use" } else { "free" }
+            )?
+        }
+        Ok(())
+    }
+
+    // for callers that may give back any size up to the usable one, which count the allocation
+    // as the usable size instead so the requested bytes do not drift
+    #[cfg(any(feature = "allocator-api", feature = "allocator-api2"))]
+    pub(crate) fn recount_requested(&self, size: usize, new_size: usize) {
+        self.counters.resize(size, new_size)
+    }
+
+    // sizes are chunk sizes, i.e. including chunk metadata, other than the requested ones
+    pub fn stats(&self) -> Stats
+    where
+        S: Space,
+    {
+        let mut stats = Stats::default();
+        for info in self.chunks() {
+            stats.add_chunk(info)
+        }
+        self.counters.load(&mut stats);
+        #[cfg(feature = "mmap-stats")]
+        {
+            stats.syscalls = self.acquire_space().syscall_stats();
+        }
+        stats
+    }
+
+    // bytes of the space that can never hold user data at the moment
+    pub fn overhead(&self) -> Overhead
+    where
+        S: Space,
+    {
+        let mut space = self.acquire_space();
+        let overlay = Overlay::new(&mut *space);
+        let start_offset = unsafe { overlay.start_offset() };
+        let mut overhead = Overhead {
+            bins: 8 * overlay.bins_slots(),
+            padding: start_offset - 8 * overlay.bins_slots(),
+            ..Default::default()
+        };
+        for chunk in unsafe { overlay.iter_all_chunk() } {
+            if unsafe { chunk.get_walk_next() }.is_some() {
+                overhead.headers += Chunk::META_SIZE
+            } else if unsafe { overlay.top_policy() } == TopPolicy::KeepWilderness {
+                overhead.top = unsafe { chunk.get_size() }
+            } else {
+                overhead.top = Chunk::META_SIZE
+            }
+        }
+        overhead
+    }
+
+    pub fn overhead_bytes(&self) -> usize
+    where
+        S: Space,
+    {
+        self.overhead().total()
+    }
+
+    // the chunk that `addr` falls into, `None` if `addr` is outside of the heap or inside the bins
+    pub fn find_chunk(&self, addr: *const u8) -> Option<ChunkInfo>
+    where
+        S: Space,
+    {
+        let mut space = self.acquire_space();
+        if !space.as_ptr_range().contains(&addr) {
+            return None;
+        }
+        let overlay = Overlay::new(&mut *space);
+        unsafe { overlay.iter_all_chunk() }
+            .take_while(|chunk| chunk.data.as_ptr() as *const u8 <= addr)
+            .last()
+            .map(|chunk| unsafe { chunk.info() })
+            .filter(|info| info.contains(addr))
+    }
+
+    // a token for smart pointers to tell later whether `ptr` still refers to the same allocation,
+    // which is the allocation id, so it changes whenever the chunk is freed and reused, and stays
+    // across reallocation
+    // safety: `ptr` is currently allocated by this allocator
+    #[cfg(feature = "alloc-id")]
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn token_of(&self, ptr: *mut u8) -> u64
+    where
+        S: Space,
+    {
+        let mut space = self.acquire_space();
+        let overlay = Overlay::new(&mut *space);
+        unsafe { Chunk::from_any_user_data(ptr, overlay.limit).get_id() }
+    }
+
+    // whether `ptr` is still allocated with `token`, `false` for a dangling pointer whose chunk is
+    // free or has been allocated again. any pointer can be passed, which walks the heap to find it
+    #[cfg(feature = "alloc-id")]
+    pub fn validate_token(&self, ptr: *const u8, token: u64) -> bool
+    where
+        S: Space,
+    {
+        self.find_chunk(ptr)
+            .is_some_and(|info| info.id == Some(token))
+    }
+
+    // the largest size `ptr` can be reallocated to in place right now, so containers can pick a
+    // growth that avoids copying. it is only a hint, other threads may take the space meanwhile
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn grow_hint(&self, ptr: *mut u8, layout: Layout) -> usize
+    where
+        S: Space,
+    {
+        // safety: `ptr` is currently allocated by this allocator with `layout`
+        let mut space = self.acquire_space();
+        let end = space.as_mut_ptr_range().end;
+        let overlay = Overlay::new(&mut *space);
+        unsafe {
+            overlay.audit(ptr, layout.size(), end);
+            overlay.grow_hint(ptr, layout)
+        }
+    }
+
+    // the bytes reserved by the `metadata-*` features next to an allocation, for GC bits, type ids
+    // or reference counts without a side table. they are zeroed on allocating and kept across
+    // reallocation, and the pointer is aligned to 8 bytes
+    // safety: `ptr` is currently allocated by this allocator, and the returned pointer is only
+    // valid until `ptr` is freed or reallocated
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn metadata_of(&self, ptr: *mut u8) -> NonNull<[u8; METADATA_SIZE]>
+    where
+        S: Space,
+    {
+        let mut space = self.acquire_space();
+        let overlay = Overlay::new(&mut *space);
+        unsafe { Chunk::from_any_user_data(ptr, overlay.limit).get_metadata() }
+    }
+
+    // the bytes `ptr` can actually hold, which is at least the size of `layout`
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn usable_size(&self, ptr: *mut u8, layout: Layout) -> usize
+    where
+        S: Space,
+    {
+        // safety: `ptr` is currently allocated by this allocator with `layout`
+        let mut space = self.acquire_space();
+        let end = space.as_mut_ptr_range().end;
+        let overlay = Overlay::new(&mut *space);
+        // an empty allocation is in a chunk with `ZeroSize::MinChunk` only
+        if layout.size() == 0 && unsafe { overlay.zero_size() } != ZeroSize::MinChunk {
+            return 0;
+        }
+        unsafe {
+            overlay.audit(ptr, layout.size(), end);
+            overlay.usable_size(ptr, layout)
+        }
+    }
+
+    // move an allocation into `dst`, which may be any allocator, and return where it is now, or
+    // null if `dst` cannot allocate it, leaving it here untouched
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn migrate(&self, ptr: *mut u8, layout: Layout, dst: &impl GlobalAlloc) -> *mut u8
+    where
+        S: Space,
+    {
+        // safety: `ptr` is currently allocated by this allocator with `layout`
+        let new_ptr = unsafe { dst.alloc(layout) };
+        if !new_ptr.is_null() {
+            unsafe {
+                copy_nonoverlapping(ptr, new_ptr, layout.size());
+                self.dealloc(ptr, layout)
+            }
+        }
+        new_ptr
+    }
+
+    // `migrate` each allocation in order and update its pointer, stop at the first one `dst`
+    // cannot allocate and return the number of migrated ones
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn migrate_all(
+        &self,
+        allocations: &mut [(*mut u8, Layout)],
+        dst: &impl GlobalAlloc,
+    ) -> usize
+    where
+        S: Space,
+    {
+        for (index, (ptr, layout)) in allocations.iter_mut().enumerate() {
+            let new_ptr = unsafe { self.migrate(*ptr, *layout, dst) };
+            if new_ptr.is_null() {
+                return index;
+            }
+            *ptr = new_ptr
+        }
+        allocations.len()
+    }
+
+    // allocate a chunk of `layout` into each of `ptrs` taking the heap once, for front ends that
+    // cache allocations, e.g. `small::Allocator`. return the number of allocated ones, which fill
+    // `ptrs` from the start and are fewer if the heap runs out
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn alloc_bulk(&self, layout: Layout, ptrs: &mut [*mut u8]) -> usize
+    where
+        S: Space,
+    {
+        let mut space = self.acquire_space();
+        let mut count = 0;
+        while count < ptrs.len() {
+            let base = space.as_ptr();
+            let ptr = if space.reservation().is_none() {
+                unsafe { Overlay::alloc_in_space(&mut *space, layout) }
+            } else {
+                unsafe { Overlay::try_alloc_in_space(&mut *space, layout) }.unwrap_or(null_mut())
+            };
+            if ptr.is_null() {
+                break;
+            }
+            // the ones allocated so far moved along with the space
+            if space.as_ptr() != base {
+                for ptr in &mut ptrs[..count] {
+                    *ptr = unsafe { space.as_mut_ptr().add(*ptr as usize - base as usize) }
+                }
+            }
+            ptrs[count] = ptr;
+            count += 1
+        }
+        drop(space);
+        // growing a reserved space releases the heap in between, as `alloc` does
+        if count == 0 && !ptrs.is_empty() {
+            ptrs[0] = unsafe { self.alloc(layout) };
+            return usize::from(!ptrs[0].is_null());
+        }
+        for ptr in &ptrs[..count] {
+            self.counters.alloc(layout.size(), *ptr)
+        }
+        count
+    }
+
+    // free every chunk of `ptrs`, allocated with `layout`, taking the heap once
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn dealloc_bulk(&self, ptrs: &[*mut u8], layout: Layout)
+    where
+        S: Space,
+    {
+        // safety: every pointer is currently allocated by this allocator with `layout`
+        let mut space = self.acquire_space();
+        for ptr in ptrs {
+            unsafe { Overlay::dealloc_in_space(&mut *space, *ptr, layout) }
+        }
+        self.shrink_after_free(&mut space);
+        drop(space);
+        for _ in ptrs {
+            self.counters.dealloc(layout.size())
+        }
+    }
+
+    // copy the whole heap into `space`, resized to the same length, to explore it separately from
+    // this heap. the copy lives at another address, so `space` must be aligned as this heap modulo
+    // 4096 bytes to keep every alignment padding valid, `None` if it is not or cannot be resized
+    pub fn clone_heap<T>(&self, mut space: T) -> Option<Allocator<T, R>>
+    where
+        S: Space,
+        T: Space,
+    {
+        let source = self.acquire_space();
+        if !space.set_size(source.len())
+            || (space.as_ptr() as usize).wrapping_sub(source.as_ptr() as usize) & 4095 != 0
+        {
+            return None;
+        }
+        space.copy_from_slice(&source);
+        Some(Allocator {
+            heap: Mutex::new(space),
+            grow_lock: Mutex::new(()),
+            name: self.name,
+            counters: self.counters.snapshot(),
+            laid_out: AtomicBool::new(true),
+            foreign: self.foreign,
+        })
+    }
+
+    // the address in `clone` that corresponds to `ptr` in this heap
+    pub fn translate<T, U>(&self, ptr: *mut u8, clone: &Allocator<T, U>) -> *mut u8
+    where
+        S: Space,
+        T: Space,
+        U: RawMutex,
+    {
+        let offset = ptr as usize - self.acquire_space().as_ptr() as usize;
+        unsafe { clone.acquire_space().as_mut_ptr().add(offset) }
+    }
+
+    // the start of the space. a space that moves to grow, e.g. `Mmap::movable`, moves every
+    // allocation along, so they are kept as offsets from here instead of as pointers
+    pub fn base(&self) -> *mut u8
+    where
+        S: Space,
+    {
+        self.acquire_space().as_mut_ptr()
+    }
+
+    // the space with the heap held, e.g. to flush a `space::FileMap`
+    pub fn with_space<T>(&self, f: impl FnOnce(&S) -> T) -> T
+    where
+        S: Space,
+    {
+        f(&self.acquire_space())
+    }
+}
+
+unsafe impl<S, R> GlobalAlloc for Allocator<S, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let user_data = unsafe { self.alloc_uncounted(layout) };
+        self.counters.alloc(layout.size(), user_data);
+        user_data
+    }
+
+    // the bytes fresh from a space that `Space::grows_zeroed` are not filled again
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let user_data = unsafe { self.alloc(layout) };
+        if user_data.is_null() || layout.size() == 0 {
+            return user_data;
+        }
+        let mut space = self.acquire_space();
+        if unsafe { !Overlay::new(&mut *space).clear_clean(user_data, layout.size()) } {
+            drop(space);
+            unsafe { user_data.write_bytes(0, layout.size()) }
+        }
+        user_data
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if !unsafe { self.dealloc_owned(ptr, layout) } {
+            unsafe { self.dealloc_foreign(ptr, layout) }
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        match Layout::from_size_align(new_size, layout.align()) {
+            Ok(new_layout) => unsafe { self.realloc_layout(ptr, layout, new_layout) },
+            Err(_) => null_mut(),
+        }
+    }
+}
+
+impl<S, R> Allocator<S, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    unsafe fn alloc_uncounted(&self, layout: Layout) -> *mut u8 {
+        let mut space = self.acquire_space();
+        if space.reservation().is_none() {
+            return unsafe { Overlay::alloc_in_space(&mut *space, layout) };
+        }
+        if let Ok(user_data) = unsafe { Overlay::try_alloc_in_space(&mut *space, layout) } {
+            return user_data;
+        }
+        drop(space);
+        unsafe { self.alloc_reserving(layout) }
+    }
+
+    unsafe fn realloc_uncounted(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_layout: Layout,
+    ) -> *mut u8 {
+        let mut space = self.acquire_space();
+        if space.reservation().is_none() {
+            return unsafe { Overlay::realloc_in_space(&mut *space, ptr, layout, new_layout) };
+        }
+        if let Some(user_data) = unsafe {
+            Overlay::try_realloc_in_space(&mut *space, ptr, layout, new_layout, usize::MAX)
+        } {
+            return user_data;
+        }
+        drop(space);
+
+        // the old allocation stays untouched by anyone else while allocating the new one
+        let new_ptr = unsafe { self.alloc_uncounted(new_layout) };
+        if !new_ptr.is_null() {
+            let mut space = self.acquire_space();
+            unsafe {
+                Overlay::move_in_space(&mut *space, ptr, layout, new_ptr, new_layout);
+                Overlay::dealloc_in_space(&mut *space, ptr, layout);
+            }
+            self.shrink_after_free(&mut space);
+        }
+        new_ptr
+    }
+
+    // free `ptr` if it is in the space, taking the heap once, and leave the foreign policy to the
+    // caller otherwise, e.g. `arenas::Arenas` which tries the other heaps
+    pub(crate) unsafe fn dealloc_owned(&self, ptr: *mut u8, layout: Layout) -> bool {
+        let mut space = self.acquire_space();
+        if !space.as_ptr_range().contains(&ptr.cast_const()) {
+            return false;
+        }
+        unsafe { Overlay::dealloc_in_space(&mut *space, ptr, layout) }
+        self.shrink_after_free(&mut space);
+        drop(space);
+        self.counters.dealloc(layout.size());
+        true
+    }
+
+    // give the free end of the heap back to the space but `pad` bytes, at the quiet points of a
+    // long running process, and return the released bytes. free chunks are always coalesced, so
+    // the free end is all that can go, and nothing goes if the space cannot shrink
+    pub fn trim(&self, pad: usize) -> usize {
+        let _growing = self.grow_lock.lock();
+        let mut space = self.acquire_space();
+        unsafe {
+            Overlay::new(&mut *space).consolidate();
+            Overlay::shrink_in_space(&mut *space, pad)
+        }
+    }
+
+    // coalesce the freed chunks that are kept aside by `Config::fast_bins` or
+    // `Config::deferred_coalescing`, e.g. at the quiet points of the program, so a later allocation
+    // does not pay for it
+    pub fn consolidate(&self) {
+        unsafe { Overlay::new(&mut *self.acquire_space()).consolidate() };
+    }
+
+    // skipped while a grower has the heap released, which expects the size to stay
+    fn shrink_after_free(&self, space: &mut S) {
+        let Some(pad) = (unsafe { Overlay::shrink_pad(space) }) else {
+            return;
+        };
+        if let Some(_growing) = self.grow_lock.try_lock() {
+            unsafe { Overlay::shrink_in_space(space, pad) };
+        }
+    }
+}
+
+impl<S, R> Allocator<S, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    // allocate, reallocate and free deterministic patterns across size classes, checking contents
+    // and invariants on the way, as a quick diagnostic at boot or after a watchdog reset. sizes that
+    // do not fit are skipped, and everything is freed before returning
+    pub fn self_test(&self) -> Result<(), SelfTestError> {
+        const SIZES: [usize; 12] = [
+            1,
+            8,
+            24,
+            40,
+            128,
+            248,
+            256,
+            300,
+            1000,
+            4000,
+            10000,
+            64 << 10,
+        ];
+        let fill = |ptr: *mut u8, size: usize, seed: usize| {
+            for offset in 0..size {
+                unsafe { *ptr.add(offset) = (seed * 31 + offset) as u8 ^ 0x5a }
+            }
+        };
+        let verify = |ptr: *mut u8, size: usize, seed: usize| {
+            (0..size)
+                .all(|offset| unsafe { *ptr.add(offset) } == (seed * 31 + offset) as u8 ^ 0x5a)
+                .then_some(())
+                .ok_or(SelfTestError::Pattern(ptr))
+        };
+        let free = |objects: &[(*mut u8, Layout); 12]| {
+            for (ptr, layout) in objects {
+                if !ptr.is_null() {
+                    unsafe { self.dealloc(*ptr, *layout) }
+                }
+            }
+        };
+
+        self.check().map_err(SelfTestError::Check)?;
+        let mut objects = [(null_mut(), Layout::new::<u8>()); 12];
+        for (seed, size) in SIZES.into_iter().enumerate() {
+            let layout = Layout::from_size_align(size, 1 << (seed % 8)).unwrap();
+            let ptr = unsafe { self.alloc(layout) };
+            if !ptr.is_null() {
+                fill(ptr, size, seed);
+                objects[seed] = (ptr, layout)
+            }
+        }
+        let result = (|| {
+            for (seed, (ptr, layout)) in objects.iter_mut().enumerate() {
+                if ptr.is_null() {
+                    continue;
+                }
+                verify(*ptr, layout.size(), seed)?;
+                if seed % 2 == 0 {
+                    let new_size = layout.size() * 2;
+                    let new_ptr = unsafe { self.realloc(*ptr, *layout, new_size) };
+                    if !new_ptr.is_null() {
+                        verify(new_ptr, layout.size(), seed)?;
+                        fill(new_ptr, new_size, seed);
+                        *ptr = new_ptr;
+                        *layout = Layout::from_size_align(new_size, layout.align()).unwrap();
+                    }
+                }
+            }
+            self.check().map_err(SelfTestError::Check)?;
+            for (seed, (ptr, layout)) in objects.iter_mut().enumerate().rev() {
+                if !ptr.is_null() {
+                    verify(*ptr, layout.size(), seed)?;
+                    if seed % 2 == 1 {
+                        unsafe { self.dealloc(*ptr, *layout) }
+                        *ptr = null_mut()
+                    }
+                }
+            }
+            self.check().map_err(SelfTestError::Check)
+        })();
+        free(&objects);
+        result?;
+        self.check().map_err(SelfTestError::Check)
+    }
+
+    pub fn alloc_within(&self, layout: Layout, budget: Budget) -> Result<NonNull<u8>, BudgetError> {
+        let user_data = self.alloc_within_uncounted(layout, budget);
+        let ptr = user_data.map_or(null_mut(), NonNull::as_ptr);
+        self.counters.alloc(layout.size(), ptr);
+        user_data
+    }
+
+    fn alloc_within_uncounted(
+        &self,
+        layout: Layout,
+        budget: Budget,
+    ) -> Result<NonNull<u8>, BudgetError> {
+        let mut space = self.acquire_space();
+        let user_data =
+            match unsafe { Overlay::try_alloc_scanning(&mut *space, layout, budget.scan) } {
+                Ok(user_data) => user_data,
+                Err(None) => return Err(BudgetError::Exceeded),
+                Err(Some(top)) if space.reservation().is_none() => unsafe {
+                    Overlay::grow_in_space(&mut *space, top, layout)
+                },
+                Err(Some(_)) => {
+                    drop(space);
+                    unsafe { self.alloc_reserving(layout) }
+                }
+            };
+        NonNull::new(user_data).ok_or(BudgetError::OutOfMemory)
+    }
+
+    // reallocate to another alignment as well as size, e.g. for `grow` and `shrink` of the
+    // `Allocator` trait, staying in the chunk if it has room for the user data at the new alignment
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn realloc_layout(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_layout: Layout,
+    ) -> *mut u8 {
+        // safety: same as `GlobalAlloc::realloc`, with the size and alignment of `new_layout`
+        if !self.owns(ptr) {
+            return unsafe { self.realloc_foreign(ptr, layout, new_layout) };
+        }
+        let user_data = unsafe { self.realloc_uncounted(ptr, layout, new_layout) };
+        self.counters
+            .realloc(layout.size(), new_layout.size(), user_data);
+        user_data
+    }
+
+    // grow the allocation to `new_size` only if it can stay where it is, see `grow_hint` for how
+    // far, and leave it untouched otherwise, e.g. for a ring buffer that can either go on in place
+    // or wrap around into a new one. never with `minimal`
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn try_grow_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        // safety: same as `GlobalAlloc::realloc`, and `layout` is updated to `new_size` on success
+        unsafe { self.resize_in_place(ptr, layout, new_size) }
+    }
+
+    // give the end of the allocation past `new_size` back to the heap without moving it, where it
+    // is reused once it is large enough for a chunk. never with `minimal`
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn shrink_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        // safety: same as `try_grow_in_place`
+        unsafe { self.resize_in_place(ptr, layout, new_size) }
+    }
+
+    // without the copy of moving down, and not for empty or foreign allocations, which may not be
+    // in a chunk
+    unsafe fn resize_in_place(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> bool {
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align()) else {
+            return false;
+        };
+        if layout.size() == 0 || new_size == 0 || !self.owns(ptr) {
+            return false;
+        }
+        let mut space = self.acquire_space();
+        let user_data =
+            unsafe { Overlay::try_realloc_in_space(&mut *space, ptr, layout, new_layout, 0) };
+        drop(space);
+        check_assert!(chunks, user_data.is_none_or(|user_data| user_data == ptr));
+        self.counters
+            .realloc(layout.size(), new_size, user_data.unwrap_or_else(null_mut));
+        user_data.is_some()
+    }
+
+    // the allocation is left untouched on error
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn realloc_within(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+        budget: Budget,
+    ) -> Result<NonNull<u8>, BudgetError> {
+        // safety: same as `GlobalAlloc::realloc`
+        let user_data = unsafe { self.realloc_within_uncounted(ptr, layout, new_size, budget) };
+        let new_ptr = user_data.map_or(null_mut(), NonNull::as_ptr);
+        self.counters.realloc(layout.size(), new_size, new_ptr);
+        user_data
+    }
+
+    unsafe fn realloc_within_uncounted(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+        budget: Budget,
+    ) -> Result<NonNull<u8>, BudgetError> {
+        let new_layout = Layout::from_size_align(new_size, layout.align())
+            .map_err(|_| BudgetError::OutOfMemory)?;
+        let mut space = self.acquire_space();
+        if let Some(user_data) = unsafe {
+            Overlay::try_realloc_in_space(&mut *space, ptr, layout, new_layout, budget.copy)
+        } {
+            return Ok(NonNull::new(user_data).unwrap());
+        }
+        if layout.size() > budget.copy {
+            return Err(BudgetError::Exceeded);
+        }
+        drop(space);
+
+        let new_ptr = self.alloc_within_uncounted(new_layout, budget)?;
+        let mut space = self.acquire_space();
+        unsafe {
+            Overlay::move_in_space(&mut *space, ptr, layout, new_ptr.as_ptr(), new_layout);
+            Overlay::dealloc_in_space(&mut *space, ptr, layout);
+        }
+        self.shrink_after_free(&mut space);
+        Ok(new_ptr)
+    }
+
+    // grow with the heap released during `Space::reserve`, the heap is only held to take the
+    // reservation and to merge the reserved space, so other threads keep going meanwhile
+    unsafe fn alloc_reserving(&self, layout: Layout) -> *mut u8 {
+        let _growing = self.grow_lock.lock();
+        loop {
+            let mut space = self.acquire_space();
+            // someone else may have grown or freed in between
+            let top = match unsafe { Overlay::try_alloc_in_space(&mut *space, layout) } {
+                Ok(user_data) => return user_data,
+                Err(top) => top,
+            };
+            let size = space.len();
+            let Some((min_len, len)) =
+                (unsafe { Overlay::new(&mut *space).growth(top, layout, size) })
+            else {
+                return null_mut();
+            };
+            let reservation = space.reservation().unwrap();
+            drop(space);
+
+            let bytes = if unsafe { S::reserve(reservation, len) } {
+                len
+            } else if unsafe { S::reserve(reservation, min_len) } {
+                min_len
+            } else {
+                return null_mut();
+            };
+
+            let mut space = self.acquire_space();
+            // only growers change the size, and they are serialized
+            debug_assert_eq!(space.len(), size);
+            space.set_reserved(bytes);
+            let top = unsafe { Overlay::new(&mut *space).top_chunk() };
+            assert!(space.set_size(bytes));
+            unsafe { Overlay::merge_growth(&mut *space, top, size) };
+            // retry, the reserved space may be taken by other allocations before merging
+        }
+    }
+}
+
+// the constructor of the default lock, see `lazy`
+impl<S, const N: usize> Allocator<Segments<S, N>> {
+    // a heap spanning the regions of `segments`, each laid out as a heap of its own with the
+    // default config when taken. allocating tries the regions taken last first, then grows the
+    // last one if its space can, then takes another one, and freeing goes to the region of the
+    // pointer. the spaces must not move, and a region is never given back, but the free end of it.
+    // e.g. the global allocator over `Segments` of `Mmap` on a system without `mremap`
+    pub const fn segmented(segments: Segments<S, N>) -> Self {
+        Self::segmented_with_lock(segments)
+    }
+}
+
+impl<S, R, const N: usize> Allocator<Segments<S, N>, R>
+where
+    R: RawMutex,
+{
+    pub const fn segmented_with_lock(segments: Segments<S, N>) -> Self {
+        Self {
+            heap: Mutex::new(segments),
+            grow_lock: Mutex::new(()),
+            name: None,
+            counters: Counters::new(),
+            // laid out region by region instead
+            laid_out: AtomicBool::new(true),
+            foreign: ForeignPolicy::Abort,
+        }
+    }
+
+    pub(crate) fn acquire_segments(&self) -> MutexGuard<'_, R, Segments<S, N>> {
+        self.heap.lock()
+    }
+}
+
+impl<S, R, const N: usize> Allocator<Segments<S, N>, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    pub fn segment_count(&self) -> usize {
+        self.heap.lock().len()
+    }
+
+    // every region, see `check`
+    pub fn check_segments(&self) -> Result<(), CheckError> {
+        for space in self.heap.lock().as_mut_slice() {
+            let end = space.as_mut_ptr_range().end;
+            unsafe { Overlay::new(space).check(end) }?
+        }
+        Ok(())
+    }
+
+    // of all the regions together, see `stats`
+    pub fn segments_stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        let mut segments = self.heap.lock();
+        for space in segments.as_mut_slice() {
+            let mut overlay = Overlay::new(space);
+            unsafe { overlay.consolidate() };
+            let mut chunk = Some(unsafe { overlay.start_chunk() });
+            while let Some(walked) = chunk {
+                stats.add_chunk(unsafe { walked.info() });
+                chunk = unsafe { walked.get_walk_next() }
+            }
+        }
+        self.counters.load(&mut stats);
+        #[cfg(feature = "mmap-stats")]
+        {
+            stats.syscalls = segments.syscall_stats();
+        }
+        stats
+    }
+
+    unsafe fn alloc_in_segments(segments: &mut Segments<S, N>, layout: Layout) -> *mut u8 {
+        let taken = segments.len();
+        let mut last_top = None;
+        for (index, space) in segments.as_mut_slice().iter_mut().enumerate().rev() {
+            match unsafe { Overlay::try_alloc_in_space(space, layout) } {
+                Ok(user_data) => return user_data,
+                Err(top) if index + 1 == taken => last_top = Some(top),
+                Err(_) => {}
+            }
+        }
+        if let (Some(top), Some(space)) = (last_top, segments.as_mut_slice().last_mut()) {
+            let user_data = unsafe { Overlay::grow_in_space(space, top, layout) };
+            if !user_data.is_null() {
+                return user_data;
+            }
+        }
+
+        // the bins and the chunk fit in what is added to the allocation, with room to spare
+        let Some(bytes) = layout
+            .size()
+            .checked_add(layout.align() + Overlay::LAZY_SIZE)
+        else {
+            return null_mut();
+        };
+        let bytes = usize::max(bytes, segments.segment_size()).next_multiple_of(8);
+        let Some(space) = segments.push(bytes) else {
+            return null_mut();
+        };
+        let (len, zeroed) = (space.len(), space.grows_zeroed());
+        if let Err(error) = unsafe { Overlay::new(space).init(len, Config::default(), zeroed) } {
+            panic!("cannot create a heap: {error:?}")
+        }
+        unsafe { Overlay::alloc_in_space(space, layout) }
+    }
+
+    // in place, or down into the free lower chunk, if it can, otherwise wherever it fits
+    unsafe fn realloc_in_segments(
+        segments: &mut Segments<S, N>,
+        index: usize,
+        user_data: *mut u8,
+        layout: Layout,
+        new_layout: Layout,
+    ) -> *mut u8 {
+        let space = &mut segments.as_mut_slice()[index];
+        if let Some(new_user_data) = unsafe {
+            Overlay::try_realloc_in_space(space, user_data, layout, new_layout, usize::MAX)
+        } {
+            return new_user_data;
+        }
+        let new_user_data = unsafe { Self::alloc_in_segments(segments, new_layout) };
+        if new_user_data.is_null() {
+            return null_mut();
+        }
+        let new_index = segments.position(new_user_data).unwrap();
+        let spaces = segments.as_mut_slice();
+        if new_index == index {
+            unsafe {
+                Overlay::move_in_space(
+                    &mut spaces[index],
+                    user_data,
+                    layout,
+                    new_user_data,
+                    new_layout,
+                )
+            }
+        } else {
+            // as `move_in_space`, the trailer is read and written through the overlay of its region
+            if Chunk::TRAILER_SIZE != 0 {
+                let overlay = Overlay::new(&mut spaces[index]);
+                let trailer = unsafe {
+                    Chunk::from_user_data(user_data, layout, overlay.limit).get_trailer()
+                };
+                let overlay = Overlay::new(&mut spaces[new_index]);
+                unsafe {
+                    Chunk::from_user_data(new_user_data, new_layout, overlay.limit)
+                        .set_trailer(trailer)
+                }
+            }
+            let size = usize::min(layout.size(), new_layout.size());
+            unsafe { copy_nonoverlapping(user_data, new_user_data, size) }
+        }
+        unsafe { Overlay::dealloc_in_space(&mut spaces[index], user_data, layout) }
+        Self::shrink_segment(&mut spaces[index]);
+        new_user_data
+    }
+
+    fn shrink_segment(space: &mut S) {
+        if let Some(pad) = unsafe { Overlay::shrink_pad(space) } {
+            unsafe { Overlay::shrink_in_space(space, pad) };
+        }
+    }
+}
+
+unsafe impl<S, R, const N: usize> GlobalAlloc for Allocator<Segments<S, N>, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let user_data = unsafe { Self::alloc_in_segments(&mut self.heap.lock(), layout) };
+        self.counters.alloc(layout.size(), user_data);
+        user_data
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let user_data = unsafe { self.alloc(layout) };
+        if user_data.is_null() || layout.size() == 0 {
+            return user_data;
+        }
+        let mut segments = self.heap.lock();
+        let index = segments.position(user_data).unwrap();
+        let space = &mut segments.as_mut_slice()[index];
+        if unsafe { !Overlay::new(space).clear_clean(user_data, layout.size()) } {
+            drop(segments);
+            unsafe { user_data.write_bytes(0, layout.size()) }
+        }
+        user_data
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let mut segments = self.heap.lock();
+        let Some(index) = segments.position(ptr) else {
+            drop(segments);
+            return unsafe { self.dealloc_foreign(ptr, layout) };
+        };
+        let space = &mut segments.as_mut_slice()[index];
+        unsafe { Overlay::dealloc_in_space(space, ptr, layout) }
+        Self::shrink_segment(space);
+        drop(segments);
+        self.counters.dealloc(layout.size())
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align()) else {
+            return null_mut();
+        };
+        let mut segments = self.heap.lock();
+        let Some(index) = segments.position(ptr) else {
+            drop(segments);
+            return unsafe { self.realloc_foreign(ptr, layout, new_layout) };
+        };
+        let user_data =
+            unsafe { Self::realloc_in_segments(&mut segments, index, ptr, layout, new_layout) };
+        drop(segments);
+        self.counters.realloc(layout.size(), new_size, user_data);
+        user_data
+    }
+}
+
+#[cfg(any(test, dev, feature = "check-bins"))]
+impl Overlay {
+    unsafe fn sanity_check(&self) {
+        let mut chunks = [None; 10];
+        // println!("check:");
+        for (i, chunk) in unsafe { self.iter_all_chunk() }.enumerate() {
+            // println!("  {chunk:?}");
+            chunks[i % 10] = Some(chunk);
+            debug_assert!(unsafe { chunk.get_size() } >= Chunk::MIN_SIZE, "{chunks:?}",);
+        }
+        for _chunk in unsafe { self.iter_free_chunk() } {}
+        let result = unsafe { self.check(self.limit.as_ptr()) };
+        debug_assert_eq!(result, Ok(()), "{chunks:?}");
+    }
+}
+
+#[cfg(not(any(test, dev, feature = "check-bins")))]
+impl Overlay {
+    unsafe fn sanity_check(&self) {}
+}
+
+#[cfg(feature = "audit")]
+impl Overlay {
+    // the user range must be inside the user data of a single in-use chunk, i.e. not overlapping
+    // the bins, any header or trailer, and every header up to the next one must be intact, so
+    // metadata written into user range or user data written over metadata fails early
+    unsafe fn audit(&self, user_data: *mut u8, size: usize, end: *mut u8) {
+        let start = unsafe { self.start_chunk() }.data.as_ptr();
+        assert!(
+            user_data >= unsafe { start.add(8) },
+            "{user_data:?} overlaps with bins"
+        );
+        let mut chunk = unsafe { self.start_chunk() };
+        loop {
+            let chunk_size = unsafe { chunk.get_size() };
+            assert!(
+                chunk_size >= Chunk::MIN_SIZE
+                    && chunk_size & 7 == 0
+                    && chunk_size <= end as usize - chunk.data.as_ptr() as usize,
+                "broken header of chunk {:?}",
+                chunk.data
+            );
+            let higher = unsafe { chunk.data.as_ptr().add(chunk_size) };
+            if higher > user_data {
+                break;
+            }
+            assert!(higher < end, "{user_data:?} is out of heap");
+            chunk = unsafe { chunk.get_higher_chunk() };
+        }
+
+        let chunk_end = unsafe { chunk.data.as_ptr().add(chunk.get_size()) };
+        assert!(
+            unsafe { chunk.get_in_use() },
+            "{user_data:?} is in free chunk {:?}",
+            chunk.data
+        );
+        assert!(
+            user_data >= unsafe { chunk.data.as_ptr().add(8) }
+                && user_data as usize + size <= chunk_end as usize - Chunk::TRAILER_SIZE,
+            "{user_data:?} of {size} bytes overlaps with metadata of chunk {:?}",
+            chunk.data
+        );
+        assert!(
+            chunk_end < end,
+            "chunk {:?} takes the place of top chunk",
+            chunk.data
+        );
+        let higher = unsafe { chunk.get_higher_chunk() };
+        let higher_size = unsafe { higher.get_size() };
+        assert!(
+            unsafe { higher.get_lower_in_use() }
+                && higher_size >= Chunk::MIN_SIZE
+                && higher_size & 7 == 0
+                && higher_size <= end as usize - chunk_end as usize,
+            "broken header of chunk {:?} after {user_data:?}",
+            higher.data
+        );
+    }
+}
+
+#[cfg(not(feature = "audit"))]
+impl Overlay {
+    unsafe fn audit(&self, _user_data: *mut u8, _size: usize, _end: *mut u8) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        fmt::Write as _,
+        ops::{Deref, DerefMut, Range},
+        sync::atomic::{AtomicUsize, Ordering::SeqCst},
+    };
+    use std::{iter::repeat, slice, string::String, vec, vec::Vec};
+
+    use crate::{
+        dump::Buffer,
+        space::{Fixed, Reservation, Static, SystemBuffer},
+        Owns,
+    };
+
+    use super::*;
+
+    #[test]
+    fn try_new() {
+        let data = &mut *vec![0u64; 512];
+        let data = unsafe { slice::from_raw_parts_mut(data.as_mut_ptr().cast::<u8>(), 4 << 10) };
+        let addr = data[1..].as_mut_ptr();
+        assert_eq!(
+            Allocator::try_new(Fixed::from(&mut data[1..])).err(),
+            Some(NewError::Misaligned(addr))
+        );
+        assert_eq!(
+            Allocator::try_new(Fixed::from(&mut data[..100])).err(),
+            Some(NewError::Length(100))
+        );
+        let Err(NewError::TooSmall { len: 256, min }) =
+            Allocator::try_new(Fixed::from(&mut data[..256]))
+        else {
+            panic!()
+        };
+        assert!((8 * Overlay::bins_len_of(Bins::default())..4 << 10).contains(&min));
+        let config = Config {
+            start_align: 48,
+            ..Default::default()
+        };
+        assert_eq!(
+            Allocator::try_with_config(Fixed::from(&mut data[..]), config).err(),
+            Some(NewError::StartAlign(48))
+        );
+        // nothing written on failure
+        assert!(data.iter().all(|byte| *byte == 0));
+        let alloc = Allocator::try_new(Fixed::from(&mut data[..min])).unwrap();
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot create a heap: TooSmall")]
+    fn new_too_small() {
+        Allocator::new(Fixed::from(&mut *vec![0; 64]));
+    }
+
+    #[test]
+    fn single_free_chunk_on_init() {
+        // leveraging the fact that System allocator always allocate 8 bytes aligned memory
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                top_policy: TopPolicy::KeepWilderness,
+                ..Default::default()
+            },
+        );
+        let all_chunk =
+            Vec::from_iter(unsafe { Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk() });
+        let free_chunk =
+            Vec::from_iter(unsafe { Overlay::new(&mut *alloc.acquire_space()).iter_free_chunk() });
+        assert_eq!(all_chunk.len(), 2);
+        assert_eq!(free_chunk, all_chunk);
+    }
+
+    #[test]
+    fn working_debug_chunk() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let chunk = unsafe { Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk() }
+            .next()
+            .unwrap();
+        assert!(std::format!("{chunk:?}").starts_with("Chunk"));
+        let mut buf = Buffer::<128>::new();
+        write!(buf, "{chunk:?}").unwrap();
+        assert_eq!(buf.as_str(), std::format!("{chunk:?}"));
+    }
+
+    #[test]
+    fn valid_addr() {
+        fn run(sizes: impl Iterator<Item = usize>) {
+            let data = &mut *vec![0; 4 << 10];
+            let ptr_range = data.as_mut_ptr_range();
+            let alloc = Allocator::new(Fixed::from(data));
+            for size in sizes {
+                let ptr = unsafe { alloc.alloc(Layout::from_size_align(size, 1).unwrap()) };
+                if ptr.is_null() {
+                    break;
+                }
+                assert!(ptr_range.contains(&ptr));
+            }
+        }
+
+        run([1].into_iter());
+        run(1..10);
+        run(repeat(1));
+        run(1..);
+    }
+
+    #[test]
+    fn aligned_addr() {
+        fn run(sizes: impl Iterator<Item = usize>, align: usize) {
+            let data = &mut *vec![0; 4 << 10];
+            let alloc = Allocator::new(Fixed::from(data));
+            for size in sizes {
+                let ptr = unsafe { alloc.alloc(Layout::from_size_align(size, align).unwrap()) };
+                if ptr.is_null() {
+                    break;
+                }
+                assert_eq!(ptr.align_offset(align), 0);
+            }
+        }
+
+        run([1].into_iter(), 16);
+        run(1..10, 16);
+        run(1..10, 32);
+        run(repeat(1), 16);
+        run(repeat(1), 32);
+        run(1.., 16);
+        run(1.., 32);
+        run(1.., 64);
+    }
+
+    #[test]
+    fn alloc_dealloc_identical() {
+        fn run(layouts: impl Iterator<Item = Layout> + Clone) {
+            let data = &mut *vec![0; 4 << 10];
+            let alloc = Allocator::with_config(
+                Fixed::from(data),
+                Config {
+                    fast_bins: false,
+                    ..Default::default()
+                },
+            );
+            let chunks = Vec::from_iter(unsafe {
+                Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk()
+            });
+            let ptrs = Vec::from_iter(
+                layouts
+                    .clone()
+                    .map(|layout| unsafe { (alloc.alloc(layout), layout) })
+                    .take_while(|(ptr, _)| !ptr.is_null()),
+            );
+            for (ptr, layout) in ptrs.into_iter() {
+                // println!("{ptr:?} {layout:?}");
+                unsafe { alloc.dealloc(ptr, layout) }
+            }
+            assert_eq!(
+                Vec::from_iter(unsafe {
+                    Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk()
+                }),
+                chunks
+            );
+
+            // again but dealloc in LIFO order
+            // yet to find a way to eliminate this duplication
+            let data = &mut *vec![0; 4 << 10];
+            let alloc = Allocator::with_config(
+                Fixed::from(data),
+                Config {
+                    fast_bins: false,
+                    ..Default::default()
+                },
+            );
+            let chunks = Vec::from_iter(unsafe {
+                Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk()
+            });
+            let ptrs = Vec::from_iter(
+                layouts
+                    .map(|layout| unsafe { (alloc.alloc(layout), layout) })
+                    .take_while(|(ptr, _)| !ptr.is_null()),
+            );
+            for (ptr, layout) in ptrs.into_iter().rev() {
+                unsafe { alloc.dealloc(ptr, layout) }
+            }
+            assert_eq!(
+                Vec::from_iter(unsafe {
+                    Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk()
+                }),
+                chunks
+            );
+        }
+
+        run([Layout::from_size_align(1, 1).unwrap()].into_iter());
+        run((1..10).map(|size| Layout::from_size_align(size, 1).unwrap()));
+        run((0..=6).map(|align| Layout::from_size_align(1, 1 << align).unwrap()));
+        run((1..).map(|size| Layout::from_size_align(size, 1).unwrap()));
+    }
+
+    // every reallocation moves with `minimal`
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    fn realloc_in_place() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                top_policy: TopPolicy::KeepWilderness,
+                ..Default::default()
+            },
+        );
+        let mut layout = Layout::from_size_align(8, 1).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        let new_ptr = unsafe { alloc.realloc(ptr, layout, 16) };
+        assert_eq!(new_ptr, ptr);
+
+        layout = Layout::from_size_align(16, 1).unwrap();
+        loop {
+            let new_ptr = unsafe { alloc.realloc(ptr, layout, layout.size() * 2) };
+            if new_ptr.is_null() {
+                break;
+            }
+            assert_eq!(new_ptr, ptr);
+            layout = Layout::from_size_align(layout.size() * 2, 1).unwrap();
+        }
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    fn realloc_lower() {
+        let data = &mut *vec![0; 8 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(500, 8).unwrap();
+        let ptrs = [(); 4].map(|_| unsafe { alloc.alloc(layout) });
+        unsafe {
+            ptrs[1].write_bytes(0xa5, 500);
+            alloc.metadata_of(ptrs[1]).write([7; METADATA_SIZE]);
+            alloc.dealloc(ptrs[0], layout)
+        }
+        let free_count = alloc.stats().free_count;
+
+        // the higher chunk is in use, so down into the lower one
+        let new_ptr = unsafe { alloc.realloc(ptrs[1], layout, 900) };
+        assert_eq!(new_ptr, ptrs[0]);
+        assert!((0..500).all(|offset| unsafe { *new_ptr.add(offset) } == 0xa5));
+        assert_eq!(
+            unsafe { alloc.metadata_of(new_ptr).read() },
+            [7; METADATA_SIZE]
+        );
+        // with the rest of the two free again
+        assert_eq!(alloc.stats().free_count, free_count);
+        assert_eq!(alloc.check(), Ok(()));
+
+        // not past the copy budget
+        unsafe { alloc.dealloc(new_ptr, Layout::from_size_align(900, 8).unwrap()) };
+        let budget = Budget { scan: 0, copy: 499 };
+        assert_eq!(
+            unsafe { alloc.realloc_within(ptrs[2], layout, 900, budget) },
+            Err(BudgetError::Exceeded)
+        );
+        let budget = Budget { scan: 0, copy: 500 };
+        let new_ptr = unsafe { alloc.realloc_within(ptrs[2], layout, 900, budget) }.unwrap();
+        assert_eq!(new_ptr.as_ptr(), ptrs[0]);
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    fn realloc_layout() {
+        let data = &mut *vec![0; 8 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let filled = |ptr: *mut u8, size| (0..size).all(|i| unsafe { *ptr.add(i) } == i as u8);
+        let layout = Layout::from_size_align(400, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        for i in 0..400 {
+            unsafe { *ptr.add(i) = i as u8 }
+        }
+        let chunk = alloc.find_chunk(ptr).unwrap();
+
+        // re-aligned within the chunk, which has room for it
+        let aligned = Layout::from_size_align(64, 64).unwrap();
+        let new_ptr = unsafe { alloc.realloc_layout(ptr, layout, aligned) };
+        assert_eq!(new_ptr as usize % 64, 0);
+        assert!(filled(new_ptr, 64));
+        assert_eq!(chunk.contains(new_ptr), !cfg!(feature = "minimal"));
+        assert_eq!(alloc.check(), Ok(()));
+
+        // and back, freed with the new layout
+        let ptr = unsafe { alloc.realloc_layout(new_ptr, aligned, layout) };
+        assert!(filled(ptr, 64));
+        let large = Layout::from_size_align(2000, 256).unwrap();
+        let ptr = unsafe { alloc.realloc_layout(ptr, layout, large) };
+        assert_eq!(ptr as usize % 256, 0);
+        assert!(filled(ptr, 64));
+        unsafe { alloc.dealloc(ptr, large) };
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    fn resize_in_place() {
+        let data = &mut *vec![0; 8 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(200, 8).unwrap();
+        let ptrs = [(); 3].map(|_| unsafe { alloc.alloc(layout) });
+
+        // the higher chunk is in use, and the lower one is not tried
+        unsafe { alloc.dealloc(ptrs[0], layout) };
+        assert!(!unsafe { alloc.try_grow_in_place(ptrs[1], layout, 300) });
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert_eq!(ptr, ptrs[0]);
+        unsafe { ptr.write_bytes(0xa5, 200) };
+        assert!(unsafe { alloc.shrink_in_place(ptr, layout, 50) });
+        let layout = Layout::from_size_align(50, 8).unwrap();
+        assert!((0..50).all(|offset| unsafe { *ptr.add(offset) } == 0xa5));
+        // into the tail given back
+        let hint = unsafe { alloc.grow_hint(ptr, layout) };
+        assert!(hint >= 200);
+        assert!(unsafe { alloc.try_grow_in_place(ptr, layout, hint) });
+        assert!(!unsafe { alloc.try_grow_in_place(ptr, layout, hint + 1) });
+        assert_eq!(alloc.stats().ops.failed, 2);
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    fn realloc_shrink() {
+        let data = &mut *vec![0; 8 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(1000, 8).unwrap();
+        let ptrs = [(); 3].map(|_| unsafe { alloc.alloc(layout) });
+        let size = alloc.find_chunk(ptrs[0]).unwrap().size;
+        unsafe { ptrs[0].write_bytes(0xa5, 1000) };
+        let free_count = alloc.stats().free_count;
+
+        // too little saved for a free chunk
+        assert_eq!(unsafe { alloc.realloc(ptrs[0], layout, 990) }, ptrs[0]);
+        assert_eq!(alloc.find_chunk(ptrs[0]).unwrap().size, size);
+        // the tail is reused
+        let layout = Layout::from_size_align(990, 8).unwrap();
+        assert_eq!(unsafe { alloc.realloc(ptrs[0], layout, 100) }, ptrs[0]);
+        let info = alloc.find_chunk(ptrs[0]).unwrap();
+        assert!(info.size < 200);
+        assert_eq!(alloc.stats().free_count, free_count + 1);
+        assert!((0..100).all(|offset| unsafe { *ptrs[0].add(offset) } == 0xa5));
+        let reused = unsafe { alloc.alloc(Layout::from_size_align(500, 8).unwrap()) };
+        assert!((ptrs[0]..ptrs[1]).contains(&reused));
+        unsafe { alloc.dealloc(reused, Layout::from_size_align(500, 8).unwrap()) }
+
+        // coalesced with a free higher chunk, the top one here
+        let layout = Layout::from_size_align(1000, 8).unwrap();
+        unsafe { alloc.dealloc(ptrs[2], layout) };
+        assert_eq!(unsafe { alloc.realloc(ptrs[1], layout, 8) }, ptrs[1]);
+        assert_eq!(alloc.stats().free_count, free_count + 1);
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[cfg(feature = "minimal")]
+    #[test]
+    fn minimal() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                bins: Bins::Sorted,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            alloc.overhead().bins,
+            8 * Overlay::bins_slots_of(Bins::Compact, false)
+        );
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        unsafe { ptr.write_bytes(0xa5, 64) };
+        assert_eq!(unsafe { alloc.grow_hint(ptr, layout) }, 64);
+        let shrunk = unsafe { alloc.realloc(ptr, layout, 16) };
+        assert_ne!(shrunk, ptr);
+        assert!((0..16).all(|offset| unsafe { *shrunk.add(offset) } == 0xa5));
+        let layout = Layout::from_size_align(16, 8).unwrap();
+        let grown = unsafe { alloc.realloc(shrunk, layout, 32) };
+        assert_ne!(grown, shrunk);
+        unsafe { alloc.dealloc(grown, Layout::from_size_align(32, 8).unwrap()) }
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    fn placement() {
+        // a large free chunk below a small one, separated by in-use chunks
+        fn run(placement: Placement) -> bool {
+            let data = &mut *vec![0; 4 << 10];
+            let alloc = Allocator::with_config(
+                Fixed::from(data),
+                Config {
+                    placement,
+                    fast_bins: false,
+                    ..Default::default()
+                },
+            );
+            let layouts = [256, 8, 64, 8].map(|size| Layout::from_size_align(size, 8).unwrap());
+            let ptrs = layouts.map(|layout| unsafe { alloc.alloc(layout) });
+            unsafe {
+                alloc.dealloc(ptrs[0], layouts[0]);
+                alloc.dealloc(ptrs[2], layouts[2])
+            }
+            let ptr = unsafe { alloc.alloc(Layout::from_size_align(48, 8).unwrap()) };
+            assert!(ptr == ptrs[0] || ptr == ptrs[2]);
+            assert_eq!(alloc.check(), Ok(()));
+            ptr == ptrs[0]
+        }
+
+        assert!(!run(Placement::BestFit));
+        assert!(run(Placement::FirstFit));
+    }
+
+    #[test]
+    fn profile_defaults() {
+        let config = Config::default();
+        let tiny = cfg!(feature = "profile-tiny");
+        assert_eq!(config.top_policy == TopPolicy::UseWilderness, tiny);
+        assert_eq!(
+            config.bins == Bins::Compact,
+            tiny || cfg!(feature = "minimal")
+        );
+        assert_eq!(config.fast_bins, cfg!(feature = "profile-fast") && !tiny);
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    fn fast_bins() {
+        let config = Config {
+            fast_bins: true,
+            top_policy: TopPolicy::KeepWilderness,
+            ..Default::default()
+        };
+        let alloc = Allocator::with_config(SystemBuffer::new(8 << 10, 4096), config);
+        let layout = Layout::from_size_align(40, 8).unwrap();
+        let ptrs =
+            Vec::from_iter((0..Overlay::FAST_LIMIT + 1).map(|_| unsafe { alloc.alloc(layout) }));
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        // kept in use, and handed out again first
+        unsafe { alloc.dealloc(ptrs[1], layout) }
+        assert!(alloc.find_chunk(ptrs[1]).unwrap().in_use);
+        assert_eq!(alloc.check(), Ok(()));
+        assert_eq!(unsafe { alloc.alloc(layout) }, ptrs[1]);
+        // flushed past the limit
+        for ptr in &ptrs {
+            unsafe { alloc.dealloc(*ptr, layout) }
+            assert_eq!(alloc.check(), Ok(()));
+        }
+        assert!(!alloc.find_chunk(ptrs[0]).unwrap().in_use);
+        let ptr = unsafe { alloc.alloc(layout) };
+        unsafe { alloc.dealloc(ptr, layout) }
+        assert!(alloc.find_chunk(ptr).unwrap().in_use);
+        // the clone takes the cached chunks along
+        let clone = alloc.clone_heap(SystemBuffer::new(8 << 10, 4096)).unwrap();
+        assert_eq!(clone.check(), Ok(()));
+        let stats = alloc.stats();
+        assert_eq!((stats.allocation_count, stats.free_count), (0, 2));
+        assert_eq!(clone.stats(), stats);
+
+        // flushed before running out
+        let ptrs = Vec::from_iter((0..40).map(|_| unsafe { alloc.alloc(layout) }));
+        for ptr in ptrs {
+            unsafe { alloc.dealloc(ptr, layout) }
+        }
+        let large = Layout::from_size_align(6 << 10, 8).unwrap();
+        assert!(!unsafe { alloc.alloc(large) }.is_null());
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    fn deferred_coalescing() {
+        let config = Config {
+            deferred_coalescing: true,
+            ..Default::default()
+        };
+        let alloc = Allocator::with_config(SystemBuffer::new(8 << 10, 4096), config);
+        let layout = Layout::from_size_align(200, 8).unwrap();
+        let ptrs = [(); 3].map(|_| unsafe { alloc.alloc(layout) });
+        let size = alloc.find_chunk(ptrs[0]).unwrap().size;
+        unsafe {
+            alloc.dealloc(ptrs[0], layout);
+            alloc.dealloc(ptrs[1], layout)
+        }
+        assert!(alloc.find_chunk(ptrs[0]).unwrap().in_use);
+        assert_eq!(alloc.check(), Ok(()));
+        // only the last one of the same size is reused before coalescing
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert_eq!(ptr, ptrs[1]);
+        unsafe { alloc.dealloc(ptr, layout) }
+        let ptr = unsafe { alloc.alloc(Layout::from_size_align(100, 8).unwrap()) };
+        assert!(!ptrs.contains(&ptr));
+        let clone = alloc.clone_heap(SystemBuffer::new(8 << 10, 4096)).unwrap();
+        assert_eq!(clone.check(), Ok(()));
+        alloc.consolidate();
+        let info = alloc.find_chunk(ptrs[0]).unwrap();
+        assert_eq!((info.in_use, info.size), (false, 2 * size));
+        assert_eq!(alloc.check(), Ok(()));
+        assert_eq!(clone.stats().free_count, alloc.stats().free_count);
+
+        // coalesced before running out
+        let data = &mut *vec![0; 8 << 10];
+        let alloc = Allocator::with_config(Fixed::from(data), config);
+        let ptrs = Vec::from_iter((0..30).map(|_| unsafe { alloc.alloc(layout) }));
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        for ptr in ptrs {
+            unsafe { alloc.dealloc(ptr, layout) }
+        }
+        let large = Layout::from_size_align(6 << 10, 8).unwrap();
+        assert!(!unsafe { alloc.alloc(large) }.is_null());
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    fn usable_size() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        for (size, align) in [(1, 1), (20, 8), (100, 8), (100, 64)] {
+            let layout = Layout::from_size_align(size, align).unwrap();
+            let ptr = unsafe { alloc.alloc(layout) };
+            let usable = unsafe { alloc.usable_size(ptr, layout) };
+            assert!(usable >= size);
+            // the slack is the caller's to use, and writing it leaves the heap intact
+            unsafe { ptr.write_bytes(0xa5, usable) };
+            assert_eq!(alloc.check(), Ok(()));
+            unsafe { alloc.dealloc(ptr, layout) }
+        }
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    fn zero_size() {
+        let new = |zero_size| {
+            let data = vec![0; 4 << 10].leak();
+            Allocator::with_config(
+                Fixed::from(data),
+                Config {
+                    zero_size,
+                    ..Default::default()
+                },
+            )
+        };
+        let zero = Layout::from_size_align(0, 16).unwrap();
+        let alloc = new(ZeroSize::Dangling);
+        let ptr = unsafe { alloc.alloc(zero) };
+        assert_eq!(ptr as usize, 16);
+        assert!(!alloc.owns(ptr));
+        assert_eq!(unsafe { alloc.usable_size(ptr, zero) }, 0);
+        assert_eq!(unsafe { alloc.alloc(zero) }, ptr);
+
+        let alloc = new(ZeroSize::Null);
+        assert!(unsafe { alloc.alloc(zero) }.is_null());
+        assert_eq!(alloc.stats().ops.failed, 1);
+
+        // real chunks, which are distinct and freed as usual
+        let alloc = new(ZeroSize::MinChunk);
+        let ptrs = [(); 2].map(|_| unsafe { alloc.alloc(zero) });
+        assert_ne!(ptrs[0], ptrs[1]);
+        assert!(ptrs
+            .iter()
+            .all(|ptr| alloc.owns(*ptr) && (*ptr as usize).is_multiple_of(16)));
+        // which have room to grow into
+        assert!(unsafe { alloc.usable_size(ptrs[0], zero) } > 0);
+        let ptr = unsafe { alloc.realloc(ptrs[0], zero, 100) };
+        assert!(!ptr.is_null());
+        unsafe {
+            alloc.dealloc(ptr, Layout::from_size_align(100, 16).unwrap());
+            alloc.dealloc(ptrs[1], zero)
+        }
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    fn zero_size_aligned() {
+        for align in [16, 64] {
+            let data = &mut *vec![0; 4 << 10];
+            let config = Config {
+                zero_size: ZeroSize::MinChunk,
+                ..Default::default()
+            };
+            let alloc = Allocator::with_config(Fixed::from(data), config);
+            let zero = Layout::from_size_align(0, align).unwrap();
+            let ptrs = [(); 4].map(|_| unsafe { alloc.alloc(zero) });
+            let chunks = ptrs.map(|ptr| alloc.find_chunk(ptr).unwrap());
+            for (i, (ptr, chunk)) in ptrs.iter().zip(&chunks).enumerate() {
+                assert!((*ptr as usize).is_multiple_of(align));
+                // past the header of a chunk of its own
+                assert!(chunk.in_use && *ptr as usize >= chunk.addr as usize + 8);
+                assert!(chunks[..i].iter().all(|other| other.addr != chunk.addr));
+            }
+            for ptr in ptrs {
+                unsafe { alloc.dealloc(ptr, zero) }
+            }
+            assert_eq!(alloc.stats().allocation_count, 0);
+            assert_eq!(alloc.check(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn compact_bins() {
+        for bins in [Bins::Sorted, Bins::Compact] {
+            let alloc = Allocator::with_config(
+                SystemBuffer::new(8 << 10, 4096),
+                Config {
+                    bins,
+                    fast_bins: false,
+                    ..Default::default()
+                },
+            );
+            let bins_len = match bins {
+                Bins::Sorted if !cfg!(feature = "minimal") => Overlay::BINS_LEN,
+                _ => Overlay::COMPACT_BINS_LEN,
+            };
+            // and a bit per bin
+            assert_eq!(
+                alloc.overhead().bins,
+                8 * (bins_len + bins_len.div_ceil(64))
+            );
+            let mut random = 0x2545_f491_4f6c_dd1d_u64;
+            let mut ptrs = Vec::new();
+            for round in 0..200 {
+                random ^= random << 13;
+                random ^= random >> 7;
+                random ^= random << 17;
+                let layout = Layout::from_size_align(8 + random as usize % 600, 8).unwrap();
+                let ptr = unsafe { alloc.alloc(layout) };
+                if !ptr.is_null() {
+                    ptrs.push((ptr, layout))
+                }
+                if round % 3 != 0 && !ptrs.is_empty() {
+                    let (ptr, layout) = ptrs.swap_remove(random as usize % ptrs.len());
+                    unsafe { alloc.dealloc(ptr, layout) }
+                }
+                assert_eq!(alloc.check(), Ok(()));
+            }
+            let clone = alloc.clone_heap(SystemBuffer::new(8 << 10, 4096)).unwrap();
+            assert_eq!(clone.check(), Ok(()));
+            assert_eq!(clone.stats(), alloc.stats());
+        }
+
+        // too small for the sorted bins alone
+        let data = &mut *vec![0; 512];
+        let config = Config {
+            bins: Bins::Compact,
+            start_align: 8,
+            fast_bins: false,
+            ..Default::default()
+        };
+        let alloc = Allocator::with_config(Fixed::from(data), config);
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        assert!(!unsafe { alloc.alloc(layout) }.is_null());
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    fn realloc_padding() {
+        for top_policy in [TopPolicy::KeepWilderness, TopPolicy::UseWilderness] {
+            let alloc = Allocator::with_config(
+                SystemBuffer::new(8 << 10, 4096),
+                Config {
+                    top_policy,
+                    start_align: 64,
+                    ..Default::default()
+                },
+            );
+            let chunks = Vec::from_iter(alloc.chunks());
+            // a free chunk below so the padding coalesces with it
+            let small = Layout::from_size_align(8, 8).unwrap();
+            let lower = unsafe { alloc.alloc(small) };
+            let layout = Layout::from_size_align(1000, 1024).unwrap();
+            let ptr = unsafe { alloc.alloc(layout) };
+            // and an allocated one above so the tail does not go to the top chunk
+            let higher = unsafe { alloc.alloc(small) };
+            unsafe { alloc.dealloc(lower, small) };
+            let padded = alloc.find_chunk(ptr).unwrap();
+            assert!(ptr as usize - padded.addr as usize >= Chunk::MIN_SIZE + 8);
+            let free_bytes = alloc.stats().free_bytes;
+
+            unsafe { ptr.write_bytes(0xa5, 1000) };
+            assert_eq!(unsafe { alloc.realloc(ptr, layout, 16) }, ptr);
+            let info = alloc.find_chunk(ptr).unwrap();
+            assert_eq!(info.addr, ptr.wrapping_sub(8));
+            assert_eq!(info.size, Chunk::MIN_SIZE);
+            let below = alloc.find_chunk(info.addr.wrapping_sub(1)).unwrap();
+            assert!(!below.in_use);
+            assert_eq!(below.addr, chunks[0].addr);
+            // both the padding and the tail
+            assert_eq!(
+                alloc.stats().free_bytes,
+                free_bytes + padded.size - info.size
+            );
+            assert_eq!(alloc.check(), Ok(()));
+            assert!((0..16).all(|offset| unsafe { *ptr.add(offset) } == 0xa5));
+
+            // growing back stays in place, and freeing it leaves the heap as before
+            let layout = Layout::from_size_align(16, 1024).unwrap();
+            assert_eq!(unsafe { alloc.realloc(ptr, layout, 200) }, ptr);
+            unsafe {
+                alloc.dealloc(ptr, Layout::from_size_align(200, 1024).unwrap());
+                alloc.dealloc(higher, small)
+            }
+            assert_eq!(Vec::from_iter(alloc.chunks()), chunks);
+
+            // the padding is large enough but what is left after it is not
+            for align in [64, 128, 1024] {
+                let layout = Layout::from_size_align(2, align).unwrap();
+                let ptr = unsafe { alloc.alloc(layout) };
+                assert_eq!(unsafe { alloc.realloc(ptr, layout, 1) }, ptr);
+                assert_eq!(alloc.check(), Ok(()));
+                unsafe { alloc.dealloc(ptr, Layout::from_size_align(1, align).unwrap()) }
+            }
+            assert_eq!(Vec::from_iter(alloc.chunks()), chunks);
+        }
+    }
+
+    #[test]
+    fn realloc_copied() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(8, 1).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        unsafe { slice::from_raw_parts_mut(ptr, 8) }
+            .copy_from_slice(&u64::to_ne_bytes(0x1122334455667788));
+        unsafe { alloc.alloc(layout) };
+        let new_ptr = unsafe { alloc.realloc(ptr, layout, 32) };
+        assert_eq!(
+            unsafe { slice::from_raw_parts_mut(new_ptr, 8) },
+            &u64::to_ne_bytes(0x1122334455667788)
+        );
+    }
+
+    #[test]
+    fn migrate() {
+        let data = &mut *vec![0; 4 << 10];
+        let src = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                fast_bins: false,
+                ..Default::default()
+            },
+        );
+        let data = &mut *vec![0; 2 << 10];
+        let dst = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                fast_bins: false,
+                ..Default::default()
+            },
+        );
+        let mut allocations = [16, 1000, 1000, 8]
+            .map(|size| Layout::from_size_align(size, 8).unwrap())
+            .map(|layout| (unsafe { src.alloc(layout) }, layout));
+        for (index, (ptr, layout)) in allocations.iter().enumerate() {
+            unsafe { ptr.write_bytes(index as u8, layout.size()) }
+        }
+        let ptrs = allocations.map(|(ptr, _)| ptr);
+
+        // the third one does not fit and stays
+        assert_eq!(unsafe { src.migrate_all(&mut allocations, &dst) }, 2);
+        for (index, (ptr, layout)) in allocations.iter().enumerate() {
+            assert_eq!(dst.owns(*ptr), index < 2);
+            assert_eq!(*ptr == ptrs[index], index >= 2);
+            assert!((0..layout.size()).all(|offset| unsafe { *ptr.add(offset) } == index as u8));
+        }
+        assert_eq!(src.stats().allocation_count, 2);
+        assert_eq!(dst.stats().allocation_count, 2);
+        let (ptr, layout) = allocations[3];
+        let ptr = unsafe { src.migrate(ptr, layout, &dst) };
+        assert!(dst.owns(ptr));
+        assert_eq!(unsafe { *ptr }, 3);
+        assert_eq!(src.check(), Ok(()));
+        assert_eq!(dst.check(), Ok(()));
+    }
+
+    #[test]
+    fn find_chunk() {
+        let data = &mut *vec![0; 4 << 10];
+        let ptr_range = data.as_mut_ptr_range();
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                fast_bins: false,
+                ..Default::default()
+            },
+        );
+        assert_eq!(alloc.find_chunk(ptr_range.start), None);
+        assert_eq!(alloc.find_chunk(ptr_range.end), None);
+
+        let layout = Layout::from_size_align(40, 16).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        for offset in 0..40 {
+            let info = alloc.find_chunk(unsafe { ptr.add(offset) }).unwrap();
+            assert!(info.in_use);
+            assert!(info.addr < ptr);
+            assert!(info.size >= 40 + Chunk::META_SIZE);
+        }
+        let info = alloc.find_chunk(ptr).unwrap();
+        let higher = alloc.find_chunk(info.addr.wrapping_add(info.size)).unwrap();
+        assert!(!higher.in_use);
+
+        unsafe { alloc.dealloc(ptr, layout) }
+        assert!(!alloc.find_chunk(ptr).unwrap().in_use);
+    }
+
+    #[test]
+    fn overhead() {
+        let alloc = Allocator::with_config(
+            SystemBuffer::new(4 << 10, 4096),
+            Config {
+                top_policy: TopPolicy::KeepWilderness,
+                start_align: 64,
+                fast_bins: false,
+                ..Default::default()
+            },
+        );
+        let base = alloc.base() as usize;
+        let overhead = alloc.overhead();
+        assert_eq!(
+            overhead.bins,
+            8 * Overlay::bins_slots_of(Bins::default(), false)
+        );
+        assert_eq!((base + overhead.bins + overhead.padding + 8) % 64, 0);
+        assert_eq!(overhead.headers, Chunk::META_SIZE);
+        assert_eq!(overhead.top, Chunk::MIN_SIZE);
+
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptrs = [0; 3].map(|_| unsafe { alloc.alloc(layout) });
+        unsafe { alloc.dealloc(ptrs[1], layout) }
+        // two in use, the free one in between and the rest of the start chunk
+        assert_eq!(alloc.overhead().headers, 4 * Chunk::META_SIZE);
+        // every byte after the padding belongs to a chunk
+        let stats = alloc.stats();
+        assert_eq!(
+            overhead.bins + overhead.padding + stats.allocated_bytes + stats.free_bytes,
+            4 << 10
+        );
+        assert_eq!(
+            alloc.overhead_bytes(),
+            overhead.bins + overhead.padding + 4 * Chunk::META_SIZE + Chunk::MIN_SIZE
+        );
+
+        // the alignment padding of a chunk is left to the caller, who knows the layout. the rest of
+        // the start chunk is taken with twice the alignment its user data has, so it is padded
+        // by half of that
+        let header = ptrs[0] as usize - alloc.find_chunk(ptrs[0]).unwrap().addr as usize;
+        let last = alloc.find_chunk(ptrs[2]).unwrap();
+        let rest = last.addr as usize + last.size + header;
+        let align = 2 << rest.trailing_zeros();
+        let ptr = unsafe { alloc.alloc(Layout::from_size_align(100, align).unwrap()) };
+        assert_eq!(alloc.find_chunk(ptr).unwrap().addr as usize + header, rest);
+        assert_eq!(ptr as usize - rest, align / 2);
+        assert_eq!(alloc.overhead().headers, 5 * Chunk::META_SIZE);
+        let stats = alloc.stats();
+        assert_eq!(
+            overhead.bins + overhead.padding + stats.allocated_bytes + stats.free_bytes,
+            4 << 10
+        );
+    }
+
+    #[test]
+    fn foreign_policy() {
+        use std::{
+            alloc::System,
+            panic::{catch_unwind, AssertUnwindSafe},
+        };
+
+        static FORWARDED: AtomicUsize = AtomicUsize::new(0);
+        unsafe fn forward(ptr: *mut u8, layout: Layout) {
+            FORWARDED.fetch_add(1, SeqCst);
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        let layout = Layout::from_size_align(16, 8).unwrap();
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let foreign = unsafe { System.alloc(layout) };
+        let result = catch_unwind(AssertUnwindSafe(|| unsafe {
+            alloc.dealloc(foreign, layout)
+        }));
+        assert!(result.is_err());
+        assert_eq!(alloc.check(), Ok(()));
+
+        let alloc = alloc.with_foreign_policy(ForeignPolicy::Ignore);
+        unsafe { alloc.dealloc(foreign, layout) }
+        assert!(unsafe { alloc.realloc(foreign, layout, 32) }.is_null());
+        let ops = alloc.stats().ops;
+        assert_eq!((ops.dealloc, ops.realloc, ops.foreign), (0, 0, 2));
+
+        let alloc = alloc.with_foreign_policy(ForeignPolicy::Forward(forward));
+        unsafe { foreign.write_bytes(0xa5, 16) };
+        let ptr = unsafe { alloc.realloc(foreign, layout, 32) };
+        assert!(alloc.owns(ptr));
+        assert!((0..16).all(|offset| unsafe { *ptr.add(offset) } == 0xa5));
+        let foreign = unsafe { System.alloc(layout) };
+        unsafe { alloc.dealloc(foreign, layout) }
+        assert_eq!(FORWARDED.load(SeqCst), 2);
+        let stats = alloc.stats();
+        assert_eq!((stats.ops.alloc, stats.ops.foreign), (1, 4));
+        assert_eq!(stats.requested_bytes, 32);
+    }
+
+    #[test]
+    fn plugged_lock() {
+        static LOCKED: AtomicUsize = AtomicUsize::new(0);
+        struct Counting(AtomicBool);
+
+        unsafe impl RawMutex for Counting {
+            #[allow(clippy::declare_interior_mutable_const)]
+            const INIT: Self = Self(AtomicBool::new(false));
+            type GuardMarker = lock_api::GuardSend;
+
+            fn lock(&self) {
+                while !self.try_lock() {
+                    std::thread::yield_now()
+                }
+            }
+
+            fn try_lock(&self) -> bool {
+                let locked = self.0.compare_exchange(false, true, SeqCst, SeqCst).is_ok();
+                if locked {
+                    LOCKED.fetch_add(1, SeqCst);
+                }
+                locked
+            }
+
+            unsafe fn unlock(&self) {
+                self.0.store(false, SeqCst)
+            }
+        }
+
+        let data = &mut *vec![0; 16 << 10];
+        let alloc =
+            Allocator::<_, Counting>::try_with_lock(Fixed::from(data), Config::default()).unwrap();
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        let ptr = unsafe { alloc.alloc(layout) };
+                        assert!(!ptr.is_null());
+                        unsafe { alloc.dealloc(ptr, layout) }
+                    }
+                });
+            }
+        });
+        assert!(LOCKED.load(SeqCst) >= 800);
+        assert_eq!(alloc.check(), Ok(()));
+        assert_eq!(alloc.stats().allocation_count, 0);
+
+        static LAZY: Allocator<Static<{ 4 << 10 }>, Counting> =
+            Allocator::lazy_with_lock(Static::new());
+        let ptr = unsafe { LAZY.alloc(layout) };
+        assert!(LAZY.owns(ptr));
+        unsafe { LAZY.dealloc(ptr, layout) }
+        assert_eq!(LAZY.check(), Ok(()));
+    }
+
+    #[test]
+    fn bulk() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(100, 16).unwrap();
+        let mut ptrs = [null_mut(); 64];
+        let count = unsafe { alloc.alloc_bulk(layout, &mut ptrs) };
+        assert!((1..64).contains(&count));
+        assert!(ptrs[..count].iter().all(|ptr| alloc.owns(*ptr)));
+        assert_eq!(alloc.stats().allocation_count, count);
+        unsafe { alloc.dealloc_bulk(&ptrs[..count], layout) }
+        let stats = alloc.stats();
+        assert_eq!(stats.allocation_count, 0);
+        assert_eq!((stats.ops.alloc, stats.ops.dealloc), (count, count));
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    fn op_counts() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptrs = [0; 3].map(|_| unsafe { alloc.alloc(layout) });
+        let ptr = unsafe { alloc.realloc(ptrs[0], layout, 300) };
+        assert!(unsafe { alloc.alloc(Layout::from_size_align(8 << 10, 8).unwrap()) }.is_null());
+        unsafe { alloc.dealloc(ptrs[1], layout) }
+        let stats = alloc.stats();
+        assert_eq!(
+            stats.ops,
+            OpCounts {
+                alloc: 4,
+                dealloc: 1,
+                realloc: 1,
+                failed: 1,
+                foreign: 0
+            }
+        );
+        assert_eq!(stats.requested_bytes, 400);
+        assert_eq!(stats.peak_requested_bytes, 500);
+
+        unsafe { alloc.dealloc(ptr, Layout::from_size_align(300, 8).unwrap()) }
+        unsafe { alloc.dealloc(ptrs[2], layout) }
+        let stats = alloc.stats();
+        assert_eq!(stats.requested_bytes, 0);
+        assert_eq!(stats.peak_requested_bytes, 500);
+        assert_eq!(stats.ops.dealloc, 3);
+    }
+
+    #[test]
+    fn attach() {
+        let data = &mut *vec![0; 4 << 10];
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let ptr = unsafe { Allocator::new(Fixed::from(&mut *data)).alloc(layout) };
+        let alloc = Allocator::attach(Fixed::from(&mut *data)).unwrap();
+        assert!(alloc.find_chunk(ptr).unwrap().in_use);
+        unsafe { alloc.dealloc(ptr, layout) }
+
+        data[1] += 1;
+        assert_eq!(
+            Allocator::attach(Fixed::from(&mut *data)).err(),
+            Some(CheckError::Version {
+                version: Overlay::LAYOUT_VERSION + 1,
+                flags: Overlay::LAYOUT_FLAGS
+            })
+        );
+        data[0] = 0;
+        assert_eq!(
+            Allocator::attach(Fixed::from(&mut *data)).err(),
+            Some(CheckError::Marker)
+        );
+        assert_eq!(
+            Allocator::attach(Fixed::from(&mut [0; 8][..])).err(),
+            Some(CheckError::Marker)
+        );
+    }
+
+    #[test]
+    fn attach_moved() {
+        let data = &mut *vec![0; 4 << 10];
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let ptrs = {
+            let alloc = Allocator::with_config(
+                Fixed::from(&mut *data),
+                Config {
+                    fast_bins: false,
+                    ..Default::default()
+                },
+            );
+            let ptrs = Vec::from_iter((0..6).map(|_| unsafe { alloc.alloc(layout) }));
+            for ptr in ptrs.iter().step_by(2) {
+                unsafe { alloc.dealloc(*ptr, layout) }
+            }
+            ptrs
+        };
+
+        // the links hold no address, so a plain copy anywhere else is the same heap
+        let copy = &mut *data.to_vec();
+        let alloc = Allocator::attach(Fixed::from(&mut *copy)).unwrap();
+        assert_eq!(alloc.check(), Ok(()));
+        assert_eq!(alloc.stats().allocation_count, 3);
+        // the last one freed is the first one taken again
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert_eq!(
+            ptr as usize - alloc.base() as usize,
+            ptrs[4] as usize - data.as_ptr() as usize
+        );
+        unsafe { alloc.dealloc(ptr, layout) }
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "written after freed")]
+    fn write_after_free() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                fast_bins: false,
+                ..Default::default()
+            },
+        );
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        unsafe { alloc.alloc(layout) };
+        unsafe { alloc.dealloc(ptr, layout) }
+        // past the links of the free chunk
+        unsafe { *ptr.add(40) = 0 };
+        unsafe { alloc.alloc(layout) };
+    }
+
+    #[test]
+    fn exact_bin_stack() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                fast_bins: false,
+                ..Default::default()
+            },
+        );
+        let layout = Layout::from_size_align(40, 8).unwrap();
+        let separator = Layout::from_size_align(8, 8).unwrap();
+        let ptrs = [0; 4].map(|_| unsafe {
+            let ptr = alloc.alloc(layout);
+            alloc.alloc(separator);
+            ptr
+        });
+        for ptr in ptrs {
+            unsafe { alloc.dealloc(ptr, layout) }
+        }
+        assert_eq!(alloc.check(), Ok(()));
+        // out of the middle of the stack, by coalescing the two freed neighbors
+        let info = alloc.find_chunk(ptrs[1]).unwrap();
+        unsafe { alloc.dealloc(info.addr.add(info.size + 8), separator) }
+        assert_eq!(alloc.check(), Ok(()));
+        // last in first out
+        for ptr in [ptrs[3], ptrs[0]] {
+            assert_eq!(unsafe { alloc.alloc(layout) }, ptr);
+        }
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    fn walk_chunks() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                top_policy: TopPolicy::KeepWilderness,
+                ..Default::default()
+            },
+        );
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let ptrs = Vec::from_iter((0..4).map(|_| unsafe { alloc.alloc(layout) }));
+        unsafe { alloc.dealloc(ptrs[1], layout) }
+        let chunks = Vec::from_iter(alloc.chunks());
+        assert_eq!(
+            Vec::from_iter(chunks.iter().map(|info| info.in_use)),
+            [true, false, true, true, false, false]
+        );
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].addr.wrapping_add(pair[0].size), pair[1].addr);
+        }
+        for ptr in ptrs {
+            assert!(chunks.iter().any(|info| info.contains(ptr)));
+        }
+        let mut walk = alloc.chunks();
+        assert_eq!(walk.by_ref().count(), chunks.len());
+        assert!(walk.next().is_none());
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    fn grow_hint() {
+        for top_policy in [TopPolicy::KeepWilderness, TopPolicy::UseWilderness] {
+            let data = &mut *vec![0; 4 << 10];
+            let config = Config {
+                top_policy,
+                fast_bins: false,
+                ..Default::default()
+            };
+            let alloc = Allocator::with_config(Fixed::from(data), config);
+            let layout = Layout::from_size_align(40, 16).unwrap();
+            let ptrs = [0; 3].map(|_| unsafe { alloc.alloc(layout) });
+            let hint = unsafe { alloc.grow_hint(ptrs[0], layout) };
+            assert!(hint >= layout.size());
+            assert_eq!(unsafe { alloc.realloc(ptrs[0], layout, hint) }, ptrs[0]);
+            let layout0 = Layout::from_size_align(hint, 16).unwrap();
+            assert_eq!(unsafe { alloc.grow_hint(ptrs[0], layout0) }, hint);
+
+            unsafe { alloc.dealloc(ptrs[1], layout) }
+            let hint = unsafe { alloc.grow_hint(ptrs[0], layout0) };
+            assert!(hint > layout0.size());
+            assert_eq!(unsafe { alloc.realloc(ptrs[0], layout0, hint) }, ptrs[0]);
+            let layout0 = Layout::from_size_align(hint, 16).unwrap();
+            assert_ne!(
+                unsafe { alloc.realloc(ptrs[0], layout0, hint + 1) },
+                ptrs[0]
+            );
+            assert_eq!(alloc.check(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn self_test() {
+        for top_policy in [TopPolicy::KeepWilderness, TopPolicy::UseWilderness] {
+            let config = Config {
+                top_policy,
+                ..Default::default()
+            };
+            // too small for the largest sizes
+            let data = &mut *vec![0; 32 << 10];
+            let alloc = Allocator::with_config(Fixed::from(data), config);
+            let chunks = Vec::from_iter(alloc.chunks());
+            assert_eq!(alloc.self_test(), Ok(()));
+            assert_eq!(Vec::from_iter(alloc.chunks()), chunks);
+
+            // growing for all of them
+            let data = &mut *vec![0; 256 << 10];
+            let alloc = Allocator::with_config(Reserved(data, 4 << 10, false), config);
+            assert_eq!(alloc.self_test(), Ok(()));
+            // the heap may shrink back once everything is freed, so the growth shows in the stats
+            assert!(alloc.stats().peak_requested_bytes > 64 << 10);
+            assert_eq!(alloc.stats().allocation_count, 0);
+        }
+
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        let info = alloc.find_chunk(ptr).unwrap();
+        unsafe { info.addr.add(info.size).write_bytes(0xff, 8) };
+        assert!(matches!(alloc.self_test(), Err(SelfTestError::Check(_))));
+    }
+
+    #[test]
+    fn budget() {
+        let data = &mut *vec![0; 16 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        // free chunks in the same bin as the request below, but all too small for it
+        let small = Layout::from_size_align(256, 8).unwrap();
+        let separator = Layout::from_size_align(8, 8).unwrap();
+        let ptrs = [0; 8].map(|_| unsafe {
+            let ptr = alloc.alloc(small);
+            alloc.alloc(separator);
+            ptr
+        });
+        for ptr in ptrs {
+            unsafe { alloc.dealloc(ptr, small) }
+        }
+        let layout = Layout::from_size_align(300, 8).unwrap();
+        let budget = Budget { scan: 4, copy: 0 };
+        assert_eq!(
+            alloc.alloc_within(layout, budget),
+            Err(BudgetError::Exceeded)
+        );
+        assert_eq!(alloc.check(), Ok(()));
+        let budget = Budget { scan: 16, copy: 0 };
+        let ptr = alloc.alloc_within(layout, budget).unwrap().as_ptr();
+        unsafe { ptr.write_bytes(0x11, layout.size()) };
+        // too large for the small free chunks, so it is right above
+        unsafe { alloc.alloc(Layout::from_size_align(1 << 10, 8).unwrap()) };
+
+        assert_eq!(
+            unsafe { alloc.realloc_within(ptr, layout, 1000, budget) },
+            Err(BudgetError::Exceeded)
+        );
+        let budget = Budget {
+            scan: 16,
+            copy: 300,
+        };
+        let new_ptr = unsafe { alloc.realloc_within(ptr, layout, 1000, budget) }.unwrap();
+        assert_ne!(new_ptr.as_ptr(), ptr);
+        let data = unsafe { slice::from_raw_parts(new_ptr.as_ptr(), layout.size()) };
+        assert!(data.iter().all(|byte| *byte == 0x11));
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    fn start_align() {
+        for start_align in [1, 8, 64, 1024] {
+            for top_policy in [TopPolicy::KeepWilderness, TopPolicy::UseWilderness] {
+                let data = &mut *vec![0; 4 << 10];
+                let config = Config {
+                    top_policy,
+                    start_align,
+                    ..Default::default()
+                };
+                let alloc = Allocator::with_config(Fixed::from(&mut data[8..]), config);
+                let ptr = unsafe { alloc.alloc(Layout::from_size_align(16, 1).unwrap()) };
+                assert_eq!(ptr as usize % start_align, 0);
+                assert_eq!(alloc.check(), Ok(()));
+                let bins = alloc.overhead().bins;
+                let bins_end = alloc.acquire_space().as_ptr() as usize + bins;
+                let first = alloc.chunks().next().unwrap();
+                assert!(first.addr as usize >= bins_end);
+                assert!((first.addr as usize) < bins_end + usize::max(start_align, 8));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "heap texture-arena is broken")]
+    fn named() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new_named(Fixed::from(data), "texture-arena");
+        assert_eq!(alloc.name(), Some("texture-arena"));
+        let mut dump = String::new();
+        alloc.dump(&mut dump).unwrap();
+        assert!(dump.starts_with("heap texture-arena\n"));
+
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        // overwrite the header of the higher chunk
+        let info = alloc.find_chunk(ptr).unwrap();
+        unsafe { info.addr.add(info.size).write_bytes(0xff, 8) };
+        alloc.sanity_check()
+    }
+
+    #[cfg(feature = "alloc-id")]
+    #[test]
+    fn token() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let aligned = Layout::from_size_align(16, 64).unwrap();
+        let ptr = unsafe { alloc.alloc(aligned) };
+        let token = unsafe { alloc.token_of(ptr) };
+        assert!(alloc.validate_token(ptr, token));
+        let guard = unsafe { alloc.alloc(layout) };
+        let grown = unsafe { alloc.realloc(ptr, aligned, 64) };
+        assert_eq!(unsafe { alloc.token_of(grown) }, token);
+
+        unsafe { alloc.dealloc(grown, Layout::from_size_align(64, 64).unwrap()) }
+        assert!(!alloc.validate_token(grown, token));
+        // one of the chunks again
+        let reused = unsafe { alloc.alloc(aligned) };
+        assert!(reused == ptr || reused == grown);
+        assert!(!alloc.validate_token(reused, token));
+        assert!(alloc.validate_token(reused, unsafe { alloc.token_of(reused) }));
+        assert!(!alloc.validate_token(null_mut(), token));
+        unsafe { alloc.dealloc(guard, layout) }
+    }
+
+    #[cfg(feature = "alloc-id")]
+    #[test]
+    fn alloc_id() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                top_policy: TopPolicy::KeepWilderness,
+                ..Default::default()
+            },
+        );
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let ptrs = Vec::from_iter((0..4).map(|_| unsafe { alloc.alloc(layout) }));
+        for (id, ptr) in ptrs.iter().enumerate() {
+            assert_eq!(alloc.find_chunk(*ptr).unwrap().id, Some(id as u64));
+        }
+        // in place, unless `minimal` copies it too
+        let ptr = unsafe { alloc.realloc(ptrs[3], layout, 64) };
+        assert_eq!(ptr == ptrs[3], !cfg!(feature = "minimal"));
+        assert_eq!(alloc.find_chunk(ptr).unwrap().id, Some(3));
+        // copied
+        let ptr = unsafe { alloc.realloc(ptrs[0], layout, 64) };
+        assert_ne!(ptr, ptrs[0]);
+        assert_eq!(alloc.find_chunk(ptr).unwrap().id, Some(0));
+        unsafe { alloc.dealloc(ptrs[1], layout) }
+        let ptr = unsafe { alloc.alloc(layout) };
+        // id 4 on was taken by the copied reallocations
+        let copied = if cfg!(feature = "minimal") { 2 } else { 1 };
+        assert_eq!(alloc.find_chunk(ptr).unwrap().id, Some(4 + copied));
+    }
+
+    #[cfg(any(feature = "metadata-8", feature = "metadata-16"))]
+    #[test]
+    fn metadata() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let aligned = Layout::from_size_align(16, 64).unwrap();
+        let ptrs = [layout, aligned, layout, layout].map(|layout| unsafe { alloc.alloc(layout) });
+        // keep the last one off the top chunk
+        unsafe { alloc.alloc(layout) };
+        for (index, ptr) in ptrs.into_iter().enumerate() {
+            let metadata = unsafe { alloc.metadata_of(ptr) };
+            assert_eq!(metadata.as_ptr() as usize % 8, 0);
+            assert_eq!(unsafe { metadata.read() }, [0; METADATA_SIZE]);
+            unsafe { metadata.write([index as u8 + 1; METADATA_SIZE]) }
+            unsafe { ptr.write_bytes(0xa5, 16) }
+        }
+        for (index, ptr) in ptrs.into_iter().enumerate() {
+            let metadata = unsafe { alloc.metadata_of(ptr) };
+            assert_eq!(unsafe { metadata.read() }, [index as u8 + 1; METADATA_SIZE]);
+        }
+        assert_eq!(alloc.check(), Ok(()));
+
+        unsafe { alloc.dealloc(ptrs[3], layout) }
+        // in place, unless `minimal` copies it too
+        let ptr = unsafe { alloc.realloc(ptrs[2], layout, 64) };
+        assert_eq!(ptr == ptrs[2], !cfg!(feature = "minimal"));
+        assert_eq!(unsafe { alloc.metadata_of(ptr).read() }, [3; METADATA_SIZE]);
+        // copied
+        let ptr = unsafe { alloc.realloc(ptrs[1], aligned, 200) };
+        assert_ne!(ptr, ptrs[1]);
+        assert_eq!(unsafe { alloc.metadata_of(ptr).read() }, [2; METADATA_SIZE]);
+        // the reused chunk starts over
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert_eq!(unsafe { alloc.metadata_of(ptr).read() }, [0; METADATA_SIZE]);
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[test]
+    fn scrub() {
+        let data = &mut *vec![0; 16 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(200, 8).unwrap();
+        let ptrs = Vec::from_iter((0..20).map(|_| unsafe { alloc.alloc(layout) }));
+        for ptr in ptrs.iter().step_by(2) {
+            unsafe { alloc.dealloc(*ptr, layout) }
+        }
+        // a few chunks per step
+        let mut cursor = ScrubCursor::default();
+        let mut steps = 1;
+        while !alloc.scrub(&mut cursor, 1 << 10).unwrap() {
+            steps += 1
+        }
+        assert!((4..=10).contains(&steps));
+        assert_eq!(cursor, ScrubCursor::default());
+
+        // a bit flipped in the middle of a free chunk
+        let info = alloc.find_chunk(ptrs[10]).unwrap();
+        unsafe { *info.addr.add(100) ^= 4 }
+        let mut cursor = ScrubCursor::default();
+        let error = loop {
+            match alloc.scrub(&mut cursor, 1 << 10) {
+                Ok(_) => {}
+                Err(error) => break error,
+            }
+        };
+        assert_eq!(error, CheckError::Poison(info.addr));
+        // found again until restored, and missed by the full check
+        assert_eq!(alloc.scrub(&mut cursor, 1 << 10), Err(error));
+        assert_eq!(alloc.check(), Ok(()));
+        unsafe { *info.addr.add(100) ^= 4 }
+        assert!(alloc.scrub(&mut cursor, usize::MAX).unwrap());
+
+        // and in the size of an allocated one below the cursor
+        let mut cursor = ScrubCursor::default();
+        assert!(!alloc.scrub(&mut cursor, 2 << 10).unwrap());
+        let info = alloc.find_chunk(ptrs[1]).unwrap();
+        unsafe { *info.addr.cast::<u64>() ^= 1 << 4 }
+        assert!(alloc.scrub(&mut cursor, 1 << 10).is_err());
+        unsafe { *info.addr.cast::<u64>() ^= 1 << 4 }
+        assert!(alloc.scrub(&mut cursor, usize::MAX).unwrap());
+    }
+
+    #[test]
+    fn check_corruption() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                fast_bins: false,
+                ..Default::default()
+            },
+        );
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        unsafe { alloc.alloc(layout) };
+        unsafe { alloc.dealloc(ptr, layout) };
+        assert_eq!(alloc.check(), Ok(()));
+
+        let info = alloc.find_chunk(ptr).unwrap();
+        let footer = unsafe { info.addr.add(info.size - 8).cast::<u64>() };
+        unsafe { *footer += 8 }
+        assert_eq!(alloc.check(), Err(CheckError::Footer(info.addr)));
+        unsafe { *footer -= 8 }
+
+        let next = unsafe { info.addr.add(16).cast() };
+        let next_addr = unsafe { read_link(next) };
+        unsafe { write_link(next, info.addr.add(1)) }
+        assert_eq!(alloc.check(), Err(CheckError::FreeList(info.addr)));
+        unsafe { write_link(next, next_addr) }
+
+        // a bin for a size class without free chunk
+        let bin = unsafe { alloc.acquire_space().as_mut_ptr().add(8 * 10) };
+        unsafe { write_link(bin.cast(), info.addr) }
+        assert_eq!(alloc.check(), Err(CheckError::Bin(10)));
+        unsafe { write_link(bin.cast(), null_mut()) }
+        assert_eq!(alloc.check(), Ok(()));
+
+        // an empty bin marked in the bitmap
+        let bins_len = Overlay::bins_len_of(Bins::default());
+        let bitmap = unsafe { alloc.acquire_space().as_mut_ptr().add(8 * bins_len) };
+        unsafe { *bitmap.cast::<u64>() ^= 1 << 11 }
+        assert_eq!(alloc.check(), Err(CheckError::Bin(11)));
+        unsafe { *bitmap.cast::<u64>() ^= 1 << 11 }
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[cfg(feature = "audit")]
+    #[test]
+    #[should_panic(expected = "broken header")]
+    fn audit_overflow() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        let size = alloc.find_chunk(ptr).unwrap().size;
+        // overflow into the header of the next chunk
+        unsafe { ptr.write_bytes(0xa5, size) };
+        unsafe { alloc.dealloc(ptr, layout) }
+    }
+
+    #[test]
+    fn clone_heap() {
+        let page_aligned = || SystemBuffer::new(8 << 10, 4096);
+        let alloc = Allocator::new(page_aligned());
+        let layout = Layout::from_size_align(100, 64).unwrap();
+        let ptrs = Vec::from_iter((0..8).map(|i| unsafe {
+            let ptr = alloc.alloc(layout);
+            ptr.write_bytes(i, 100);
+            ptr
+        }));
+        for ptr in ptrs.iter().step_by(2) {
+            unsafe { alloc.dealloc(*ptr, layout) }
+        }
+
+        let clone = alloc.clone_heap(page_aligned()).unwrap();
+        assert_eq!(clone.check(), Ok(()));
+        assert_eq!(clone.stats(), alloc.stats());
+        let cloned = alloc.translate(ptrs[1], &clone);
+        assert_ne!(cloned, ptrs[1]);
+        assert_eq!(unsafe { *cloned }, 1);
+        // diverge from here
+        unsafe {
+            clone.dealloc(cloned, layout);
+            clone.alloc(Layout::from_size_align(1000, 8).unwrap());
+            alloc.alloc(layout);
+        }
+        assert_eq!(clone.check(), Ok(()));
+        assert_eq!(alloc.check(), Ok(()));
+        assert_eq!(unsafe { *ptrs[1] }, 1);
+        assert_ne!(clone.stats(), alloc.stats());
+
+        let misaligned = &mut std::vec![0; (8 << 10) + 8][8..];
+        if misaligned.as_ptr() as usize & 4095 != 0 {
+            assert!(alloc.clone_heap(Fixed::from(misaligned)).is_none());
+        }
+    }
+
+    // grows in place within a reserved buffer, with the heap released if the last field is set
+    struct Reserved<'a>(&'a mut [u8], usize, bool);
+
+    impl Deref for Reserved<'_> {
+        type Target = [u8];
+
+        fn deref(&self) -> &Self::Target {
+            &self.0[..self.1]
+        }
+    }
+
+    impl DerefMut for Reserved<'_> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0[..self.1]
+        }
+    }
+
+    impl Space for Reserved<'_> {
+        fn set_size(&mut self, bytes: usize) -> bool {
+            if bytes > self.0.len() {
+                return false;
+            }
+            self.1 = bytes;
+            true
         }
-    }
-}
 
-pub struct Allocator<S>(Mutex<S>);
+        fn can_shrink(&self) -> bool {
+            true
+        }
 
-impl<S> Allocator<S> {
-    pub fn new(mut space: S) -> Self
-    where
-        S: Space,
-    {
-        unsafe { Overlay::new(&mut space).init(space.len()) };
-        Self(Mutex::new(space))
+        fn reservation(&self) -> Option<Reservation> {
+            Some(Reservation {
+                addr: self.0.as_ptr() as _,
+                len: self.0.len(),
+            })
+            .filter(|_| self.2)
+        }
+
+        unsafe fn reserve(reservation: Reservation, bytes: usize) -> bool {
+            RESERVE_COUNT.fetch_add(1, SeqCst);
+            bytes <= reservation.len
+        }
     }
 
-    pub(crate) fn acquire_space(&self) -> MutexGuard<'_, S> {
-        loop {
-            if let Some(space) = self.0.try_lock() {
-                break space;
-            }
+    static RESERVE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    // grows by at most the last field at once, still reporting success
+    struct Partial<'a>(&'a mut [u8], usize, usize);
+
+    impl Deref for Partial<'_> {
+        type Target = [u8];
+
+        fn deref(&self) -> &Self::Target {
+            &self.0[..self.1]
         }
     }
 
-    pub fn sanity_check(&self)
-    where
-        S: Space,
-    {
-        unsafe { Overlay::new(&mut *self.acquire_space()).sanity_check() }
+    impl DerefMut for Partial<'_> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0[..self.1]
+        }
     }
-}
 
-unsafe impl<S> GlobalAlloc for Allocator<S>
-where
-    S: Space,
-{
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        unsafe { Overlay::alloc_in_space(&mut *self.acquire_space(), layout) }
+    impl Space for Partial<'_> {
+        fn set_size(&mut self, bytes: usize) -> bool {
+            if bytes > self.0.len() {
+                return false;
+            }
+            self.1 = usize::min(bytes, self.1 + self.2);
+            true
+        }
     }
 
-    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        unsafe { Overlay::dealloc_in_space(&mut *self.acquire_space(), ptr, layout) }
+    // zeroes the bytes it grows with, like fresh pages
+    struct Zeroing<'a>(&'a mut [u8], usize);
+
+    impl Deref for Zeroing<'_> {
+        type Target = [u8];
+
+        fn deref(&self) -> &Self::Target {
+            &self.0[..self.1]
+        }
     }
 
-    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
-        unsafe { Overlay::realloc_in_space(&mut *self.acquire_space(), ptr, layout, new_size) }
+    impl DerefMut for Zeroing<'_> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0[..self.1]
+        }
     }
-}
 
-#[cfg(any(test, dev, feature = "paranoid"))]
-impl Overlay {
-    unsafe fn iter_all_chunk(&self) -> impl Iterator<Item = Chunk> {
-        use core::iter::from_fn;
+    impl Space for Zeroing<'_> {
+        fn set_size(&mut self, bytes: usize) -> bool {
+            if bytes > self.0.len() {
+                return false;
+            }
+            if bytes > self.1 {
+                self.0[self.1..bytes].fill(0)
+            }
+            self.1 = bytes;
+            true
+        }
 
-        debug_assert_eq!(unsafe { self.space.as_ref() }, &0x82);
-        let mut chunk = Some(unsafe { self.start_chunk() });
-        from_fn(move || {
-            let item = chunk;
-            chunk = match item {
-                Some(item) if unsafe { item.get_in_use() || !item.is_top() } => {
-                    Some(unsafe { item.get_higher_chunk() })
-                }
-                _ => None,
+        fn grows_zeroed(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn alloc_zeroed() {
+        fn run<S: Space>(alloc: &Allocator<S>) -> [bool; 3] {
+            let clean = |ptr| {
+                let mut space = alloc.acquire_space();
+                let overlay = Overlay::new(&mut *space);
+                unsafe { Chunk::from_any_user_data(ptr, overlay.limit).get_clean() }
             };
-            item
-        })
+            let zeroed = |ptr, size| {
+                unsafe { slice::from_raw_parts(ptr, size) }
+                    .iter()
+                    .all(|byte| *byte == 0)
+            };
+            let layout = Layout::from_size_align(1000, 8).unwrap();
+            let ptrs = [(); 4].map(|_| unsafe { alloc.alloc_zeroed(layout) });
+            let fresh = ptrs.iter().all(|ptr| clean(*ptr));
+            for ptr in ptrs {
+                assert!(zeroed(ptr, 1000));
+                unsafe { ptr.write_bytes(0x55, 1000) }
+            }
+            unsafe { alloc.dealloc(ptrs[1], layout) }
+            let ptr = unsafe { alloc.alloc_zeroed(layout) };
+            let reused = clean(ptr);
+            assert!(zeroed(ptr, 1000));
+            // beyond the initial size
+            let large = Layout::from_size_align(300 << 10, 64).unwrap();
+            let large_ptr = unsafe { alloc.alloc_zeroed(large) };
+            let grown = clean(large_ptr);
+            assert!(zeroed(large_ptr, 300 << 10));
+            assert_eq!(alloc.check(), Ok(()));
+            [fresh, reused, grown]
+        }
+
+        let data = &mut *vec![0xaa; 1 << 20];
+        let alloc = Allocator::lazy(Zeroing(data, 0));
+        assert_eq!(run(&alloc), [true, false, true]);
+        // not known to be zero
+        let data = &mut *vec![0xaa; 1 << 20];
+        let alloc = Allocator::lazy(Reserved(data, 0, false));
+        assert_eq!(run(&alloc), [false; 3]);
     }
 
-    unsafe fn iter_free_chunk(&self) -> impl Iterator<Item = Chunk> {
-        use core::iter::from_fn;
+    // gives back whole pages by zeroing them, counting the bytes
+    struct Discarding<'a>(&'a mut [u8], usize);
 
-        debug_assert_eq!(unsafe { self.space.as_ref() }, &0x82);
-        let mut chunk = Some(unsafe { self.find_smallest(0) });
-        from_fn(move || {
-            let item = chunk;
-            if let Some(item) = item {
-                chunk = unsafe { item.get_next() };
-            }
-            item
-        })
+    impl Deref for Discarding<'_> {
+        type Target = [u8];
+
+        fn deref(&self) -> &Self::Target {
+            self.0
+        }
     }
 
-    unsafe fn sanity_check(&self) {
-        let mut chunks = [None; 10];
-        // println!("check:");
-        for (i, chunk) in unsafe { self.iter_all_chunk() }.enumerate() {
-            // println!("  {chunk:?}");
-            chunks[i % 10] = Some(chunk);
-            debug_assert!(unsafe { chunk.get_size() } >= Chunk::MIN_SIZE, "{chunks:?}",);
+    impl DerefMut for Discarding<'_> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            self.0
         }
-        for _chunk in unsafe { self.iter_free_chunk() } {}
-        // TODO more check if needed
     }
-}
 
-#[cfg(not(any(test, dev, feature = "paranoid")))]
-impl Overlay {
-    unsafe fn sanity_check(&self) {}
-}
+    impl Space for Discarding<'_> {
+        fn set_size(&mut self, bytes: usize) -> bool {
+            bytes == self.0.len()
+        }
 
-#[cfg(test)]
-mod tests {
-    use std::{iter::repeat, slice, vec, vec::Vec};
+        fn discard(&mut self, range: Range<usize>) -> Option<Range<usize>> {
+            let range = range.start.next_multiple_of(4096)..range.end / 4096 * 4096;
+            if range.is_empty() {
+                return None;
+            }
+            self.0[range.clone()].fill(0);
+            self.1 += range.len();
+            Some(range)
+        }
+    }
 
-    use crate::space::Fixed;
+    #[test]
+    fn discard_free() {
+        let data = &mut *vec![0xaa; 1 << 20];
+        let alloc = Allocator::new(Discarding(data, 0));
+        let large = Layout::from_size_align(200 << 10, 8).unwrap();
+        let small = Layout::from_size_align(1 << 10, 8).unwrap();
+        let ptrs = [large, small, large, small].map(|layout| unsafe { alloc.alloc(layout) });
+        for ptr in ptrs {
+            unsafe { ptr.write_bytes(0x55, 1 << 10) }
+        }
+        // too small to
+        unsafe { alloc.dealloc(ptrs[1], small) }
+        assert_eq!(alloc.acquire_space().1, 0);
+        // in the middle of the heap, and coalesced with the small one
+        unsafe { alloc.dealloc(ptrs[2], large) }
+        let discarded = alloc.acquire_space().1;
+        assert!(discarded >= (200 << 10) - 8192);
+        assert_eq!(alloc.check(), Ok(()));
 
-    use super::*;
+        let ptr = unsafe { alloc.alloc_zeroed(large) };
+        assert_eq!(ptr, ptrs[1]);
+        let mut space = alloc.acquire_space();
+        let overlay = Overlay::new(&mut *space);
+        assert!(unsafe { Chunk::from_any_user_data(ptr, overlay.limit).get_clean() });
+        drop(space);
+        assert!(unsafe { slice::from_raw_parts(ptr, 200 << 10) }
+            .iter()
+            .all(|byte| *byte == 0));
+        unsafe { alloc.dealloc(ptrs[0], large) }
+        unsafe { alloc.dealloc(ptr, large) }
+        assert_eq!(alloc.check(), Ok(()));
+    }
 
     #[test]
-    fn single_free_chunk_on_init() {
-        // leveraging the fact that System allocator always allocate 8 bytes aligned memory
-        let data = &mut *vec![0; 4 << 10];
-        let alloc = Allocator::new(Fixed::from(data));
-        let all_chunk =
-            Vec::from_iter(unsafe { Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk() });
-        let free_chunk =
-            Vec::from_iter(unsafe { Overlay::new(&mut *alloc.acquire_space()).iter_free_chunk() });
-        assert_eq!(all_chunk.len(), 2);
-        assert_eq!(free_chunk, all_chunk);
+    fn segmented() {
+        let data = &mut *vec![0; 4 * (256 << 10)];
+        let mut regions = data
+            .chunks_mut(256 << 10)
+            .map(|region| Reserved(region, 0, false));
+        let spaces = core::array::from_fn::<_, 4, _>(|_| regions.next().unwrap());
+        let alloc = Allocator::segmented(Segments::new(spaces, 64 << 10));
+        assert_eq!(alloc.segment_count(), 0);
+        let layout = Layout::from_size_align(100 << 10, 8).unwrap();
+        let ptrs = Vec::from_iter((0..6).map(|_| unsafe { alloc.alloc(layout) }));
+        for (i, ptr) in ptrs.iter().enumerate() {
+            assert!(!ptr.is_null());
+            assert!(alloc.owns(*ptr));
+            unsafe { ptr.write_bytes(i as u8, 100 << 10) }
+        }
+        // the first region grown in place before taking the others
+        assert_eq!(alloc.segment_count(), 3);
+        assert_eq!(alloc.segments_stats().allocation_count, 6);
+        assert_eq!(alloc.check_segments(), Ok(()));
+        // larger than a region
+        let large = Layout::from_size_align(256 << 10, 8).unwrap();
+        assert!(unsafe { alloc.alloc(large) }.is_null());
+
+        // moved into another region
+        let new_ptr = unsafe { alloc.realloc(ptrs[0], layout, 120 << 10) };
+        assert!(!new_ptr.is_null());
+        assert_eq!(alloc.segment_count(), 4);
+        assert!(unsafe { slice::from_raw_parts(new_ptr, 100 << 10) }
+            .iter()
+            .all(|byte| *byte == 0));
+        unsafe { alloc.dealloc(new_ptr, Layout::from_size_align(120 << 10, 8).unwrap()) }
+        for &ptr in &ptrs[1..] {
+            unsafe { alloc.dealloc(ptr, layout) }
+        }
+        let stats = alloc.segments_stats();
+        assert_eq!((stats.allocation_count, stats.ops.failed), (0, 1));
+        assert_eq!(alloc.check_segments(), Ok(()));
+        assert!(!alloc.owns(&0u8));
     }
 
     #[test]
-    fn working_debug_chunk() {
-        let data = &mut *vec![0; 4 << 10];
-        let alloc = Allocator::new(Fixed::from(data));
-        assert!(std::format!(
-            "{:?}",
-            unsafe { Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk() }
-                .next()
-                .unwrap()
-        )
-        .starts_with("Chunk"));
+    fn partial_growth() {
+        let data = &mut *vec![0; 128 << 10];
+        // not even whole chunks at once
+        let alloc = Allocator::new(Partial(data, 4 << 10, 1001));
+        let layout = Layout::from_size_align(10 << 10, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert!(!ptr.is_null());
+        let len = alloc.acquire_space().len();
+        assert!(len > 10 << 10 && len % 8 == 0);
+        assert_eq!(alloc.check(), Ok(()));
+        let ptrs = Vec::from_iter((0..4).map(|_| unsafe { alloc.alloc(layout) }));
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        // grown as far as possible, then out of memory
+        let large = Layout::from_size_align(128 << 10, 8).unwrap();
+        assert!(unsafe { alloc.alloc(large) }.is_null());
+        assert!(alloc.acquire_space().len() <= 128 << 10);
+        assert_eq!(alloc.check(), Ok(()));
+        for ptr in ptrs.into_iter().chain([ptr]) {
+            unsafe { alloc.dealloc(ptr, layout) }
+        }
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.check(), Ok(()));
     }
 
     #[test]
-    fn valid_addr() {
-        fn run(sizes: impl Iterator<Item = usize>) {
-            let data = &mut *vec![0; 4 << 10];
-            let ptr_range = data.as_mut_ptr_range();
-            let alloc = Allocator::new(Fixed::from(data));
-            for size in sizes {
-                let ptr = unsafe { alloc.alloc(Layout::from_size_align(size, 1).unwrap()) };
-                if ptr.is_null() {
-                    break;
-                }
-                assert!(ptr_range.contains(&ptr));
-            }
-        }
+    fn lazy() {
+        let data = &mut *vec![0; 256 << 10];
+        let alloc = Allocator::lazy(Reserved(data, 0, false));
+        let layout = Layout::from_size_align(200 << 10, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert!(!ptr.is_null());
+        assert!(alloc.acquire_space().len() > Overlay::LAZY_SIZE);
+        unsafe { alloc.dealloc(ptr, layout) }
+        assert_eq!(alloc.stats().ops.alloc, 1);
+        assert_eq!(alloc.check(), Ok(()));
 
-        run([1].into_iter());
-        run(1..10);
-        run(repeat(1));
-        run(1..);
+        // laid out on the first use of any kind
+        let data = &mut *vec![0; 8 << 10];
+        let alloc = Allocator::lazy(Fixed::from(data));
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.acquire_space().len(), 8 << 10);
+        assert_eq!(alloc.check(), Ok(()));
     }
 
     #[test]
-    fn aligned_addr() {
-        fn run(sizes: impl Iterator<Item = usize>, align: usize) {
-            let data = &mut *vec![0; 4 << 10];
-            let alloc = Allocator::new(Fixed::from(data));
-            for size in sizes {
-                let ptr = unsafe { alloc.alloc(Layout::from_size_align(size, align).unwrap()) };
-                if ptr.is_null() {
-                    break;
-                }
-                assert_eq!(ptr.align_offset(align), 0);
+    fn grow_reserving() {
+        let data = &mut *vec![0; 4 << 20];
+        let alloc = Allocator::new(Reserved(data, 4 << 10, true));
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let layout = Layout::from_size_align(300, 8).unwrap();
+                    let mut ptrs = Vec::new();
+                    for _ in 0..1000 {
+                        let ptr = unsafe { alloc.alloc(layout) };
+                        assert!(!ptr.is_null());
+                        unsafe { ptr.write_bytes(0xa5, layout.size()) }
+                        ptrs.push(ptr);
+                    }
+                    for ptr in ptrs.iter().step_by(2) {
+                        let ptr = unsafe { alloc.realloc(*ptr, layout, 1000) };
+                        assert!(!ptr.is_null());
+                        assert_eq!(unsafe { *ptr.add(299) }, 0xa5);
+                        unsafe { alloc.dealloc(ptr, Layout::from_size_align(1000, 8).unwrap()) }
+                    }
+                    for ptr in ptrs.iter().skip(1).step_by(2) {
+                        unsafe { alloc.dealloc(*ptr, layout) }
+                    }
+                });
             }
+        });
+        assert!(RESERVE_COUNT.load(SeqCst) > 0);
+        assert_eq!(alloc.check(), Ok(()));
+        assert_eq!(alloc.stats().allocation_count, 0);
+    }
+
+    #[test]
+    fn use_wilderness() {
+        let data = &mut *vec![0; 64 << 10];
+        let config = Config {
+            top_policy: TopPolicy::UseWilderness,
+            ..Default::default()
+        };
+        let alloc = Allocator::with_config(Reserved(data, 4 << 10, false), config);
+        assert_eq!(alloc.stats().free_count, 1);
+
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptrs = Vec::from_iter((0..10).map(|_| unsafe { alloc.alloc(layout) }));
+        assert_eq!(alloc.stats().free_count, 1);
+        for ptr in ptrs.iter().rev() {
+            unsafe { alloc.dealloc(*ptr, layout) }
+            assert_eq!(alloc.check(), Ok(()));
         }
+        assert_eq!(alloc.stats().free_count, 1);
 
-        run([1].into_iter(), 16);
-        run(1..10, 16);
-        run(1..10, 32);
-        run(repeat(1), 16);
-        run(repeat(1), 32);
-        run(1.., 16);
-        run(1.., 32);
-        run(1.., 64);
+        // extend the top chunk in place
+        let layout = Layout::from_size_align(10 << 10, 64).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert!(!ptr.is_null());
+        assert_eq!(alloc.check(), Ok(()));
+        assert_eq!(alloc.stats().free_count, 1);
+        unsafe { alloc.dealloc(ptr, layout) }
+        assert_eq!(alloc.check(), Ok(()));
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.stats().free_count, 1);
+
+        for methods in crate::fuzz::Method::padded_sequences() {
+            let data = &mut *vec![0; 4 << 10];
+            let alloc = Allocator::with_config(Fixed::from(data), config);
+            crate::fuzz::Method::run_fuzz_checked(&methods, alloc);
+        }
     }
 
     #[test]
-    fn alloc_dealloc_identical() {
-        fn run(layouts: impl Iterator<Item = Layout> + Clone) {
-            let data = &mut *vec![0; 4 << 10];
-            let alloc = Allocator::new(Fixed::from(data));
-            let chunks = Vec::from_iter(unsafe {
-                Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk()
-            });
-            let ptrs = Vec::from_iter(
-                layouts
-                    .clone()
-                    .map(|layout| unsafe { (alloc.alloc(layout), layout) })
-                    .take_while(|(ptr, _)| !ptr.is_null()),
-            );
-            for (ptr, layout) in ptrs.into_iter() {
-                // println!("{ptr:?} {layout:?}");
-                unsafe { alloc.dealloc(ptr, layout) }
-            }
-            assert_eq!(
-                Vec::from_iter(unsafe {
-                    Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk()
-                }),
-                chunks
-            );
+    fn shrink_after_free() {
+        for top_policy in [TopPolicy::KeepWilderness, TopPolicy::UseWilderness] {
+            let config = Config {
+                top_policy,
+                ..Default::default()
+            };
+            let data = &mut *vec![0; 4 << 20];
+            let alloc = Allocator::with_config(Reserved(data, 4 << 10, false), config);
+            let small = Layout::from_size_align(200, 8).unwrap();
+            let large = Layout::from_size_align(1 << 20, 8).unwrap();
+            let ptrs = [0; 2].map(|_| unsafe { alloc.alloc(small) });
+            let large_ptr = unsafe { alloc.alloc(large) };
+            let grown = alloc.acquire_space().len();
+            assert!(grown > 1 << 20);
 
-            // again but dealloc in LIFO order
-            // yet to find a way to eliminate this duplication
-            let data = &mut *vec![0; 4 << 10];
-            let alloc = Allocator::new(Fixed::from(data));
-            let chunks = Vec::from_iter(unsafe {
-                Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk()
-            });
-            let ptrs = Vec::from_iter(
-                layouts
-                    .map(|layout| unsafe { (alloc.alloc(layout), layout) })
-                    .take_while(|(ptr, _)| !ptr.is_null()),
-            );
-            for (ptr, layout) in ptrs.into_iter().rev() {
-                unsafe { alloc.dealloc(ptr, layout) }
+            // not worth shrinking yet
+            unsafe { alloc.dealloc(ptrs[1], small) }
+            assert_eq!(alloc.acquire_space().len(), grown);
+            unsafe { alloc.dealloc(large_ptr, large) }
+            let len = alloc.acquire_space().len();
+            assert!(len < 8 << 10, "{len}");
+            assert_eq!(alloc.check(), Ok(()));
+            assert_eq!(alloc.stats().allocation_count, 1);
+
+            // and grows again
+            let large_ptr = unsafe { alloc.alloc(large) };
+            assert!(!large_ptr.is_null());
+            unsafe {
+                alloc.dealloc(ptrs[0], small);
+                alloc.dealloc(large_ptr, large)
             }
-            assert_eq!(
-                Vec::from_iter(unsafe {
-                    Overlay::new(&mut *alloc.acquire_space()).iter_all_chunk()
-                }),
-                chunks
-            );
+            assert!(alloc.acquire_space().len() < 8 << 10);
+            assert_eq!(alloc.check(), Ok(()));
         }
-
-        run([Layout::from_size_align(1, 1).unwrap()].into_iter());
-        run((1..10).map(|size| Layout::from_size_align(size, 1).unwrap()));
-        run((0..=6).map(|align| Layout::from_size_align(1, 1 << align).unwrap()));
-        run((1..).map(|size| Layout::from_size_align(size, 1).unwrap()));
     }
 
     #[test]
-    fn realloc_in_place() {
-        let data = &mut *vec![0; 4 << 10];
-        let alloc = Allocator::new(Fixed::from(data));
-        let mut layout = Layout::from_size_align(8, 1).unwrap();
-        let ptr = unsafe { alloc.alloc(layout) };
-        let new_ptr = unsafe { alloc.realloc(ptr, layout, 16) };
-        assert_eq!(new_ptr, ptr);
-
-        layout = Layout::from_size_align(16, 1).unwrap();
-        loop {
-            let new_ptr = unsafe { alloc.realloc(ptr, layout, layout.size() * 2) };
-            if new_ptr.is_null() {
-                break;
+    fn trim() {
+        for top_policy in [TopPolicy::KeepWilderness, TopPolicy::UseWilderness] {
+            let config = Config {
+                top_policy,
+                ..Default::default()
+            };
+            let data = &mut *vec![0; 4 << 20];
+            let alloc = Allocator::with_config(Reserved(data, 4 << 10, false), config);
+            let layout = Layout::from_size_align(64 << 10, 8).unwrap();
+            let ptrs = Vec::from_iter((0..8).map(|_| unsafe { alloc.alloc(layout) }));
+            for ptr in &ptrs[1..] {
+                unsafe { alloc.dealloc(*ptr, layout) }
             }
-            assert_eq!(new_ptr, ptr);
-            layout = Layout::from_size_align(layout.size() * 2, 1).unwrap();
+            // down to the headroom after freeing, unless it is `profile-tiny`
+            let len = alloc.acquire_space().len();
+            let released = alloc.trim(4 << 10);
+            assert_eq!(alloc.acquire_space().len(), len - released);
+            let stats = alloc.stats();
+            assert!(stats.free_bytes <= (4 << 10) + 2 * Chunk::MIN_SIZE);
+            assert_eq!(alloc.check(), Ok(()));
+            assert_eq!(alloc.trim(4 << 10), 0);
+            alloc.trim(0);
+            assert_eq!(alloc.stats().free_bytes, Chunk::MIN_SIZE);
+            assert_eq!(alloc.check(), Ok(()));
+
+            unsafe { alloc.dealloc(ptrs[0], layout) }
+            alloc.trim(0);
+            assert_eq!(alloc.check(), Ok(()));
+            assert!(!unsafe { alloc.alloc(layout) }.is_null());
         }
+        let data = &mut *vec![0; 64 << 10];
+        assert_eq!(Allocator::new(Fixed::from(data)).trim(0), 0);
     }
 
     #[test]
-    fn realloc_copied() {
-        let data = &mut *vec![0; 4 << 10];
-        let alloc = Allocator::new(Fixed::from(data));
-        let layout = Layout::from_size_align(8, 1).unwrap();
-        let ptr = unsafe { alloc.alloc(layout) };
-        unsafe { slice::from_raw_parts_mut(ptr, 8) }
-            .copy_from_slice(&u64::to_ne_bytes(0x1122334455667788));
-        unsafe { alloc.alloc(layout) };
-        let new_ptr = unsafe { alloc.realloc(ptr, layout, 32) };
-        assert_eq!(
-            unsafe { slice::from_raw_parts_mut(new_ptr, 8) },
-            &u64::to_ne_bytes(0x1122334455667788)
-        );
+    fn growth_sizing() {
+        for top_policy in [TopPolicy::KeepWilderness, TopPolicy::UseWilderness] {
+            let config = Config {
+                top_policy,
+                ..Default::default()
+            };
+            let data = &mut *vec![0; 4 << 20];
+            let alloc = Allocator::with_config(Reserved(data, 4 << 10, false), config);
+            let layout = Layout::from_size_align(1 << 20, 8).unwrap();
+            assert!(!unsafe { alloc.alloc(layout) }.is_null());
+            // not doubling into 2 MB
+            assert!(alloc.acquire_space().len() <= (1 << 20) + (8 << 10));
+
+            // ramp up to 1 MB in use
+            let data = &mut *vec![0; 4 << 20];
+            let alloc = Allocator::with_config(Reserved(data, 4 << 10, false), config);
+            let layout = Layout::from_size_align(500, 8).unwrap();
+            let mut len = alloc.acquire_space().len();
+            let mut growth_count = 0;
+            for _ in 0..2000 {
+                assert!(!unsafe { alloc.alloc(layout) }.is_null());
+                let new_len = alloc.acquire_space().len();
+                if new_len != len {
+                    growth_count += 1;
+                    len = new_len;
+                }
+            }
+            assert_eq!(alloc.check(), Ok(()));
+            let allocated_bytes = alloc.stats().allocated_bytes;
+            if cfg!(feature = "profile-tiny") {
+                assert!(len <= allocated_bytes + (8 << 10));
+            } else if cfg!(feature = "profile-fast") {
+                assert!(growth_count <= 12, "{growth_count} times");
+                assert!(len <= 3 * allocated_bytes);
+            } else {
+                assert!(growth_count <= 16, "{growth_count} times");
+                assert!(len <= 2 * allocated_bytes);
+            }
+        }
     }
 
     // #[test]
@@ -938,7 +5896,7 @@ mod fuzz_failures {
 
     use crate::{
         fuzz::Method::{self, *},
-        space::Fixed,
+        space::{Fixed, SystemBuffer},
     };
 
     use super::*;
@@ -1069,13 +6027,32 @@ mod fuzz_failures {
         );
     }
 
+    #[test]
+    fn padded_sequences() {
+        for methods in Method::padded_sequences() {
+            let alloc = Allocator::new(SystemBuffer::new(4 << 10, 4 << 10));
+            Method::run_fuzz(methods.into_iter(), alloc);
+        }
+    }
+
+    #[test]
+    fn padded_dealloc() {
+        let alloc = Allocator::new(SystemBuffer::new(4 << 10, 4 << 10));
+        let chunks = std::vec::Vec::from_iter(alloc.chunks());
+        for log_align in 4..11 {
+            let layout = Layout::from_size_align(24, 1 << log_align).unwrap();
+            let ptr = unsafe { alloc.alloc(layout) };
+            let info = alloc.find_chunk(ptr).unwrap();
+            // the padding indicator is in place of the chunk metadata
+            assert!(ptr as usize - info.addr as usize >= 8);
+            unsafe { alloc.dealloc(ptr, layout) }
+            assert_eq!(std::vec::Vec::from_iter(alloc.chunks()), chunks);
+        }
+    }
+
     #[test]
     fn test6() {
-        let layout = Layout::from_size_align(4 << 10, 4 << 10).unwrap();
-        let data = unsafe { System.alloc(layout) };
-        let alloc = Allocator::new(Fixed::from(unsafe {
-            std::slice::from_raw_parts_mut(data, 4 << 10)
-        }));
+        let alloc = Allocator::new(SystemBuffer::new(4 << 10, 4 << 10));
         Method::run_fuzz(
             [
            // ... fill here with any found aligned allocation bug
@@ -1083,6 +6060,39 @@ mod fuzz_failures {
             .into_iter(),
             alloc,
         );
-        unsafe { System.dealloc(data, layout) }
+    }
+
+    #[test]
+    fn huge_sizes() {
+        let huge = isize::MAX as usize - 8;
+        let methods = [
+            Alloc {
+                size: huge,
+                align: 1,
+            },
+            Alloc { size: 8, align: 1 },
+            Alloc {
+                size: huge - 2048,
+                align: 2048,
+            },
+            Realloc {
+                index: 0,
+                new_size: huge,
+            },
+            Realloc {
+                index: 0,
+                new_size: isize::MAX as usize,
+            },
+            Dealloc { index: 0 },
+        ];
+        let data = &mut *vec![0; 4 << 10];
+        Method::run_fuzz_checked(&methods, Allocator::new(Fixed::from(data)));
+        let data = &mut *vec![0; 64 << 10];
+        let config = Config {
+            top_policy: TopPolicy::UseWilderness,
+            ..Default::default()
+        };
+        Method::run_fuzz_checked(&methods, Allocator::with_config(Fixed::from(data), config));
+        Method::run_fuzz(methods.into_iter(), System);
     }
 }
@@ -0,0 +1,57 @@
+use crate::{linked::Allocator, space::Segments, Space};
+
+// whether a pointer is handed out by an allocator, so combinators can route it back
+pub trait Owns {
+    fn owns(&self, ptr: *const u8) -> bool;
+}
+
+impl<A> Owns for &A
+where
+    A: Owns + ?Sized,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        A::owns(self, ptr)
+    }
+}
+
+impl<S, R> Owns for Allocator<S, R>
+where
+    S: Space,
+    R: lock_api::RawMutex,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        self.acquire_space().as_ptr_range().contains(&ptr)
+    }
+}
+
+impl<S, R, const N: usize> Owns for Allocator<Segments<S, N>, R>
+where
+    S: Space,
+    R: lock_api::RawMutex,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        self.acquire_segments().position(ptr).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+    use std::vec;
+
+    use crate::space::Fixed;
+
+    use super::*;
+
+    #[test]
+    fn owns() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let ptr = unsafe { alloc.alloc(Layout::from_size_align(16, 1).unwrap()) };
+        assert!(alloc.owns(ptr));
+        // through a reference, as combinators borrowing their inner allocators do
+        let by_ref = |alloc: &dyn Owns| Owns::owns(&alloc, ptr);
+        assert!(by_ref(&alloc));
+        assert!(!alloc.owns(&0u8));
+    }
+}
@@ -0,0 +1,48 @@
+// `use simpile::prelude::*;` for the usual heap, and names of the common ones without generics, so
+// a global allocator reads e.g.
+// #[global_allocator]
+// static GLOBAL: MmapHeap = MmapHeap::lazy(Mmap::new());
+// a heap laid out on first use is the `lazy` of any of them
+
+#[cfg(feature = "switchable")]
+pub use crate::switchable::Switchable;
+pub use crate::{
+    footprint::MemoryFootprint,
+    linked::{
+        Allocator, Bins, CheckError, Config, ForeignPolicy, NewError, OpCounts, Placement, Stats,
+        TopPolicy, ZeroSize,
+    },
+    space::{Fixed, Mmap, Static, StaticBuffer, StaticSpace},
+    Builder, Owns, Space,
+};
+
+pub type MmapHeap = Allocator<Mmap>;
+pub type StaticHeap<const N: usize> = Allocator<Static<N>>;
+// over a `StaticBuffer` taken once, see `static_heap!`
+pub type StaticBufferHeap = Allocator<StaticSpace>;
+pub type FixedHeap<'a> = Allocator<Fixed<'a>>;
+#[cfg(feature = "switchable")]
+pub type SwitchableMmapHeap = Switchable<MmapHeap>;
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+    use std::vec;
+
+    use super::*;
+
+    #[test]
+    fn prelude() {
+        static HEAP: StaticHeap<{ 4 << 10 }> = StaticHeap::lazy(Static::new());
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptr = unsafe { HEAP.alloc(layout) };
+        assert!(HEAP.owns(ptr));
+        unsafe { HEAP.dealloc(ptr, layout) }
+
+        let data = &mut *vec![0; 4 << 10];
+        let alloc: FixedHeap = Builder::new(Fixed::from(data)).bins(Bins::Compact).build();
+        let stats: Stats = alloc.stats();
+        assert_eq!(stats.allocation_count, 0);
+        assert_eq!(alloc.check(), Ok::<_, CheckError>(()));
+    }
+}
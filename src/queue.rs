@@ -0,0 +1,287 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    mem::size_of,
+    ops::Deref,
+    ptr::{copy_nonoverlapping, null_mut, NonNull},
+    slice,
+};
+
+use lock_api::{Mutex, RawMutex};
+
+use crate::{
+    linked::{Allocator, DefaultLock, Stats},
+    Owns, Space,
+};
+
+// a bounded queue of byte messages for many producers and a consumer, e.g. between interrupt
+// handlers or tasks and a main loop, over a heap of its own, so a burst of messages can neither
+// starve the rest of the program nor be starved by it. a message is copied into the heap when
+// pushed and freed when the popped `Message` drops. pushing fails instead of waiting once the
+// queue holds `capacity` messages or its heap is out of memory, which is where the producer backs
+// off, e.g. drops the message or retries later
+pub struct Queue<S, R = DefaultLock> {
+    heap: Allocator<S, R>,
+    list: Mutex<R, List>,
+    capacity: usize,
+}
+
+// every message is linked through a header right before its bytes, oldest first
+#[repr(C)]
+struct Header {
+    next: *mut Header,
+    len: usize,
+}
+
+struct List {
+    head: *mut Header,
+    tail: *mut Header,
+    len: usize,
+    bytes: usize,
+}
+
+// the messages are only touched with the list locked, or by the one `Message` popped from it
+unsafe impl Send for List {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushError {
+    // `capacity` messages are waiting
+    Full,
+    // the heap cannot fit the message, e.g. the waiting ones are too large together
+    OutOfMemory,
+}
+
+impl<S, R> Queue<S, R>
+where
+    R: RawMutex,
+{
+    // e.g. `Queue::new(Allocator::new(HEAP.take().unwrap()), 64)` with `static_heap!(HEAP, ..)`
+    pub const fn new(heap: Allocator<S, R>, capacity: usize) -> Self {
+        Self {
+            heap,
+            list: Mutex::new(List {
+                head: null_mut(),
+                tail: null_mut(),
+                len: 0,
+                bytes: 0,
+            }),
+            capacity,
+        }
+    }
+
+    pub fn heap(&self) -> &Allocator<S, R> {
+        &self.heap
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // the number of waiting messages
+    pub fn len(&self) -> usize {
+        self.list.lock().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // the bytes of the waiting messages, without their headers
+    pub fn bytes(&self) -> usize {
+        self.list.lock().bytes
+    }
+
+    fn layout(len: usize) -> Layout {
+        Layout::from_size_align(size_of::<Header>() + len, 8).unwrap()
+    }
+}
+
+impl<S, R> Queue<S, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    pub fn push(&self, bytes: &[u8]) -> Result<(), PushError> {
+        if self.len() >= self.capacity {
+            return Err(PushError::Full);
+        }
+        // copied before taking the list, so producers only contend on the heap meanwhile
+        let header = unsafe { self.heap.alloc(Self::layout(bytes.len())) }.cast::<Header>();
+        if header.is_null() {
+            return Err(PushError::OutOfMemory);
+        }
+        unsafe {
+            header.write(Header {
+                next: null_mut(),
+                len: bytes.len(),
+            });
+            copy_nonoverlapping(bytes.as_ptr(), header.add(1).cast(), bytes.len())
+        }
+
+        let mut list = self.list.lock();
+        // another producer took the last place while copying
+        if list.len >= self.capacity {
+            drop(list);
+            unsafe { self.free(header) }
+            return Err(PushError::Full);
+        }
+        if list.tail.is_null() {
+            list.head = header
+        } else {
+            unsafe { (*list.tail).next = header }
+        }
+        list.tail = header;
+        list.len += 1;
+        list.bytes += bytes.len();
+        Ok(())
+    }
+
+    // the oldest message, `None` if empty
+    pub fn pop(&self) -> Option<Message<'_, S, R>> {
+        let mut list = self.list.lock();
+        let header = NonNull::new(list.head)?;
+        list.head = unsafe { header.as_ref() }.next;
+        if list.head.is_null() {
+            list.tail = null_mut()
+        }
+        list.len -= 1;
+        list.bytes -= unsafe { header.as_ref() }.len;
+        Some(Message {
+            queue: self,
+            header,
+        })
+    }
+
+    // of the heap, with the waiting messages as its allocations
+    pub fn stats(&self) -> Stats {
+        self.heap.stats()
+    }
+
+    unsafe fn free(&self, header: *mut Header) {
+        let layout = Self::layout(unsafe { (*header).len });
+        unsafe { self.heap.dealloc(header.cast(), layout) }
+    }
+}
+
+// the bytes of a message are in the heap of the queue, so with several queues, or a queue beside
+// other heaps, a pointer into a message tells which one it belongs to
+impl<S, R> Owns for Queue<S, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        self.heap.owns(ptr)
+    }
+}
+
+// a popped message, which gives its bytes back to the heap when dropped
+pub struct Message<'a, S, R = DefaultLock>
+where
+    S: Space,
+    R: RawMutex,
+{
+    queue: &'a Queue<S, R>,
+    header: NonNull<Header>,
+}
+
+impl<S, R> Deref for Message<'_, S, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        let header = self.header.as_ptr();
+        unsafe { slice::from_raw_parts(header.add(1).cast(), (*header).len) }
+    }
+}
+
+impl<S, R> Drop for Message<'_, S, R>
+where
+    S: Space,
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        unsafe { self.queue.free(self.header.as_ptr()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, vec, vec::Vec};
+
+    use crate::{space::Fixed, static_heap};
+
+    use super::*;
+
+    #[test]
+    fn backpressure() {
+        let data = &mut *vec![0; 4 << 10];
+        let queue = Queue::new(Allocator::new(Fixed::from(data)), 4);
+        for i in 0..4u8 {
+            queue.push(&[i; 10]).unwrap();
+        }
+        assert_eq!(queue.push(&[4; 10]), Err(PushError::Full));
+        assert_eq!((queue.len(), queue.bytes()), (4, 40));
+        assert_eq!(queue.stats().allocation_count, 4);
+
+        let message = queue.pop().unwrap();
+        assert_eq!(*message, [0; 10]);
+        assert!(queue.owns(message.as_ptr()));
+        drop(message);
+        assert_eq!(queue.stats().allocation_count, 3);
+        // larger than what is left of the heap
+        assert_eq!(queue.push(&[5; 4 << 10]), Err(PushError::OutOfMemory));
+        queue.push(&[]).unwrap();
+
+        let messages = Vec::from_iter(core::iter::from_fn(|| queue.pop()));
+        assert_eq!(messages.len(), 4);
+        assert_eq!(*messages[2], [3; 10]);
+        assert!(messages[3].is_empty());
+        drop(messages);
+        assert!(queue.is_empty());
+        assert_eq!(queue.stats().allocation_count, 0);
+        assert_eq!(queue.heap().check(), Ok(()));
+    }
+
+    #[test]
+    fn producers() {
+        static_heap!(HEAP, 64 << 10);
+        let queue = Queue::new(Allocator::new(HEAP.take().unwrap()), 16);
+        let other_data = &mut *vec![0; 4 << 10];
+        let other = Allocator::new(Fixed::from(other_data));
+        thread::scope(|scope| {
+            for producer in 0..4u8 {
+                let queue = &queue;
+                scope.spawn(move || {
+                    for i in 0..200u8 {
+                        let message = [producer, i, producer ^ i];
+                        while queue.push(&message[..(i % 4) as usize]).is_err() {
+                            thread::yield_now()
+                        }
+                    }
+                });
+            }
+            let mut received = 0;
+            while received < 4 * 200 {
+                let Some(message) = queue.pop() else {
+                    thread::yield_now();
+                    continue;
+                };
+                if let [producer, i, check] = *message {
+                    assert_eq!(producer ^ i, check)
+                }
+                // routed to the queue rather than to the other heap
+                if !message.is_empty() {
+                    assert!(queue.owns(message.as_ptr()));
+                    assert!(!other.owns(message.as_ptr()));
+                }
+                received += 1
+            }
+        });
+        assert!(queue.is_empty());
+        assert_eq!(queue.stats().allocation_count, 0);
+        assert_eq!(queue.heap().check(), Ok(()));
+    }
+}
@@ -0,0 +1,179 @@
+use core::fmt::{self, Write};
+
+use spin::Mutex;
+
+use crate::{
+    linked::{Allocator, ChunkInfo, Stats},
+    Space,
+};
+
+// object safe view of a heap, so heaps over different spaces can be registered together
+pub trait Heap: Sync {
+    fn name(&self) -> Option<&str>;
+
+    fn stats(&self) -> Stats;
+
+    fn sanity_check(&self);
+
+    fn for_each_chunk(&self, f: &mut dyn FnMut(ChunkInfo));
+
+    // give free memory back to the system, return the released bytes. heaps that cannot shrink
+    // release nothing
+    fn trim(&self) -> usize {
+        0
+    }
+}
+
+impl<S, R> Heap for Allocator<S, R>
+where
+    S: Space + Send,
+    R: lock_api::RawMutex + Sync,
+{
+    fn name(&self) -> Option<&str> {
+        Allocator::name(self)
+    }
+
+    fn stats(&self) -> Stats {
+        Allocator::stats(self)
+    }
+
+    fn sanity_check(&self) {
+        Allocator::sanity_check(self)
+    }
+
+    fn for_each_chunk(&self, f: &mut dyn FnMut(ChunkInfo)) {
+        for info in self.chunks() {
+            f(info)
+        }
+    }
+
+    fn trim(&self) -> usize {
+        Allocator::trim(self, 0)
+    }
+}
+
+// fixed capacity so it works without an allocator, which may be exactly what is registered
+pub struct Registry<'a, const N: usize>(Mutex<[Option<&'a dyn Heap>; N]>);
+
+impl<'a, const N: usize> Registry<'a, N> {
+    pub const fn new() -> Self {
+        Self(Mutex::new([None; N]))
+    }
+
+    // return false if the registry is full
+    pub fn register(&self, heap: &'a dyn Heap) -> bool {
+        let mut heaps = self.0.lock();
+        if let Some(slot) = heaps.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(heap);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn unregister(&self, heap: &dyn Heap) -> bool {
+        let mut heaps = self.0.lock();
+        if let Some(slot) = heaps.iter_mut().find(|slot| {
+            slot.is_some_and(|registered| {
+                (registered as *const dyn Heap).cast::<()>() == (heap as *const dyn Heap).cast()
+            })
+        }) {
+            *slot = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn for_each(&self, mut f: impl FnMut(&dyn Heap)) {
+        for heap in self.0.lock().iter().flatten() {
+            f(*heap)
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        self.for_each(|heap| stats.merge(heap.stats()));
+        stats
+    }
+
+    pub fn sanity_check(&self) {
+        self.for_each(|heap| heap.sanity_check())
+    }
+
+    // one line per chunk, prefixed by the index of the heap in this registry
+    pub fn dump(&self, out: &mut impl Write) -> fmt::Result {
+        let mut result = Ok(());
+        for (index, heap) in self.0.lock().iter().enumerate() {
+            let Some(heap) = heap else {
+                continue;
+            };
+            result = result.and_then(|_| match heap.name() {
+                Some(name) => writeln!(out, "heap {index} ({name}): {:?}", heap.stats()),
+                None => writeln!(out, "heap {index}: {:?}", heap.stats()),
+            });
+            heap.for_each_chunk(&mut |info| {
+                result = result.and_then(|_| {
+                    writeln!(
+                        out,
+                        "  {:?} {} {}",
+                        info.addr,
+                        info.size,
+                        if info.in_use { "in_use" } else { "free" }
+                    )
+                })
+            });
+        }
+        result
+    }
+}
+
+impl<const N: usize> Default for Registry<'_, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+    use std::{string::String, vec};
+
+    use crate::space::Fixed;
+
+    use super::*;
+
+    #[test]
+    fn aggregate() {
+        let data1 = &mut *vec![0; 4 << 10];
+        let data2 = &mut *vec![0; 8 << 10];
+        let alloc1 = Allocator::new(Fixed::from(data1));
+        let alloc2 = Allocator::new_named(Fixed::from(data2), "second");
+        let registry = Registry::<4>::new();
+        assert!(registry.register(&alloc1));
+        assert!(registry.register(&alloc2));
+
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        unsafe { alloc1.alloc(layout) };
+        unsafe { alloc2.alloc(layout) };
+        unsafe { alloc2.alloc(layout) };
+        let stats = registry.stats();
+        assert_eq!(stats.allocation_count, 3);
+        assert_eq!(stats.allocated_bytes, {
+            let mut stats = alloc1.stats();
+            stats.merge(alloc2.stats());
+            stats.allocated_bytes
+        });
+        registry.sanity_check();
+
+        let mut dump = String::new();
+        registry.dump(&mut dump).unwrap();
+        assert!(dump.starts_with("heap 0:"));
+        assert!(dump.contains("heap 1 (second):"));
+        assert_eq!(dump.matches("in_use").count(), 3);
+
+        assert!(registry.unregister(&alloc1));
+        assert!(!registry.unregister(&alloc1));
+        assert_eq!(registry.stats().allocation_count, 2);
+    }
+}
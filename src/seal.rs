@@ -0,0 +1,207 @@
+use crate::{entropy::EntropySource, linked::Allocator, Space};
+
+// chacha20 as in rfc 8439, implemented here so sealing works without any dependency
+const CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+fn block(key: &[u8; 32], counter: u32, nonce: &[u8; 12]) -> [u8; 64] {
+    let word = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+    let mut input = [0; 16];
+    input[..4].copy_from_slice(&CONSTANTS);
+    for (index, bytes) in key.chunks_exact(4).enumerate() {
+        input[4 + index] = word(bytes);
+    }
+    input[12] = counter;
+    for (index, bytes) in nonce.chunks_exact(4).enumerate() {
+        input[13 + index] = word(bytes);
+    }
+
+    let mut state = input;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+    let mut output = [0; 64];
+    for (index, bytes) in output.chunks_exact_mut(4).enumerate() {
+        bytes.copy_from_slice(&state[index].wrapping_add(input[index]).to_le_bytes());
+    }
+    output
+}
+
+// xor the keystream into `data`, so applying twice restores it
+fn apply_keystream(data: &mut [u8], key: &[u8; 32], nonce: &[u8; 12]) {
+    for (counter, bytes) in data.chunks_mut(64).enumerate() {
+        let keystream = block(key, counter as u32, nonce);
+        for (byte, key_byte) in bytes.iter_mut().zip(keystream) {
+            *byte ^= key_byte
+        }
+    }
+}
+
+// the first slot of the space only uses its first byte for the initialization marker, the rest
+// stays in plain text while sealed to tell a sealed heap and to store the nonce of the seal
+const MARKER: u8 = 0x82;
+const SEALED_MARKER: u8 = 0x53;
+const PLAIN_LEN: usize = 8;
+
+fn nonce_of(space: &[u8]) -> [u8; 12] {
+    let mut nonce = [0; 12];
+    nonce[..4].copy_from_slice(&space[4..PLAIN_LEN]);
+    nonce
+}
+
+impl<S, R> Allocator<S, R>
+where
+    S: Space,
+    R: lock_api::RawMutex,
+{
+    // encrypt everything in the space except the first slot, including the bins and every chunk
+    // metadata, so nothing of the heap is readable until `unseal`. no allocator method may be
+    // called on a sealed heap other than `unseal` and `check`, which reports a marker error.
+    // return false if the heap is already sealed
+    pub fn seal(&self, key: &[u8; 32]) -> bool {
+        let mut space = self.acquire_space();
+        if space[0] != MARKER {
+            return false;
+        }
+        // a fresh nonce per seal, so two sealed images under the same key cannot be xor-ed
+        // against each other. the slot is not initialized, so it starts from whatever is there
+        let count = u32::from_le_bytes(space[4..PLAIN_LEN].try_into().unwrap()).wrapping_add(1);
+        Self::seal_space(&mut space, key, count);
+        true
+    }
+
+    // same as `seal` but with a random nonce, for heaps sealed under a key shared with other
+    // heaps, whose counters would start from the same value and repeat each other's nonces. the
+    // nonce has 32 bits, so such a key should still be rotated long before 2^16 seals. return false
+    // if the entropy source fails, in which case the heap is not sealed
+    pub fn seal_with(&self, key: &[u8; 32], entropy: &impl EntropySource) -> bool {
+        let mut space = self.acquire_space();
+        if space[0] != MARKER {
+            return false;
+        }
+        let Some(nonce) = entropy.next_u32() else {
+            return false;
+        };
+        Self::seal_space(&mut space, key, nonce);
+        true
+    }
+
+    fn seal_space(space: &mut S, key: &[u8; 32], nonce: u32) {
+        space[4..PLAIN_LEN].copy_from_slice(&nonce.to_le_bytes());
+        let nonce = nonce_of(space);
+        apply_keystream(&mut space[PLAIN_LEN..], key, &nonce);
+        space[0] = SEALED_MARKER;
+    }
+
+    // return false if the heap is not sealed, or if it is not a valid heap after decryption,
+    // e.g. the key is wrong, in which case the heap stays sealed
+    pub fn unseal(&self, key: &[u8; 32]) -> bool {
+        let mut space = self.acquire_space();
+        if space[0] != SEALED_MARKER {
+            return false;
+        }
+        let nonce = nonce_of(&space);
+        apply_keystream(&mut space[PLAIN_LEN..], key, &nonce);
+        space[0] = MARKER;
+        if Self::check_space(&mut space).is_err() {
+            apply_keystream(&mut space[PLAIN_LEN..], key, &nonce);
+            space[0] = SEALED_MARKER;
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+    use std::vec;
+
+    use crate::{linked::CheckError, space::Fixed};
+
+    use super::*;
+
+    #[test]
+    fn chacha20_block() {
+        // rfc 8439 section 2.3.2
+        let key = core::array::from_fn(|index| index as u8);
+        let nonce = [0, 0, 0, 0x09, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        let output = block(&key, 1, &nonce);
+        assert_eq!(
+            output[..16],
+            [
+                0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20,
+                0x71, 0xc4
+            ]
+        );
+        assert_eq!(output[60..], [0xa2, 0x50, 0x3c, 0x4e]);
+    }
+
+    #[test]
+    fn seal_unseal() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(32, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        unsafe { ptr.write_bytes(0x11, layout.size()) };
+        let key = [0x42; 32];
+
+        assert!(alloc.seal(&key));
+        assert!(!alloc.seal(&key));
+        assert_eq!(alloc.check(), Err(CheckError::Marker));
+        let sealed = unsafe { core::slice::from_raw_parts(ptr, layout.size()) };
+        assert!(sealed.iter().any(|byte| *byte != 0x11));
+
+        assert!(!alloc.unseal(&[0x24; 32]));
+        assert_eq!(alloc.check(), Err(CheckError::Marker));
+        assert!(alloc.unseal(&key));
+        assert!(!alloc.unseal(&key));
+        assert_eq!(alloc.check(), Ok(()));
+        let unsealed = unsafe { core::slice::from_raw_parts(ptr, layout.size()) };
+        assert!(unsealed.iter().all(|byte| *byte == 0x11));
+        unsafe { alloc.dealloc(ptr, layout) }
+        alloc.sanity_check();
+    }
+
+    struct Counting(core::cell::Cell<u8>);
+
+    impl EntropySource for Counting {
+        fn fill(&self, bytes: &mut [u8]) -> bool {
+            for byte in bytes {
+                self.0.set(self.0.get() + 1);
+                *byte = self.0.get()
+            }
+            true
+        }
+    }
+
+    #[test]
+    fn seal_with_entropy() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let key = [0x42; 32];
+        let entropy = Counting(Default::default());
+        assert!(alloc.seal_with(&key, &entropy));
+        assert_eq!(alloc.acquire_space()[4..PLAIN_LEN], [1, 2, 3, 4]);
+        assert!(!alloc.seal_with(&key, &entropy));
+        assert!(alloc.unseal(&key));
+        assert_eq!(alloc.check(), Ok(()));
+    }
+}
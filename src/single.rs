@@ -0,0 +1,136 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::Cell,
+    ops::Deref,
+};
+
+use lock_api::{GuardNoSend, RawMutex};
+
+use crate::{linked, Owns};
+
+// a heap that is not `Sync`, for wasm and single core targets without interrupts allocating, so
+// taking the heap is a flag instead of an atomic. the chunk logic is the one of `linked`, e.g.
+// `Allocator::try_with_lock(space, config)` or `Allocator::lazy_with_lock(space)`
+pub type Allocator<S> = linked::Allocator<S, RawCell>;
+
+// taking the lock again before releasing it can only be reentrance, e.g. allocating while walking
+// the heap, which panics instead of spinning forever
+pub struct RawCell(Cell<bool>);
+
+unsafe impl RawMutex for RawCell {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self(Cell::new(false));
+
+    type GuardMarker = GuardNoSend;
+
+    fn lock(&self) {
+        assert!(self.try_lock(), "heap is already taken on this thread")
+    }
+
+    fn try_lock(&self) -> bool {
+        !self.0.replace(true)
+    }
+
+    unsafe fn unlock(&self) {
+        self.0.set(false)
+    }
+
+    fn is_locked(&self) -> bool {
+        self.0.get()
+    }
+}
+
+// shares an allocator that is not `Sync` so it can be the global allocator, or any other `static`
+pub struct AssumeSingleThreaded<A>(A);
+
+// safety: see `new`
+unsafe impl<A> Sync for AssumeSingleThreaded<A> {}
+
+impl<A> AssumeSingleThreaded<A> {
+    // safety: `alloc` is never used from more than one thread at the same time, e.g. the program
+    // does not spawn threads, and interrupt handlers do not allocate
+    #[allow(clippy::missing_safety_doc)]
+    pub const unsafe fn new(alloc: A) -> Self {
+        Self(alloc)
+    }
+}
+
+impl<A> Deref for AssumeSingleThreaded<A> {
+    type Target = A;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<A> Owns for AssumeSingleThreaded<A>
+where
+    A: Owns,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        self.0.owns(ptr)
+    }
+}
+
+unsafe impl<A> GlobalAlloc for AssumeSingleThreaded<A>
+where
+    A: GlobalAlloc,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { self.0.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        unsafe { self.0.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.0.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe { self.0.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic::{catch_unwind, AssertUnwindSafe},
+        vec,
+    };
+
+    use crate::space::{Fixed, Static};
+
+    use super::*;
+
+    #[test]
+    fn single_threaded() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::try_with_lock(Fixed::from(data), Default::default()).unwrap();
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert!(alloc.owns(ptr));
+        let ptr = unsafe { alloc.realloc(ptr, layout, 300) };
+        unsafe { alloc.dealloc(ptr, Layout::from_size_align(300, 8).unwrap()) }
+        assert_eq!(alloc.check(), Ok(()));
+
+        // reentrance
+        let chunks = alloc.chunks();
+        let result = catch_unwind(AssertUnwindSafe(|| unsafe { alloc.alloc(layout) }));
+        assert!(result.is_err());
+        drop(chunks);
+        assert_eq!(alloc.stats().allocation_count, 0);
+    }
+
+    #[test]
+    fn assume_single_threaded() {
+        static GLOBAL: AssumeSingleThreaded<Allocator<Static<{ 4 << 10 }>>> =
+            unsafe { AssumeSingleThreaded::new(Allocator::lazy_with_lock(Static::new())) };
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptr = unsafe { GLOBAL.alloc(layout) };
+        assert!(GLOBAL.owns(ptr));
+        unsafe { GLOBAL.dealloc(ptr, layout) }
+        assert_eq!(GLOBAL.check(), Ok(()));
+    }
+}
@@ -0,0 +1,269 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    array,
+    ptr::{copy_nonoverlapping, null_mut},
+};
+
+use lock_api::{Mutex, RawMutex};
+
+use crate::{
+    linked::{self, DefaultLock},
+    Owns, Space,
+};
+
+const GRANULE: usize = 16;
+pub const MAX_SIZE: usize = 512;
+const CLASSES: usize = MAX_SIZE / GRANULE;
+
+// a front end for allocation heavy programs, which serves allocations up to `MAX_SIZE` bytes from
+// a free list per size class of 16 bytes, taking chunks from the heap `N` at once and giving back
+// `N` of them when a class caches more than `2 * N`, so most operations neither take the heap nor
+// search its bins. the cached chunks stay allocated in the heap, see `flush`. the larger or more
+// aligned allocations go to the heap directly. the foreign policy of the heap does not apply to
+// the cached classes, so every freed pointer must be from this allocator
+pub struct Allocator<S, R = DefaultLock, const N: usize = 32> {
+    heap: linked::Allocator<S, R>,
+    classes: Mutex<R, [Class; CLASSES]>,
+}
+
+// a stack of free chunks linked through their first word
+#[derive(Clone, Copy)]
+struct Class {
+    head: *mut u8,
+    len: usize,
+}
+
+// the chunks are only touched with the classes locked
+unsafe impl Send for Class {}
+
+impl Class {
+    const EMPTY: Self = Self {
+        head: null_mut(),
+        len: 0,
+    };
+
+    unsafe fn push(&mut self, ptr: *mut u8) {
+        unsafe { ptr.cast::<*mut u8>().write(self.head) }
+        self.head = ptr;
+        self.len += 1
+    }
+
+    // null if empty
+    unsafe fn pop(&mut self) -> *mut u8 {
+        let ptr = self.head;
+        if !ptr.is_null() {
+            self.head = unsafe { ptr.cast::<*mut u8>().read() };
+            self.len -= 1
+        }
+        ptr
+    }
+}
+
+fn class_of(layout: Layout) -> Option<usize> {
+    (layout.size() <= MAX_SIZE && layout.align() <= GRANULE)
+        .then(|| layout.size().saturating_sub(1) / GRANULE)
+}
+
+fn class_layout(index: usize) -> Layout {
+    Layout::from_size_align((index + 1) * GRANULE, GRANULE).unwrap()
+}
+
+impl<S, R, const N: usize> Allocator<S, R, N>
+where
+    R: RawMutex,
+{
+    // e.g. `static GLOBAL: small::Allocator<Mmap> = small::Allocator::new(Allocator::lazy(..))`
+    pub const fn new(heap: linked::Allocator<S, R>) -> Self {
+        assert!(N != 0);
+        Self {
+            heap,
+            classes: Mutex::new([Class::EMPTY; CLASSES]),
+        }
+    }
+
+    pub fn heap(&self) -> &linked::Allocator<S, R> {
+        &self.heap
+    }
+
+    // the number of chunks cached in the classes
+    pub fn cached_count(&self) -> usize {
+        self.classes.lock().iter().map(|class| class.len).sum()
+    }
+}
+
+impl<S, R, const N: usize> Allocator<S, R, N>
+where
+    S: Space,
+    R: RawMutex,
+{
+    // give every cached chunk back to the heap, e.g. before checking it for leaks or trimming it
+    pub fn flush(&self) {
+        let mut classes = self.classes.lock();
+        for (index, class) in classes.iter_mut().enumerate() {
+            while class.len != 0 {
+                let ptrs = array::from_fn::<_, N, _>(|_| unsafe { class.pop() });
+                let count = ptrs.iter().take_while(|ptr| !ptr.is_null()).count();
+                unsafe { self.heap.dealloc_bulk(&ptrs[..count], class_layout(index)) }
+            }
+        }
+    }
+}
+
+impl<S, R, const N: usize> Owns for Allocator<S, R, N>
+where
+    S: Space,
+    R: RawMutex,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        self.heap.owns(ptr)
+    }
+}
+
+unsafe impl<S, R, const N: usize> GlobalAlloc for Allocator<S, R, N>
+where
+    S: Space,
+    R: RawMutex,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let Some(index) = class_of(layout) else {
+            return unsafe { self.heap.alloc(layout) };
+        };
+        let mut classes = self.classes.lock();
+        let class = &mut classes[index];
+        if class.len == 0 {
+            let mut ptrs = [null_mut(); N];
+            let count = unsafe { self.heap.alloc_bulk(class_layout(index), &mut ptrs) };
+            for ptr in &ptrs[..count] {
+                unsafe { class.push(*ptr) }
+            }
+        }
+        unsafe { class.pop() }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let Some(index) = class_of(layout) else {
+            return unsafe { self.heap.dealloc(ptr, layout) };
+        };
+        let mut classes = self.classes.lock();
+        let class = &mut classes[index];
+        unsafe { class.push(ptr) }
+        if class.len > 2 * N {
+            let ptrs = array::from_fn::<_, N, _>(|_| unsafe { class.pop() });
+            unsafe { self.heap.dealloc_bulk(&ptrs, class_layout(index)) }
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align()) else {
+            return null_mut();
+        };
+        match (class_of(layout), class_of(new_layout)) {
+            (None, None) => return unsafe { self.heap.realloc(ptr, layout, new_size) },
+            (Some(index), Some(new_index)) if index == new_index => return ptr,
+            _ => {}
+        }
+        let new_ptr = unsafe { self.alloc(new_layout) };
+        if !new_ptr.is_null() {
+            unsafe {
+                copy_nonoverlapping(ptr, new_ptr, usize::min(layout.size(), new_size));
+                self.dealloc(ptr, layout)
+            }
+        }
+        new_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, vec, vec::Vec};
+
+    use crate::space::{Fixed, SystemBuffer};
+
+    use super::*;
+
+    #[test]
+    fn classes() {
+        let data = &mut *vec![0; 64 << 10];
+        let alloc = Allocator::<_, _, 8>::new(linked::Allocator::new(Fixed::from(data)));
+        let layout = Layout::from_size_align(24, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert_eq!(ptr as usize % GRANULE, 0);
+        // refilled in bulk
+        assert_eq!(alloc.heap().stats().allocation_count, 8);
+        assert_eq!(alloc.cached_count(), 7);
+        // the same class
+        assert_eq!(unsafe { alloc.realloc(ptr, layout, 32) }, ptr);
+        let large = Layout::from_size_align(MAX_SIZE + 1, 8).unwrap();
+        let large_ptr = unsafe { alloc.alloc(large) };
+        assert_eq!(alloc.heap().stats().allocation_count, 9);
+        let ptr = unsafe { alloc.realloc(ptr, Layout::from_size_align(32, 8).unwrap(), 100) };
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        unsafe {
+            alloc.dealloc(ptr, layout);
+            alloc.dealloc(large_ptr, large)
+        }
+        assert_eq!(alloc.cached_count(), 16);
+
+        // given back in bulk
+        let ptrs = Vec::from_iter((0..30).map(|_| unsafe { alloc.alloc(layout) }));
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        for ptr in ptrs {
+            unsafe { alloc.dealloc(ptr, layout) }
+        }
+        assert!(alloc.cached_count() <= 8 + 2 * 8);
+        alloc.flush();
+        assert_eq!(alloc.cached_count(), 0);
+        assert_eq!(alloc.heap().stats().allocation_count, 0);
+        assert_eq!(alloc.heap().check(), Ok(()));
+    }
+
+    #[test]
+    fn out_of_memory() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::<_, _, 64>::new(linked::Allocator::new(Fixed::from(data)));
+        let layout = Layout::from_size_align(256, 8).unwrap();
+        let ptrs = Vec::from_iter((0..64).map(|_| unsafe { alloc.alloc(layout) }));
+        let count = ptrs.iter().take_while(|ptr| !ptr.is_null()).count();
+        assert!((1..64).contains(&count));
+        assert!(ptrs[count..].iter().all(|ptr| ptr.is_null()));
+        for ptr in &ptrs[..count] {
+            unsafe { alloc.dealloc(*ptr, layout) }
+        }
+        alloc.flush();
+        assert_eq!(alloc.heap().stats().allocation_count, 0);
+    }
+
+    #[test]
+    fn concurrent() {
+        let heap = linked::Allocator::new(SystemBuffer::new(1 << 20, 4096));
+        let alloc = Allocator::<_, _, 8>::new(heap);
+        thread::scope(|scope| {
+            for thread in 0..4 {
+                let alloc = &alloc;
+                scope.spawn(move || {
+                    let mut ptrs = Vec::new();
+                    for round in 0..300usize {
+                        let size = (round * 7 + thread * 13) % (MAX_SIZE + 100) + 1;
+                        let layout = Layout::from_size_align(size, 8).unwrap();
+                        let ptr = unsafe { alloc.alloc(layout) };
+                        assert!(!ptr.is_null());
+                        unsafe { ptr.write_bytes(thread as u8, size) };
+                        ptrs.push((ptr, layout));
+                        if round % 3 == 0 {
+                            let (ptr, layout) = ptrs.swap_remove(round % ptrs.len());
+                            let bytes = unsafe { core::slice::from_raw_parts(ptr, layout.size()) };
+                            assert!(bytes.iter().all(|byte| *byte == thread as u8));
+                            unsafe { alloc.dealloc(ptr, layout) }
+                        }
+                    }
+                    for (ptr, layout) in ptrs {
+                        unsafe { alloc.dealloc(ptr, layout) }
+                    }
+                });
+            }
+        });
+        alloc.flush();
+        assert_eq!(alloc.heap().stats().allocation_count, 0);
+        assert_eq!(alloc.heap().check(), Ok(()));
+    }
+}
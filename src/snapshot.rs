@@ -0,0 +1,170 @@
+use std::{collections::BTreeMap, vec::Vec};
+
+use crate::{
+    linked::{Allocator, ChunkInfo},
+    Space,
+};
+
+// in-use chunks of a heap at some moment, sorted by address
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot(Vec<ChunkInfo>);
+
+impl Snapshot {
+    pub fn new(walk: impl Iterator<Item = ChunkInfo>) -> Self {
+        Self(Vec::from_iter(walk.filter(|info| info.in_use)))
+    }
+
+    pub fn chunks(&self) -> &[ChunkInfo] {
+        &self.0
+    }
+
+    // allocations are matched by id if they have one, otherwise by address
+    fn key(info: &ChunkInfo) -> u64 {
+        #[cfg(feature = "alloc-id")]
+        if let Some(id) = info.id {
+            return id;
+        }
+        info.addr as u64
+    }
+
+    pub fn diff(&self, later: &Self) -> Diff {
+        let earlier = BTreeMap::from_iter(self.0.iter().map(|info| (Self::key(info), *info)));
+        let mut diff = Diff::default();
+        for info in &later.0 {
+            match earlier.get(&Self::key(info)) {
+                None => diff.added.push(*info),
+                Some(earlier) if earlier.size != info.size || earlier.addr != info.addr => {
+                    diff.resized.push((*earlier, *info))
+                }
+                Some(_) => {}
+            }
+        }
+        let later = BTreeMap::from_iter(later.0.iter().map(|info| (Self::key(info), ())));
+        diff.removed.extend(
+            self.0
+                .iter()
+                .filter(|info| !later.contains_key(&Self::key(info))),
+        );
+        diff
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Diff {
+    pub added: Vec<ChunkInfo>,
+    pub removed: Vec<ChunkInfo>,
+    // (earlier, later)
+    pub resized: Vec<(ChunkInfo, ChunkInfo)>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeClassDiff {
+    pub added: usize,
+    pub added_bytes: usize,
+    pub removed: usize,
+    pub removed_bytes: usize,
+    pub resized: usize,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.resized.is_empty()
+    }
+
+    // keyed by chunk size rounded up to power of two, resized allocations are counted in the size
+    // class of the later chunk
+    pub fn by_size_class(&self) -> BTreeMap<usize, SizeClassDiff> {
+        let mut classes = BTreeMap::<_, SizeClassDiff>::new();
+        for info in &self.added {
+            let class = classes.entry(info.size.next_power_of_two()).or_default();
+            class.added += 1;
+            class.added_bytes += info.size;
+        }
+        for info in &self.removed {
+            let class = classes.entry(info.size.next_power_of_two()).or_default();
+            class.removed += 1;
+            class.removed_bytes += info.size;
+        }
+        for (_, info) in &self.resized {
+            classes
+                .entry(info.size.next_power_of_two())
+                .or_default()
+                .resized += 1;
+        }
+        classes
+    }
+}
+
+impl<S, R> Allocator<S, R>
+where
+    S: Space,
+    R: lock_api::RawMutex,
+{
+    pub fn snapshot(&self) -> Snapshot {
+        // the heap is locked during walking, so reserve outside of the walk in case this allocator
+        // is the global one, and retry if the heap grows in between
+        let mut chunks = Vec::new();
+        loop {
+            chunks.reserve(self.chunks().count() + 16);
+            chunks.clear();
+            let mut walk = self.chunks();
+            for info in walk.by_ref() {
+                if chunks.len() == chunks.capacity() {
+                    break;
+                }
+                chunks.push(info);
+            }
+            if walk.next().is_none() {
+                break Snapshot::new(chunks.into_iter());
+            }
+        }
+    }
+}
+
+// the resized chunk is reallocated in place, which `minimal` never does
+#[cfg(all(test, not(feature = "minimal")))]
+mod tests {
+    use std::vec;
+
+    use core::alloc::{GlobalAlloc, Layout};
+
+    use crate::{
+        linked::{Config, TopPolicy},
+        space::Fixed,
+    };
+
+    use super::*;
+
+    #[test]
+    fn diff_snapshots() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                top_policy: TopPolicy::KeepWilderness,
+                ..Default::default()
+            },
+        );
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let kept = unsafe { alloc.alloc(layout) };
+        let removed = unsafe { alloc.alloc(layout) };
+        let resized = unsafe { alloc.alloc(layout) };
+        let before = alloc.snapshot();
+        assert_eq!(before.chunks().len(), 3);
+        assert!(before.diff(&alloc.snapshot()).is_empty());
+
+        unsafe { alloc.dealloc(removed, layout) }
+        let resized = unsafe { alloc.realloc(resized, layout, 64) };
+        let added = unsafe { alloc.alloc(Layout::from_size_align(200, 1).unwrap()) };
+        let diff = before.diff(&alloc.snapshot());
+        assert_eq!(diff.removed.len(), 1);
+        assert!(diff.removed[0].contains(removed));
+        assert!(diff.added.iter().any(|info| info.contains(added)));
+        assert!(!diff.added.iter().any(|info| info.contains(kept)));
+        assert_eq!(diff.resized.len(), 1);
+        assert!(diff.resized[0].1.contains(resized));
+        let classes = diff.by_size_class();
+        assert_eq!(classes.values().map(|class| class.added).sum::<usize>(), 1);
+        assert_eq!(classes[&256].added, 1);
+    }
+}
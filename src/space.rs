@@ -1,8 +1,12 @@
 use core::{
-    ops::{Deref, DerefMut},
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut, Range},
     ptr::null_mut,
     slice,
+    sync::atomic::{AtomicBool, Ordering::SeqCst},
 };
+#[cfg(feature = "nix")]
+use core::{ffi::c_void, ptr::copy_nonoverlapping};
 
 pub trait Space
 where
@@ -10,6 +14,16 @@ where
 {
     fn set_size(&mut self, bytes: usize) -> bool;
 
+    // `set_size` telling why it fails, for a space that can fail in more ways than running out.
+    // a failed resize leaves the space as it is
+    fn try_set_size(&mut self, bytes: usize) -> Result<(), SizeError> {
+        if self.set_size(bytes) {
+            Ok(())
+        } else {
+            Err(SizeError::Exhausted)
+        }
+    }
+
     fn grow(&mut self, min_bytes: usize) -> bool {
         // we can do saturated multiply here but probably cannot grow that much
         if let Some(size) = self.len().checked_mul(usize::max(
@@ -21,21 +35,306 @@ where
             false
         }
     }
+
+    // growing in place in two steps, so the slow part, e.g. the syscall mapping more memory, does
+    // not block other users of the heap. `reservation` is taken with exclusive access to the space,
+    // then `reserve` runs without it, serialized with other `reserve` of the same space, and must
+    // not change anything reachable through the space. at last `set_reserved` and `set_size` to the
+    // reserved bytes run with exclusive access again, and the latter is expected to be cheap
+    // `None` if the space only grows with `set_size`
+    fn reservation(&self) -> Option<Reservation> {
+        None
+    }
+
+    // `reservation` must come from this kind of space, and the space must be still alive
+    #[allow(clippy::missing_safety_doc)]
+    unsafe fn reserve(reservation: Reservation, bytes: usize) -> bool
+    where
+        Self: Sized,
+    {
+        let _ = (reservation, bytes);
+        false
+    }
+
+    fn set_reserved(&mut self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    // the bytes of the space backed by memory right now, in whole pages, `None` if the space cannot
+    // tell
+    fn resident_bytes(&self) -> Option<usize> {
+        None
+    }
+
+    // whether `set_size` below the current size may succeed, so the heap does not prepare for
+    // shrinking a space that never shrinks. a shrinking space must not move
+    fn can_shrink(&self) -> bool {
+        false
+    }
+
+    // whether the bytes a successful `set_size` or `set_reserved` adds are zero, e.g. fresh pages
+    // of an anonymous mapping, so the heap does not zero them again for `alloc_zeroed`. not so for
+    // a space that keeps the bytes of a shrinking and gives them back when growing again
+    fn grows_zeroed(&self) -> bool {
+        false
+    }
+
+    // give back the memory of the whole pages within `range` of the space, which keeps its length
+    // and reads as zero there afterwards, e.g. the pages in the middle of a large free chunk. the
+    // pages given back, `None` if none is, and always if the space cannot
+    fn discard(&mut self, range: Range<usize>) -> Option<Range<usize>> {
+        let _ = range;
+        None
+    }
+
+    // the system calls made by the space so far, all zero if the space makes none
+    #[cfg(feature = "mmap-stats")]
+    fn syscall_stats(&self) -> SyscallStats {
+        SyscallStats::default()
+    }
+}
+
+// the memory region backing a space, which may be larger than the space itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reservation {
+    pub addr: *mut u8,
+    pub len: usize,
+}
+
+// why `Space::try_set_size` fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeError {
+    // no memory or address space left, or none the space can take, e.g. it is of a fixed size
+    Exhausted,
+    // the memory is there but cannot be locked, with the error number of the system, e.g. beyond
+    // `RLIMIT_MEMLOCK`. it is given back then
+    Lock(i32),
+}
+
+// successful calls only, a failed call does not change the mapping
+#[cfg(feature = "mmap-stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyscallStats {
+    pub mmap: usize,
+    pub mremap: usize,
+    pub munmap: usize,
+    pub madvise: usize,
+    pub mprotect: usize,
+    pub mlock: usize,
+    pub munlock: usize,
+    // by `mmap` and growing `mremap`, or committed by `mprotect` within a reservation
+    pub mapped_bytes: usize,
+    // by `munmap` and shrinking `mremap`, or given back by `mmap` within a reservation
+    pub unmapped_bytes: usize,
+    pub advised_bytes: usize,
+}
+
+#[cfg(feature = "mmap-stats")]
+impl SyscallStats {
+    pub fn merge(&mut self, other: Self) {
+        self.mmap += other.mmap;
+        self.mremap += other.mremap;
+        self.munmap += other.munmap;
+        self.madvise += other.madvise;
+        self.mprotect += other.mprotect;
+        self.mlock += other.mlock;
+        self.munlock += other.munlock;
+        self.mapped_bytes += other.mapped_bytes;
+        self.unmapped_bytes += other.unmapped_bytes;
+        self.advised_bytes += other.advised_bytes;
+    }
+
+    fn remap(&mut self, from: usize, to: usize) {
+        self.mremap += 1;
+        if to > from {
+            self.mapped_bytes += to - from
+        } else {
+            self.unmapped_bytes += from - to
+        }
+    }
+}
+
+// the huge pages `Mmap` may map, see `Mmap::set_huge_pages`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugePageSize {
+    Size2M,
+    Size1G,
+}
+
+impl HugePageSize {
+    pub const fn bytes(self) -> usize {
+        match self {
+            Self::Size2M => 2 << 20,
+            Self::Size1G => 1 << 30,
+        }
+    }
 }
 
 pub struct Mmap {
     addr: *mut u8,
     len: usize,
+    // mapped bytes, larger than `len` after reserving and before resizing
+    #[cfg_attr(not(feature = "nix"), allow(dead_code))]
+    mapped: usize,
+    #[cfg_attr(not(feature = "nix"), allow(dead_code))]
+    movable: bool,
+    // the bytes of address space reserved at once, zero if the mapping grows by remapping
+    #[cfg_attr(not(feature = "nix"), allow(dead_code))]
+    reserve: usize,
+    huge_pages: Option<HugePageSize>,
+    // whether the mapping is made of huge pages, rather than only advised to be
+    hugetlb: bool,
+    lock: bool,
+    #[cfg_attr(not(feature = "nix"), allow(dead_code))]
+    private: bool,
+    #[cfg_attr(not(feature = "nix"), allow(dead_code))]
+    populate: bool,
+    #[cfg_attr(not(feature = "nix"), allow(dead_code))]
+    noreserve: bool,
+    #[cfg(feature = "mmap-stats")]
+    syscalls: SyscallStats,
+}
+
+// the flags of the mapping, e.g.
+// `static GLOBAL: Allocator<Mmap> = Allocator::lazy(Mmap::builder().private(true).build())`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MmapBuilder {
+    private: bool,
+    populate: bool,
+    noreserve: bool,
+}
+
+impl MmapBuilder {
+    // `MAP_PRIVATE` instead of `MAP_SHARED`, so a forked child gets a copy of the heap rather than
+    // writing into the one of the parent, and discarded pages are only dropped. always so for a
+    // movable or reserved mapping
+    pub const fn private(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+
+    // prefault the pages as they are mapped, so the first writes to them do not fault, at the cost
+    // of taking the memory right away. by `MAP_POPULATE`, and `MADV_POPULATE_WRITE` for the pages
+    // of later growths where the system has it. only on linux
+    pub const fn populate(mut self, populate: bool) -> Self {
+        self.populate = populate;
+        self
+    }
+
+    // `MAP_NORESERVE`, so the mapping takes no swap space ahead and the system may overcommit it
+    // even if set not to. writing to a page fails with `SIGSEGV` then if the memory is out. always
+    // so for the reservation of a reserved mapping
+    pub const fn noreserve(mut self, noreserve: bool) -> Self {
+        self.noreserve = noreserve;
+        self
+    }
+
+    pub const fn build(self) -> Mmap {
+        let mut space = Mmap::new();
+        space.private = self.private;
+        space.populate = self.populate;
+        space.noreserve = self.noreserve;
+        space
+    }
 }
 
 unsafe impl Send for Mmap {}
 unsafe impl Sync for Mmap {}
 
 impl Mmap {
+    // a shared mapping, see `MmapBuilder` for the other flags
     pub const fn new() -> Self {
         Self {
             addr: null_mut(),
             len: 0,
+            mapped: 0,
+            movable: false,
+            reserve: 0,
+            huge_pages: None,
+            hugetlb: false,
+            lock: false,
+            private: false,
+            populate: false,
+            noreserve: false,
+            #[cfg(feature = "mmap-stats")]
+            syscalls: SyscallStats {
+                mmap: 0,
+                mremap: 0,
+                munmap: 0,
+                madvise: 0,
+                mprotect: 0,
+                mlock: 0,
+                munlock: 0,
+                mapped_bytes: 0,
+                unmapped_bytes: 0,
+                advised_bytes: 0,
+            },
+        }
+    }
+
+    pub const fn builder() -> MmapBuilder {
+        MmapBuilder {
+            private: false,
+            populate: false,
+            noreserve: false,
+        }
+    }
+
+    // growing moves the mapping when it cannot be extended in place, instead of failing, and the
+    // heap with it. every allocation moves too, so it is only for heaps addressed by offsets from
+    // `Allocator::base`, e.g. a large buffer that is only reached through the heap owning it
+    pub const fn movable() -> Self {
+        let mut space = Self::new();
+        space.movable = true;
+        space
+    }
+
+    // `max_bytes` of address space is reserved on the first growth and its pages are only made
+    // accessible as the space grows into them, so the mapping never moves and growing up to
+    // `max_bytes` never fails because something else got mapped right after it. the reservation
+    // takes no memory by itself
+    pub const fn with_reserve(max_bytes: usize) -> Self {
+        assert!(max_bytes != 0);
+        let mut space = Self::new();
+        space.reserve = max_bytes;
+        space
+    }
+
+    // map huge pages of `size` from the ones the system sets aside, or advise the system to merge
+    // the pages into huge ones if there are not enough of them, for fewer TLB misses in a large
+    // heap. the mapping grows and shrinks by whole huge pages then, while the space keeps the asked
+    // length. only taken by the first mapping, and only on linux, elsewhere only the rounding is
+    pub const fn set_huge_pages(&mut self, size: HugePageSize) {
+        self.huge_pages = Some(size)
+    }
+
+    pub fn huge_pages(&self) -> Option<HugePageSize> {
+        self.huge_pages
+    }
+
+    // false if falling back to advising, or before the first mapping
+    pub fn hugetlb(&self) -> bool {
+        self.hugetlb
+    }
+
+    // lock the pages in memory as the space grows, so they are never swapped out, e.g. for keys
+    // that must not reach the disk or a heap that must not fault. growing fails with
+    // `SizeError::Lock` if the new pages cannot be locked, and the pages given back by shrinking are
+    // unlocked first. only taken by the growths after it, so it is best set before the first
+    pub const fn lock_memory(&mut self, lock: bool) {
+        self.lock = lock
+    }
+
+    pub fn locks_memory(&self) -> bool {
+        self.lock
+    }
+
+    // the length of the mapping holding `bytes`
+    #[cfg(feature = "nix")]
+    fn mapping_len(&self, bytes: usize) -> usize {
+        match self.huge_pages {
+            Some(size) => bytes.next_multiple_of(size.bytes()),
+            None => bytes,
         }
     }
 }
@@ -49,13 +348,20 @@ impl Default for Mmap {
 impl Deref for Mmap {
     type Target = [u8];
 
+    // nothing is mapped yet at a null address
     fn deref(&self) -> &Self::Target {
+        if self.addr.is_null() {
+            return &[];
+        }
         unsafe { slice::from_raw_parts(self.addr, self.len) }
     }
 }
 
 impl DerefMut for Mmap {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        if self.addr.is_null() {
+            return &mut [];
+        }
         unsafe { slice::from_raw_parts_mut(self.addr, self.len) }
     }
 }
@@ -63,10 +369,180 @@ impl DerefMut for Mmap {
 #[cfg(feature = "nix")]
 impl Space for Mmap {
     fn set_size(&mut self, bytes: usize) -> bool {
+        self.try_set_size(bytes).is_ok()
+    }
+
+    // the pages gained are locked after resizing and the ones given back unlocked before, see
+    // `lock_memory`. unlocking is undone if resizing fails, and resizing if locking does
+    fn try_set_size(&mut self, bytes: usize) -> Result<(), SizeError> {
+        use nix::{
+            libc::{sysconf, _SC_PAGESIZE},
+            sys::mman::{mlock, munlock},
+        };
+
+        let old = (self.len, self.mapped);
+        let page = usize::try_from(unsafe { sysconf(_SC_PAGESIZE) }).unwrap();
+        let kept = self.mapping_len(bytes).next_multiple_of(page);
+        let unlocked = self.lock && kept < self.mapped;
+        if unlocked {
+            let len = self.mapped - kept;
+            if unsafe { munlock(self.addr.add(kept).cast(), len) }.is_ok() {
+                #[cfg(feature = "mmap-stats")]
+                {
+                    self.syscalls.munlock += 1
+                }
+            }
+        }
+        if !self.resize(bytes) {
+            if unlocked {
+                let len = self.mapped - kept;
+                let _ = unsafe { mlock(self.addr.add(kept).cast(), len) };
+            }
+            return Err(SizeError::Exhausted);
+        }
+        if self.lock && self.mapped > old.1 {
+            let len = self.mapped - old.1;
+            if let Err(errno) = unsafe { mlock(self.addr.add(old.1).cast(), len) } {
+                // giving back the new pages, which are the ones above the old size
+                assert!(self.resize(old.0));
+                return Err(SizeError::Lock(errno as i32));
+            }
+            #[cfg(feature = "mmap-stats")]
+            {
+                self.syscalls.mlock += 1
+            }
+        }
+        Ok(())
+    }
+
+    // shrinking `mremap` unmaps the end in place
+    fn can_shrink(&self) -> bool {
+        true
+    }
+
+    // anonymous pages, and the ones past the size are mapped by growing only. not so for a shared
+    // mapping, whose object keeps the pages of a shrinking and maps them again when growing, nor
+    // for the rest of the last huge page, which is kept when shrinking
+    fn grows_zeroed(&self) -> bool {
+        self.is_private() && self.huge_pages.is_none()
+    }
+
+    // dropped from a private mapping, and removed from the object behind a shared one, which
+    // dropping alone would keep. whole huge pages if they are
+    #[cfg(target_os = "linux")]
+    fn discard(&mut self, range: Range<usize>) -> Option<Range<usize>> {
+        use nix::{
+            libc::{sysconf, _SC_PAGESIZE},
+            sys::mman::{madvise, MmapAdvise},
+        };
+
+        // locked pages stay
+        if self.lock {
+            return None;
+        }
+        let page = match self.huge_pages {
+            Some(size) if self.hugetlb => size.bytes(),
+            _ => usize::try_from(unsafe { sysconf(_SC_PAGESIZE) }).ok()?,
+        };
+        let (start, end) = (range.start.next_multiple_of(page), range.end / page * page);
+        if start >= end {
+            return None;
+        }
+        let advice = if self.is_private() {
+            MmapAdvise::MADV_DONTNEED
+        } else {
+            MmapAdvise::MADV_REMOVE
+        };
+        unsafe { madvise(self.addr.add(start).cast(), end - start, advice) }.ok()?;
+        #[cfg(feature = "mmap-stats")]
+        {
+            self.syscalls.madvise += 1;
+            self.syscalls.advised_bytes += end - start;
+        }
+        Some(start..end)
+    }
+
+    // a batch of pages at a time, so probing does not allocate
+    #[cfg(target_os = "linux")]
+    fn resident_bytes(&self) -> Option<usize> {
+        use nix::libc::{mincore, sysconf, _SC_PAGESIZE};
+
+        const BATCH: usize = 256;
+        let page = usize::try_from(unsafe { sysconf(_SC_PAGESIZE) }).ok()?;
+        let mut pages = [0; BATCH];
+        let mut resident = 0;
+        for offset in (0..self.len).step_by(page * BATCH) {
+            let len = usize::min(self.len - offset, page * BATCH);
+            if unsafe { mincore(self.addr.add(offset).cast(), len, pages.as_mut_ptr()) } != 0 {
+                return None;
+            }
+            resident += pages[..len.div_ceil(page)]
+                .iter()
+                .filter(|page| **page & 1 != 0)
+                .count()
+                * page
+        }
+        Some(resident)
+    }
+
+    // a movable mapping grows with the heap held instead, as it cannot move while released, and
+    // so does a reserved one, which only changes the protection of its pages, one of huge pages,
+    // which is remapped by whole huge pages, a locked one, whose new pages are locked by
+    // `set_size`, and any without `mremap`, whose new pages are mapped with the flags of the space
+    fn reservation(&self) -> Option<Reservation> {
+        if cfg!(not(target_os = "linux"))
+            || self.addr.is_null()
+            || self.movable
+            || self.reserve != 0
+            || self.huge_pages.is_some()
+            || self.lock
+        {
+            return None;
+        }
+        Some(Reservation {
+            addr: self.addr,
+            len: self.mapped,
+        })
+    }
+
+    // not moving, so the memory in use stays where it is
+    #[cfg(target_os = "linux")]
+    unsafe fn reserve(reservation: Reservation, bytes: usize) -> bool {
+        use nix::sys::mman::{mremap, MRemapFlags};
+
+        unsafe {
+            mremap(
+                reservation.addr as _,
+                reservation.len,
+                bytes,
+                MRemapFlags::empty(),
+                None,
+            )
+        }
+        .is_ok()
+    }
+
+    // `reserve` has no access to the space, so a successful one is counted here instead
+    fn set_reserved(&mut self, bytes: usize) {
+        #[cfg(feature = "mmap-stats")]
+        self.syscalls.remap(self.mapped, bytes);
+        self.mapped = bytes
+    }
+
+    #[cfg(feature = "mmap-stats")]
+    fn syscall_stats(&self) -> SyscallStats {
+        self.syscalls
+    }
+}
+
+#[cfg(feature = "nix")]
+impl Mmap {
+    // resizing only, without locking, see `try_set_size`
+    fn resize(&mut self, bytes: usize) -> bool {
         use core::num::NonZeroUsize;
         use nix::{
-            libc::{MAP_ANONYMOUS, MAP_SHARED, PROT_READ, PROT_WRITE},
-            sys::mman::{mmap, mremap, MRemapFlags, MapFlags, ProtFlags},
+            libc::{PROT_READ, PROT_WRITE},
+            sys::mman::{mmap, MapFlags, ProtFlags},
         };
 
         if bytes == self.len {
@@ -77,90 +553,1087 @@ impl Space for Mmap {
             self.clear();
             return true;
         };
+        if (self.len..=self.mapped).contains(&bytes.get()) {
+            self.len = bytes.get();
+            return true;
+        }
+        if self.reserve != 0 {
+            return self.commit(bytes.get());
+        }
+        let Ok(mapped) = NonZeroUsize::try_from(self.mapping_len(bytes.get())) else {
+            return false;
+        };
+        // shrinking within the last huge page
+        if mapped.get() == self.mapped {
+            self.len = bytes.get();
+            return true;
+        }
 
         let result = if self.addr.is_null() {
-            unsafe {
+            let map_flags = self.map_flags();
+            let map = |flags| unsafe {
                 mmap(
                     None,
-                    bytes,
+                    mapped,
                     ProtFlags::from_bits(PROT_READ | PROT_WRITE).unwrap(),
-                    MapFlags::from_bits(MAP_SHARED | MAP_ANONYMOUS).unwrap(),
+                    map_flags | flags,
                     -1,
                     0,
                 )
+            };
+            let hugetlb = self.hugetlb_flags();
+            let result = (!hugetlb.is_empty())
+                .then(|| map(hugetlb))
+                .filter(Result::is_ok);
+            self.hugetlb = result.is_some();
+            let result = result.unwrap_or_else(|| map(MapFlags::empty()));
+            #[cfg(feature = "mmap-stats")]
+            if result.is_ok() {
+                self.syscalls.mmap += 1;
+                self.syscalls.mapped_bytes += mapped.get();
             }
+            result
         } else {
-            unsafe {
-                mremap(
-                    self.addr as _,
-                    self.len,
-                    bytes.get(),
-                    MRemapFlags::empty(),
-                    None,
-                )
-            }
+            #[cfg(target_os = "linux")]
+            let result = unsafe { self.remap(mapped.get()) };
+            #[cfg(not(target_os = "linux"))]
+            let result = unsafe { self.map_around(mapped.get()) };
+            result
         };
         if let Ok(addr) = result {
+            let old = self.mapped;
             self.addr = addr as _;
             self.len = bytes.get();
+            self.mapped = mapped.get();
+            if !self.hugetlb && mapped.get() > old {
+                self.advise_huge_pages(old)
+            }
+            // the first mapping is populated by mapping
+            if old != 0 {
+                self.populate(old)
+            }
         }
         result.is_ok()
     }
-}
 
-#[cfg(feature = "nix")]
-impl Mmap {
+    // the mapping resized to `mapped` bytes, moved if it is movable and cannot stay
+    #[cfg(target_os = "linux")]
+    unsafe fn remap(&mut self, mapped: usize) -> nix::Result<*mut c_void> {
+        use nix::sys::mman::{mremap, MRemapFlags};
+
+        let flags = if self.movable {
+            MRemapFlags::MREMAP_MAYMOVE
+        } else {
+            MRemapFlags::empty()
+        };
+        let result = unsafe { mremap(self.addr.cast(), self.mapped, mapped, flags, None) };
+        #[cfg(feature = "mmap-stats")]
+        if result.is_ok() {
+            self.syscalls.remap(self.mapped, mapped)
+        }
+        result
+    }
+
+    // `remap` for the systems without `mremap`, e.g. macos and the bsds: the pages right after the
+    // mapping are mapped too if nothing else is there, and the ones past `mapped` unmapped. a
+    // movable mapping that cannot grow in place is copied into a new one instead
+    #[cfg_attr(all(target_os = "linux", not(test)), allow(dead_code))]
+    unsafe fn map_around(&mut self, mapped: usize) -> nix::Result<*mut c_void> {
+        use core::num::NonZeroUsize;
+        use nix::{
+            errno::Errno,
+            libc::{sysconf, _SC_PAGESIZE},
+            sys::mman::{mmap, munmap, ProtFlags},
+        };
+
+        // the system maps whole pages
+        let page = usize::try_from(unsafe { sysconf(_SC_PAGESIZE) }).unwrap();
+        let end = self.mapped.next_multiple_of(page);
+        let new_end = mapped.next_multiple_of(page);
+        let prot = ProtFlags::PROT_READ | ProtFlags::PROT_WRITE;
+        if new_end < end {
+            unsafe { munmap(self.addr.add(new_end).cast(), end - new_end) }?;
+            #[cfg(feature = "mmap-stats")]
+            {
+                self.syscalls.munmap += 1;
+                self.syscalls.unmapped_bytes += end - new_end
+            }
+        } else if new_end > end {
+            let len = NonZeroUsize::new(new_end - end).unwrap();
+            let addr = unsafe { self.addr.add(end) };
+            if !unsafe { map_at(addr, len, prot, self.map_flags(), -1, 0) } {
+                if !self.movable {
+                    return Err(Errno::ENOMEM);
+                }
+                let len = NonZeroUsize::new(new_end).unwrap();
+                let addr = unsafe { mmap(None, len, prot, self.map_flags(), -1, 0) }?;
+                unsafe {
+                    copy_nonoverlapping(self.addr, addr.cast(), self.len);
+                    munmap(self.addr.cast(), end).unwrap()
+                }
+                #[cfg(feature = "mmap-stats")]
+                {
+                    self.syscalls.mmap += 1;
+                    self.syscalls.munmap += 1;
+                    self.syscalls.mapped_bytes += new_end;
+                    self.syscalls.unmapped_bytes += end
+                }
+                return Ok(addr);
+            }
+            #[cfg(feature = "mmap-stats")]
+            {
+                self.syscalls.mmap += 1;
+                self.syscalls.mapped_bytes += len.get()
+            }
+        }
+        Ok(self.addr.cast())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn hugetlb_flags(&self) -> nix::sys::mman::MapFlags {
+        use nix::sys::mman::MapFlags;
+
+        match self.huge_pages {
+            Some(HugePageSize::Size2M) => MapFlags::MAP_HUGETLB | MapFlags::MAP_HUGE_2MB,
+            Some(HugePageSize::Size1G) => MapFlags::MAP_HUGETLB | MapFlags::MAP_HUGE_1GB,
+            None => MapFlags::empty(),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn hugetlb_flags(&self) -> nix::sys::mman::MapFlags {
+        nix::sys::mman::MapFlags::empty()
+    }
+
+    // a shared anonymous mapping is backed by an object of its first length, which remapping does
+    // not extend, so a movable one is private, and so is a reserved one, whose pages past the size
+    // are replaced by fresh ones
+    fn is_private(&self) -> bool {
+        self.private || self.movable || self.reserve != 0
+    }
+
+    // of the first mapping, besides the huge pages
+    fn map_flags(&self) -> nix::sys::mman::MapFlags {
+        use nix::sys::mman::MapFlags;
+
+        let mut flags = MapFlags::MAP_ANONYMOUS;
+        flags |= if self.is_private() {
+            MapFlags::MAP_PRIVATE
+        } else {
+            MapFlags::MAP_SHARED
+        };
+        if self.noreserve {
+            flags |= noreserve_flag()
+        }
+        #[cfg(target_os = "linux")]
+        if self.populate {
+            flags |= MapFlags::MAP_POPULATE
+        }
+        flags
+    }
+
+    // the mapping from `offset` on, which the system may not support, e.g. before linux 5.14, and
+    // a failure only leaves the pages to fault in as written
+    fn populate(&mut self, offset: usize) {
+        #[cfg(target_os = "linux")]
+        if self.populate && offset < self.mapped {
+            // not in the `libc` version of `nix` yet
+            const MADV_POPULATE_WRITE: nix::libc::c_int = 23;
+
+            let len = self.mapped - offset;
+            let addr = unsafe { self.addr.add(offset) };
+            if unsafe { nix::libc::madvise(addr.cast(), len, MADV_POPULATE_WRITE) } == 0 {
+                #[cfg(feature = "mmap-stats")]
+                {
+                    self.syscalls.madvise += 1;
+                    self.syscalls.advised_bytes += len;
+                }
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = offset;
+    }
+
+    // the mapping from `offset` on, a failure only leaves the pages as they are
+    fn advise_huge_pages(&mut self, offset: usize) {
+        #[cfg(target_os = "linux")]
+        if self.huge_pages.is_some() && offset < self.mapped {
+            use nix::sys::mman::{madvise, MmapAdvise};
+
+            let len = self.mapped - offset;
+            let addr = unsafe { self.addr.add(offset) };
+            if unsafe { madvise(addr.cast(), len, MmapAdvise::MADV_HUGEPAGE) }.is_ok() {
+                #[cfg(feature = "mmap-stats")]
+                {
+                    self.syscalls.madvise += 1;
+                    self.syscalls.advised_bytes += len;
+                }
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = offset;
+    }
+
+    // the reservation is taken by the first call, then the pages up to `bytes` are committed and
+    // the ones past it given back, replaced by fresh inaccessible ones
+    fn commit(&mut self, bytes: usize) -> bool {
+        use core::num::NonZeroUsize;
+        use nix::{
+            libc::{sysconf, _SC_PAGESIZE},
+            sys::mman::{mmap, mprotect, MapFlags, ProtFlags},
+        };
+
+        if bytes > self.reserve {
+            return false;
+        }
+        let flags = MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS | noreserve_flag();
+        if self.addr.is_null() {
+            let reserve = NonZeroUsize::new(self.reserve).unwrap();
+            let Ok(addr) = (unsafe { mmap(None, reserve, ProtFlags::PROT_NONE, flags, -1, 0) })
+            else {
+                return false;
+            };
+            #[cfg(feature = "mmap-stats")]
+            {
+                self.syscalls.mmap += 1;
+            }
+            self.addr = addr.cast()
+        }
+
+        let page = usize::try_from(unsafe { sysconf(_SC_PAGESIZE) }).unwrap();
+        let committed = usize::min(self.mapping_len(bytes).next_multiple_of(page), self.reserve);
+        let old = self.mapped;
+        if committed > self.mapped {
+            let addr = unsafe { self.addr.add(self.mapped) };
+            let len = committed - self.mapped;
+            let prot = ProtFlags::PROT_READ | ProtFlags::PROT_WRITE;
+            if unsafe { mprotect(addr.cast(), len, prot) }.is_err() {
+                return false;
+            }
+            #[cfg(feature = "mmap-stats")]
+            {
+                self.syscalls.mprotect += 1;
+                self.syscalls.mapped_bytes += len
+            }
+        } else if committed < self.mapped {
+            let addr = NonZeroUsize::new(self.addr as usize + committed);
+            let len = NonZeroUsize::new(self.mapped - committed).unwrap();
+            let flags = flags | MapFlags::MAP_FIXED;
+            if unsafe { mmap(addr, len, ProtFlags::PROT_NONE, flags, -1, 0) }.is_err() {
+                return false;
+            }
+            #[cfg(feature = "mmap-stats")]
+            {
+                self.syscalls.mmap += 1;
+                self.syscalls.unmapped_bytes += len.get()
+            }
+        }
+        // the rest of the last page is kept, and must be zero when growing into it again, unlike the
+        // rest of a huge page
+        if bytes < self.len && self.huge_pages.is_none() {
+            let kept = usize::min(self.len, committed) - bytes;
+            unsafe { self.addr.add(bytes).write_bytes(0, kept) }
+        }
+        self.len = bytes;
+        self.mapped = committed;
+        if committed > old {
+            self.advise_huge_pages(old);
+            self.populate(old)
+        }
+        true
+    }
+
+    // the reservation too, which is taken again when growing next
     pub fn clear(&mut self) {
-        unsafe { nix::sys::mman::munmap(self.addr as _, self.len) }.unwrap();
+        let len = if self.reserve != 0 {
+            self.reserve
+        } else {
+            self.mapped
+        };
+        unsafe { nix::sys::mman::munmap(self.addr as _, len) }.unwrap();
+        #[cfg(feature = "mmap-stats")]
+        {
+            self.syscalls.munmap += 1;
+            self.syscalls.unmapped_bytes += self.mapped;
+        }
         self.addr = null_mut();
         self.len = 0;
+        self.mapped = 0;
+    }
+}
+
+// map `len` bytes at exactly `addr` if nothing else is mapped there. the address is only a hint to
+// a system without `MAP_FIXED_NOREPLACE`, e.g. linux before 4.17 or macos, so a mapping elsewhere
+// is undone
+#[cfg(feature = "nix")]
+unsafe fn map_at(
+    addr: *mut u8,
+    len: core::num::NonZeroUsize,
+    prot: nix::sys::mman::ProtFlags,
+    flags: nix::sys::mman::MapFlags,
+    fd: nix::libc::c_int,
+    offset: nix::libc::off_t,
+) -> bool {
+    use core::num::NonZeroUsize;
+    use nix::sys::mman::{mmap, munmap};
+
+    #[cfg(target_os = "linux")]
+    let flags = flags | nix::sys::mman::MapFlags::MAP_FIXED_NOREPLACE;
+    let Ok(mapped) = (unsafe {
+        mmap(
+            NonZeroUsize::new(addr as usize),
+            len,
+            prot,
+            flags,
+            fd,
+            offset,
+        )
+    }) else {
+        return false;
+    };
+    if mapped.cast() != addr {
+        unsafe { munmap(mapped, len.get()) }.unwrap();
+        return false;
     }
+    true
+}
+
+// `MAP_NORESERVE`, which freebsd and dragonfly do not have, as they never reserve swap space ahead
+#[cfg(feature = "nix")]
+fn noreserve_flag() -> nix::sys::mman::MapFlags {
+    #[cfg(not(any(target_os = "dragonfly", target_os = "freebsd")))]
+    return nix::sys::mman::MapFlags::MAP_NORESERVE;
+    #[cfg(any(target_os = "dragonfly", target_os = "freebsd"))]
+    return nix::sys::mman::MapFlags::empty();
 }
 
 #[cfg(feature = "nix")]
 impl Drop for Mmap {
     fn drop(&mut self) {
-        if self.len != 0 {
+        if !self.addr.is_null() {
             self.clear()
         }
     }
 }
 
-pub struct Fixed<'a>(&'a mut [u8]);
+// a file mapped shared, so the heap in it outlives the process, e.g. laid out by
+// `Allocator::lazy` on the first run and taken over by `Allocator::attach` on the later ones. the
+// file grows with the space and never shrinks. the system writes the pages back at any time, and
+// `flush` waits for that, e.g. before reporting some update as durable
+#[cfg(feature = "std")]
+pub struct FileMap {
+    file: std::fs::File,
+    addr: *mut u8,
+    len: usize,
+    // whole pages mapped, each growth maps the file range right after the last one
+    mapped: usize,
+}
 
-impl<'a> From<&'a mut [u8]> for Fixed<'a> {
-    fn from(value: &'a mut [u8]) -> Self {
-        Self(value)
+#[cfg(feature = "std")]
+unsafe impl Send for FileMap {}
+#[cfg(feature = "std")]
+unsafe impl Sync for FileMap {}
+
+#[cfg(feature = "std")]
+impl FileMap {
+    // the whole file, which is empty if just created
+    pub fn new(file: std::fs::File) -> std::io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len()).map_err(std::io::Error::other)?;
+        let mut space = Self {
+            file,
+            addr: null_mut(),
+            len: 0,
+            mapped: 0,
+        };
+        if !space.set_size(len) {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(space)
+    }
+
+    pub fn file(&self) -> &std::fs::File {
+        &self.file
+    }
+
+    pub fn flush(&self) -> std::io::Result<()> {
+        use nix::sys::mman::{msync, MsFlags};
+
+        if self.addr.is_null() {
+            return Ok(());
+        }
+        unsafe { msync(self.addr.cast(), self.len, MsFlags::MS_SYNC) }?;
+        Ok(())
+    }
+
+    // the file range from the end of the mapping to `mapped`
+    fn map(&mut self, mapped: usize) -> bool {
+        use core::num::NonZeroUsize;
+        use nix::sys::mman::{mmap, MapFlags, ProtFlags};
+        use std::os::fd::AsRawFd;
+
+        let len = NonZeroUsize::new(mapped - self.mapped).unwrap();
+        let prot = ProtFlags::PROT_READ | ProtFlags::PROT_WRITE;
+        let fd = self.file.as_raw_fd();
+        let offset = self.mapped as _;
+        if self.addr.is_null() {
+            let Ok(addr) = (unsafe { mmap(None, len, prot, MapFlags::MAP_SHARED, fd, offset) })
+            else {
+                return false;
+            };
+            self.addr = addr.cast()
+        } else {
+            let addr = unsafe { self.addr.add(self.mapped) };
+            if !unsafe { map_at(addr, len, prot, MapFlags::MAP_SHARED, fd, offset) } {
+                return false;
+            }
+        }
+        self.mapped = mapped;
+        true
     }
 }
 
-impl Deref for Fixed<'_> {
+#[cfg(feature = "std")]
+impl Deref for FileMap {
     type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
-        self.0
+        if self.addr.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.addr, self.len) }
     }
 }
 
-impl DerefMut for Fixed<'_> {
+#[cfg(feature = "std")]
+impl DerefMut for FileMap {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.0
+        if self.addr.is_null() {
+            return &mut [];
+        }
+        unsafe { slice::from_raw_parts_mut(self.addr, self.len) }
     }
 }
 
-impl Space for Fixed<'_> {
+#[cfg(feature = "std")]
+impl Space for FileMap {
+    // not moving, the range after the mapping is taken only if nothing else is there. the file is
+    // put back to its length if the mapping fails, as the heap is as long as the whole file
     fn set_size(&mut self, bytes: usize) -> bool {
-        bytes == self.0.len()
+        use nix::libc::{sysconf, _SC_PAGESIZE};
+
+        if bytes <= self.len {
+            return bytes == self.len;
+        }
+        let Ok(file_len) = self.file.metadata().map(|metadata| metadata.len()) else {
+            return false;
+        };
+        if (bytes as u64) > file_len && self.file.set_len(bytes as u64).is_err() {
+            return false;
+        }
+        let page = usize::try_from(unsafe { sysconf(_SC_PAGESIZE) }).unwrap();
+        let mapped = bytes.next_multiple_of(page);
+        if mapped > self.mapped && !self.map(mapped) {
+            let _ = self.file.set_len(file_len);
+            return false;
+        }
+        self.len = bytes;
+        true
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::alloc::{alloc, dealloc, Layout};
+#[cfg(feature = "std")]
+impl Drop for FileMap {
+    fn drop(&mut self) {
+        if !self.addr.is_null() {
+            unsafe { nix::sys::mman::munmap(self.addr.cast(), self.mapped) }.unwrap()
+        }
+    }
+}
 
-    use super::*;
+pub struct Fixed<'a>(&'a mut [u8]);
 
-    #[test]
+impl<'a> From<&'a mut [u8]> for Fixed<'a> {
+    fn from(value: &'a mut [u8]) -> Self {
+        Self(value)
+    }
+}
+
+impl Deref for Fixed<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl DerefMut for Fixed<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0
+    }
+}
+
+impl Space for Fixed<'_> {
+    fn set_size(&mut self, bytes: usize) -> bool {
+        bytes == self.0.len()
+    }
+}
+
+// a `Fixed`-like space over a static buffer, which can be taken out of the buffer at most once so
+// the exclusiveness is checked at runtime instead of relying on `static mut`
+pub struct StaticSpace {
+    addr: *mut u8,
+    len: usize,
+}
+
+unsafe impl Send for StaticSpace {}
+unsafe impl Sync for StaticSpace {}
+
+impl Deref for StaticSpace {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.addr, self.len) }
+    }
+}
+
+impl DerefMut for StaticSpace {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { slice::from_raw_parts_mut(self.addr, self.len) }
+    }
+}
+
+impl Space for StaticSpace {
+    fn set_size(&mut self, bytes: usize) -> bool {
+        bytes == self.len
+    }
+}
+
+// page aligned, same as what `Mmap` provides
+#[repr(C, align(4096))]
+pub struct StaticBuffer<const N: usize> {
+    buf: UnsafeCell<[u8; N]>,
+    taken: AtomicBool,
+}
+
+unsafe impl<const N: usize> Sync for StaticBuffer<N> {}
+
+impl<const N: usize> StaticBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: UnsafeCell::new([0; N]),
+            taken: AtomicBool::new(false),
+        }
+    }
+
+    // return `None` if the space has been taken before
+    pub fn take(&'static self) -> Option<StaticSpace> {
+        if self.taken.swap(true, SeqCst) {
+            None
+        } else {
+            Some(StaticSpace {
+                addr: self.buf.get().cast(),
+                len: N,
+            })
+        }
+    }
+}
+
+impl<const N: usize> Default for StaticBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// static_heap!(HEAP, 64 << 10);
+// let alloc = Allocator::new(HEAP.take().unwrap());
+// `take` runs at runtime, so a heap that is a `static` itself, e.g. the global allocator, is declared
+// over an inline `Static` space instead, and laid out on first use, see `Allocator::lazy`
+// static_heap!(#[global_allocator] GLOBAL: Allocator, 64 << 10);
+#[macro_export]
+macro_rules! static_heap {
+    ($vis:vis $name:ident, $size:expr) => {
+        $vis static $name: $crate::space::StaticBuffer<{ $size }> =
+            $crate::space::StaticBuffer::new();
+    };
+    ($(#[$attr:meta])* $vis:vis $name:ident: Allocator, $size:expr) => {
+        $(#[$attr])*
+        $vis static $name: $crate::linked::Allocator<$crate::space::Static<{ $size }>> =
+            $crate::linked::Allocator::lazy($crate::space::Static::new());
+    };
+}
+
+// the bytes of the space inline, so unlike `StaticBuffer` it is built in a const and the heap over
+// it can be a `static` itself, e.g.
+// #[global_allocator]
+// static GLOBAL: Allocator<Static<{ 64 << 10 }>> = Allocator::lazy(Static::new());
+// it must not move once the heap is laid out in it
+#[repr(C, align(4096))]
+pub struct Static<const N: usize>([u8; N]);
+
+impl<const N: usize> Static<N> {
+    pub const fn new() -> Self {
+        Self([0; N])
+    }
+}
+
+impl<const N: usize> Default for Static<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Deref for Static<N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> DerefMut for Static<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<const N: usize> Space for Static<N> {
+    fn set_size(&mut self, bytes: usize) -> bool {
+        bytes == N
+    }
+}
+
+// a fixed space of `len` zeroed bytes from the system allocator, aligned to `align`, for test
+// heaps that need a known alignment, e.g. to reproduce a fuzz crash. freed on drop
+#[cfg(any(feature = "std", test))]
+pub struct SystemBuffer {
+    addr: *mut u8,
+    layout: std::alloc::Layout,
+}
+
+#[cfg(any(feature = "std", test))]
+unsafe impl Send for SystemBuffer {}
+#[cfg(any(feature = "std", test))]
+unsafe impl Sync for SystemBuffer {}
+
+#[cfg(any(feature = "std", test))]
+impl SystemBuffer {
+    // panic if `align` is not a power of two, and abort as any failed allocation does
+    pub fn new(len: usize, align: usize) -> Self {
+        use std::alloc::{handle_alloc_error, GlobalAlloc, Layout, System};
+
+        let layout = Layout::from_size_align(len, align)
+            .unwrap()
+            .align_to(8)
+            .unwrap();
+        assert_ne!(len, 0);
+        let addr = unsafe { System.alloc_zeroed(layout) };
+        if addr.is_null() {
+            handle_alloc_error(layout)
+        }
+        Self { addr, layout }
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl Deref for SystemBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.addr, self.layout.size()) }
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl DerefMut for SystemBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { slice::from_raw_parts_mut(self.addr, self.layout.size()) }
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl Space for SystemBuffer {
+    fn set_size(&mut self, bytes: usize) -> bool {
+        bytes == self.layout.size()
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl Drop for SystemBuffer {
+    fn drop(&mut self) {
+        use std::alloc::{GlobalAlloc, System};
+
+        unsafe { System.dealloc(self.addr, self.layout) }
+    }
+}
+
+// the linear memory of a wasm module, from the first page grown on. memory only grows, and other
+// code may grow it too, e.g. a second allocator, so the space grows in place only while it still
+// ends at the end of memory and fails otherwise
+#[cfg(target_arch = "wasm32")]
+pub struct WasmMemory {
+    addr: *mut u8,
+    len: usize,
+    // grown pages, covering `len` and more after shrinking
+    pages: usize,
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe impl Send for WasmMemory {}
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for WasmMemory {}
+
+#[cfg(any(target_arch = "wasm32", test))]
+const WASM_PAGE_SIZE: usize = 64 << 10;
+
+// the bytes of `pages` pages, `None` past the address space, which a whole 4 GiB memory already is
+// on wasm32
+#[cfg(any(target_arch = "wasm32", test))]
+fn wasm_pages_len(pages: usize) -> Option<usize> {
+    pages.checked_mul(WASM_PAGE_SIZE)
+}
+
+// whether a memory of `memory_pages` ends where `pages` pages from `addr` do. both ends are `None`
+// at the end of a whole 4 GiB memory on wasm32
+#[cfg(any(target_arch = "wasm32", test))]
+fn wasm_memory_ends_at(memory_pages: usize, addr: usize, pages: usize) -> bool {
+    let end = wasm_pages_len(pages).and_then(|len| len.checked_add(addr));
+    wasm_pages_len(memory_pages) == end
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WasmMemory {
+    pub const fn new() -> Self {
+        Self {
+            addr: null_mut(),
+            len: 0,
+            pages: 0,
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Default for WasmMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Deref for WasmMemory {
+    type Target = [u8];
+
+    // nothing is mapped yet at a null address
+    fn deref(&self) -> &Self::Target {
+        if self.addr.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.addr, self.len) }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl DerefMut for WasmMemory {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        if self.addr.is_null() {
+            return &mut [];
+        }
+        unsafe { slice::from_raw_parts_mut(self.addr, self.len) }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Space for WasmMemory {
+    fn set_size(&mut self, bytes: usize) -> bool {
+        use core::arch::wasm32::{memory_grow, memory_size};
+
+        if wasm_pages_len(self.pages).is_none_or(|len| bytes <= len) {
+            self.len = bytes;
+            return true;
+        }
+        let pages = bytes.div_ceil(WASM_PAGE_SIZE);
+        if self.pages != 0 && !wasm_memory_ends_at(memory_size(0), self.addr as usize, self.pages) {
+            return false;
+        }
+        // the previous size in pages, or `usize::MAX` if memory cannot grow that much
+        let start = memory_grow(0, pages - self.pages);
+        if start == usize::MAX {
+            return false;
+        }
+        if self.pages == 0 {
+            self.addr = (start * WASM_PAGE_SIZE) as _;
+        }
+        self.len = bytes;
+        self.pages = pages;
+        true
+    }
+}
+
+// any space behind one type, so heaps over different spaces share the code of one
+// `Allocator<DynSpace>` and can be kept together, at the cost of a virtual call on every access to
+// the space. `reserve` cannot be dispatched without an instance, so an erased space grows with
+// `set_size` only, with the heap held meanwhile
+#[cfg(any(feature = "alloc", test))]
+pub struct DynSpace<'a>(alloc::boxed::Box<dyn Space + Send + 'a>);
+
+#[cfg(any(feature = "alloc", test))]
+impl<'a> DynSpace<'a> {
+    pub fn new(space: impl Space + Send + 'a) -> Self {
+        Self(alloc::boxed::Box::new(space))
+    }
+}
+
+#[cfg(any(feature = "alloc", test))]
+impl Deref for DynSpace<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(any(feature = "alloc", test))]
+impl DerefMut for DynSpace<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(any(feature = "alloc", test))]
+impl Space for DynSpace<'_> {
+    fn set_size(&mut self, bytes: usize) -> bool {
+        self.0.set_size(bytes)
+    }
+
+    fn try_set_size(&mut self, bytes: usize) -> Result<(), SizeError> {
+        self.0.try_set_size(bytes)
+    }
+
+    fn grow(&mut self, min_bytes: usize) -> bool {
+        self.0.grow(min_bytes)
+    }
+
+    fn resident_bytes(&self) -> Option<usize> {
+        self.0.resident_bytes()
+    }
+
+    fn can_shrink(&self) -> bool {
+        self.0.can_shrink()
+    }
+
+    fn grows_zeroed(&self) -> bool {
+        self.0.grows_zeroed()
+    }
+
+    fn discard(&mut self, range: Range<usize>) -> Option<Range<usize>> {
+        self.0.discard(range)
+    }
+
+    #[cfg(feature = "mmap-stats")]
+    fn syscall_stats(&self) -> SyscallStats {
+        self.0.syscall_stats()
+    }
+}
+
+// a space whose start is aligned to `A`, checked when wrapped and again after every resize, so a
+// heap or its user relying on e.g. page aligned chunks says so in the type. a space moving to an
+// unaligned address when resized panics, as the memory in it is already there
+pub struct Aligned<S, const A: usize>(S);
+
+impl<S, const A: usize> Aligned<S, A>
+where
+    S: Space,
+{
+    const POWER_OF_TWO: () = assert!(A.is_power_of_two(), "alignment not a power of two");
+
+    // the space back if it is not aligned. an empty space has no start to align
+    pub fn new(space: S) -> Result<Self, S> {
+        let () = Self::POWER_OF_TWO;
+        if Self::is_aligned(&space) {
+            Ok(Self(space))
+        } else {
+            Err(space)
+        }
+    }
+
+    // shared only, resizing the space bypassing the checks
+    pub fn get_ref(&self) -> &S {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> S {
+        self.0
+    }
+
+    fn is_aligned(space: &S) -> bool {
+        space.is_empty() || (space.as_ptr() as usize).is_multiple_of(A)
+    }
+
+    fn checked<T>(&self, done: T) -> T {
+        assert!(
+            Self::is_aligned(&self.0),
+            "space moved to an unaligned address"
+        );
+        done
+    }
+}
+
+impl<S, const A: usize> Deref for Aligned<S, A>
+where
+    S: Space,
+{
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S, const A: usize> DerefMut for Aligned<S, A>
+where
+    S: Space,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<S, const A: usize> Space for Aligned<S, A>
+where
+    S: Space,
+{
+    fn set_size(&mut self, bytes: usize) -> bool {
+        let done = self.0.set_size(bytes);
+        self.checked(done)
+    }
+
+    fn try_set_size(&mut self, bytes: usize) -> Result<(), SizeError> {
+        let done = self.0.try_set_size(bytes);
+        self.checked(done)
+    }
+
+    fn grow(&mut self, min_bytes: usize) -> bool {
+        let done = self.0.grow(min_bytes);
+        self.checked(done)
+    }
+
+    fn reservation(&self) -> Option<Reservation> {
+        self.0.reservation()
+    }
+
+    // growing in place, the start stays where it is checked to be aligned
+    unsafe fn reserve(reservation: Reservation, bytes: usize) -> bool {
+        unsafe { S::reserve(reservation, bytes) }
+    }
+
+    fn set_reserved(&mut self, bytes: usize) {
+        self.0.set_reserved(bytes)
+    }
+
+    fn resident_bytes(&self) -> Option<usize> {
+        self.0.resident_bytes()
+    }
+
+    fn can_shrink(&self) -> bool {
+        self.0.can_shrink()
+    }
+
+    fn grows_zeroed(&self) -> bool {
+        self.0.grows_zeroed()
+    }
+
+    fn discard(&mut self, range: Range<usize>) -> Option<Range<usize>> {
+        self.0.discard(range)
+    }
+
+    #[cfg(feature = "mmap-stats")]
+    fn syscall_stats(&self) -> SyscallStats {
+        self.0.syscall_stats()
+    }
+}
+
+// up to `N` regions mapped independently of each other, for a heap to span them like the segments
+// of dlmalloc, see `Allocator::segmented`. the heap takes another region when the ones it has
+// cannot fit an allocation, instead of growing the last one in place, so it needs no `mremap` and
+// never moves. the spaces are empty until taken, in order
+pub struct Segments<S, const N: usize> {
+    spaces: [S; N],
+    // taken
+    len: usize,
+    segment_size: usize,
+}
+
+impl<S, const N: usize> Segments<S, N> {
+    // e.g. `Segments::new([const { Mmap::new() }; 8], 1 << 20)`, taking regions of 1 MB, or of the
+    // size of an allocation that does not fit one
+    pub const fn new(spaces: [S; N], segment_size: usize) -> Self {
+        assert!(segment_size != 0);
+        Self {
+            spaces,
+            len: 0,
+            segment_size,
+        }
+    }
+
+    pub fn segment_size(&self) -> usize {
+        self.segment_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // the taken regions, in the order taken
+    pub fn as_slice(&self) -> &[S] {
+        &self.spaces[..self.len]
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [S] {
+        &mut self.spaces[..self.len]
+    }
+}
+
+impl<S, const N: usize> Segments<S, N>
+where
+    S: Space,
+{
+    // the region `ptr` is in
+    pub fn position(&self, ptr: *const u8) -> Option<usize> {
+        self.as_slice()
+            .iter()
+            .position(|space| space.as_ptr_range().contains(&ptr))
+    }
+
+    // take the next region, resized to `bytes`. `None` if all are taken or it cannot be resized,
+    // and it stays untaken then
+    pub fn push(&mut self, bytes: usize) -> Option<&mut S> {
+        let space = self.spaces.get_mut(self.len)?;
+        if !space.set_size(bytes) {
+            return None;
+        }
+        self.len += 1;
+        Some(space)
+    }
+
+    // of all the regions together
+    #[cfg(feature = "mmap-stats")]
+    pub fn syscall_stats(&self) -> SyscallStats {
+        let mut stats = SyscallStats::default();
+        for space in &self.spaces {
+            stats.merge(space.syscall_stats())
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
     fn persistent_data() {
         fn run<S: Space>(space: &mut S) {
             let done = space.set_size(4 << 10); // 4KB
@@ -181,9 +1654,425 @@ mod tests {
             assert_eq!((space.as_ptr() as usize) % (4 << 10), 0);
         }
         // run(&mut Mmap::new());
-        let layout = Layout::from_size_align(4 << 10, 4 << 10).unwrap();
-        let mut space = Fixed::from(unsafe { slice::from_raw_parts_mut(alloc(layout), 4 << 10) });
-        run(&mut space);
-        unsafe { dealloc(space.0.as_mut_ptr(), layout) }
+        run(&mut SystemBuffer::new(4 << 10, 4 << 10));
+    }
+
+    #[test]
+    fn system_buffer() {
+        let mut space = SystemBuffer::new(100, 256);
+        assert_eq!(space.as_ptr() as usize % 256, 0);
+        assert_eq!(space.len(), 100);
+        assert!(space.iter().all(|byte| *byte == 0));
+        space[99] = 1;
+        assert!(space.set_size(100));
+        assert!(!space.set_size(200));
+    }
+
+    #[test]
+    fn dyn_space() {
+        use core::alloc::{GlobalAlloc, Layout};
+
+        use crate::{linked::Allocator, Owns};
+
+        let data = &mut *std::vec![0; 4 << 10];
+        let heaps = [
+            Allocator::new(DynSpace::new(Fixed::from(&mut data[..2 << 10]))),
+            Allocator::new(DynSpace::new(SystemBuffer::new(4 << 10, 64))),
+        ];
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        for alloc in &heaps {
+            let ptr = unsafe { alloc.alloc(layout) };
+            assert!(alloc.owns(ptr));
+            unsafe { alloc.dealloc(ptr, layout) }
+            assert_eq!(alloc.check(), Ok(()));
+        }
+        assert!(!heaps[0].acquire_space().can_shrink());
+    }
+
+    // the page arithmetic of `WasmMemory`, with the overflow of a whole 4 GiB memory on wasm32
+    // stood in for by a memory as large as `usize` itself
+    #[test]
+    fn wasm_pages() {
+        let full = usize::MAX / WASM_PAGE_SIZE + 1;
+        assert_eq!(wasm_pages_len(2), Some(128 << 10));
+        assert_eq!(wasm_pages_len(full), None);
+        assert!(wasm_memory_ends_at(4, 128 << 10, 2));
+        assert!(!wasm_memory_ends_at(5, 128 << 10, 2));
+        // only a space running to the end ends with a whole memory
+        assert!(!wasm_memory_ends_at(full, 64 << 10, 2));
+        assert!(wasm_memory_ends_at(full, 64 << 10, full - 1));
+    }
+
+    #[test]
+    fn aligned_space() {
+        use core::alloc::{GlobalAlloc, Layout};
+
+        use crate::linked::Allocator;
+
+        let space = SystemBuffer::new(4 << 10, 4 << 10);
+        let mut space = Aligned::<_, 4096>::new(space).ok().unwrap();
+        assert!(space.set_size(4 << 10));
+        assert!(!space.set_size(8 << 10));
+        let alloc = Allocator::new(space);
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert!(!ptr.is_null());
+        unsafe { alloc.dealloc(ptr, layout) }
+        assert_eq!(alloc.check(), Ok(()));
+
+        let data = &mut *std::vec![0u64; 64];
+        let data = unsafe { slice::from_raw_parts_mut(data.as_mut_ptr().cast::<u8>(), 512) };
+        let space = Aligned::<_, 8>::new(Fixed::from(&mut data[8..]))
+            .ok()
+            .unwrap();
+        assert!(Aligned::<_, 8>::new(Fixed::from(&mut space.into_inner()[1..])).is_err());
+        assert!(Aligned::<_, 4096>::new(Fixed::from(&mut [][..])).is_ok());
+    }
+
+    #[test]
+    fn static_space() {
+        static_heap!(HEAP, 4 << 10);
+        let mut space = HEAP.take().unwrap();
+        assert!(HEAP.take().is_none());
+        assert!(space.set_size(4 << 10));
+        assert!(!space.set_size(8 << 10));
+        assert_eq!((space.as_ptr() as usize) % (4 << 10), 0);
+        space[0] = 0x82;
+        assert_eq!(space[0], 0x82);
+    }
+
+    #[test]
+    fn static_inline() {
+        use core::alloc::{GlobalAlloc, Layout};
+
+        static_heap!(HEAP: Allocator, 16 << 10);
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptr = unsafe { HEAP.alloc(layout) };
+        assert!(!ptr.is_null());
+        assert_eq!(HEAP.stats().allocation_count, 1);
+        unsafe { HEAP.dealloc(ptr, layout) }
+        assert_eq!(HEAP.check(), Ok(()));
+        assert_eq!((HEAP.acquire_space().as_ptr() as usize) % (4 << 10), 0);
+    }
+
+    #[cfg(all(feature = "nix", target_os = "linux"))]
+    #[test]
+    fn mmap_resident() {
+        let mut space = Mmap::new();
+        assert_eq!(space.resident_bytes(), Some(0));
+        assert!(space.set_size(1 << 20));
+        space[..8 << 10].fill(1);
+        space[(1 << 20) - 1] = 1;
+        let resident = space.resident_bytes().unwrap();
+        assert!((12 << 10..1 << 20).contains(&resident), "{resident}");
+        assert_eq!(Fixed::from(&mut [0; 8][..]).resident_bytes(), None);
+    }
+
+    #[cfg(all(feature = "nix", target_os = "linux"))]
+    #[test]
+    fn mmap_builder() {
+        let mut space = Mmap::builder()
+            .private(true)
+            .populate(true)
+            .noreserve(true)
+            .build();
+        assert!(space.set_size(1 << 20));
+        // faulted in without touching
+        assert_eq!(space.resident_bytes(), Some(1 << 20));
+        space.fill(1);
+        assert_eq!(space.discard(0..64 << 10), Some(0..64 << 10));
+        assert_eq!((space[(64 << 10) - 1], space[64 << 10]), (0, 1));
+        // unless something else is mapped right after
+        if space.set_size(2 << 20) {
+            assert_eq!(space.resident_bytes(), Some(2 << 20));
+        }
+
+        let mut space = Mmap::builder().build();
+        assert!(space.set_size(1 << 20));
+        assert_eq!(space.resident_bytes(), Some(0));
+    }
+
+    #[cfg(all(feature = "nix", target_os = "linux"))]
+    #[test]
+    fn mmap_lock() {
+        // growing in place may fail, depending on what is mapped after
+        for mut space in [Mmap::movable(), Mmap::with_reserve(4 << 20)] {
+            space.lock_memory(true);
+            assert!(space.locks_memory());
+            // within the lock limit, unless running privileged
+            match space.try_set_size(16 << 10) {
+                Ok(()) => {}
+                Err(SizeError::Lock(_)) => {
+                    assert!(space.is_empty());
+                    continue;
+                }
+                Err(error) => panic!("{error:?}"),
+            }
+            // locked pages are resident without touching them
+            assert_eq!(space.resident_bytes(), Some(16 << 10));
+            assert_eq!(space.try_set_size(32 << 10), Ok(()));
+            assert_eq!(space.resident_bytes(), Some(32 << 10));
+            assert_eq!(space.try_set_size(4 << 10), Ok(()));
+            assert_eq!(space.discard(0..4 << 10), None);
+            assert_eq!(space.reservation(), None);
+            #[cfg(feature = "mmap-stats")]
+            {
+                let stats = space.syscall_stats();
+                assert_eq!((stats.mlock, stats.munlock), (2, 1));
+            }
+        }
+        assert_eq!(
+            Fixed::from(&mut [0; 8][..]).try_set_size(16),
+            Err(SizeError::Exhausted)
+        );
+    }
+
+    #[cfg(all(feature = "nix", target_os = "linux"))]
+    #[test]
+    fn mmap_discard() {
+        for mut space in [Mmap::new(), Mmap::movable(), Mmap::with_reserve(4 << 20)] {
+            assert!(space.set_size(1 << 20));
+            space.fill(1);
+            let discarded = space.discard(100..(64 << 10) + 100).unwrap();
+            assert_eq!(discarded, 4 << 10..64 << 10);
+            assert!(space.resident_bytes().unwrap() <= (1 << 20) - (60 << 10));
+            assert_eq!((space[(4 << 10) - 1], space[4 << 10]), (1, 0));
+            assert_eq!((space[(64 << 10) - 1], space[64 << 10]), (0, 1));
+            assert_eq!(space.discard(100..4000), None);
+        }
+        assert_eq!(Fixed::from(&mut [0; 8][..]).discard(0..8), None);
+    }
+
+    #[cfg(all(feature = "nix", target_os = "linux"))]
+    #[test]
+    fn mmap_movable() {
+        use core::{
+            alloc::{GlobalAlloc, Layout},
+            num::NonZeroUsize,
+        };
+        use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+
+        use crate::linked::Allocator;
+
+        let alloc = Allocator::lazy(Mmap::movable());
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        unsafe { ptr.write_bytes(0xa5, 100) };
+        let (base, offset) = (alloc.base(), ptr as usize - alloc.base() as usize);
+
+        // something mapped right after the heap, so it cannot grow in place
+        let end = base as usize + alloc.acquire_space().len();
+        let blocker = unsafe {
+            mmap(
+                NonZeroUsize::new(end),
+                NonZeroUsize::new(4096).unwrap(),
+                ProtFlags::PROT_NONE,
+                MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS | MapFlags::MAP_FIXED_NOREPLACE,
+                -1,
+                0,
+            )
+        };
+        let large = Layout::from_size_align(1 << 20, 8).unwrap();
+        let large_ptr = unsafe { alloc.alloc(large) };
+        assert!(!large_ptr.is_null());
+        assert_ne!(alloc.base(), base);
+        let ptr = unsafe { alloc.base().add(offset) };
+        assert!((0..100).all(|i| unsafe { *ptr.add(i) } == 0xa5));
+        assert_eq!(alloc.check(), Ok(()));
+        unsafe {
+            alloc.dealloc(ptr, layout);
+            alloc.dealloc(large_ptr, large)
+        }
+        assert_eq!(alloc.stats().allocation_count, 0);
+        assert_eq!(alloc.check(), Ok(()));
+        if let Ok(blocker) = blocker {
+            unsafe { munmap(blocker, 4096) }.unwrap()
+        }
+    }
+
+    // the growth of the systems without `mremap`, which linux can take too
+    #[cfg(feature = "nix")]
+    #[test]
+    fn mmap_map_around() {
+        use core::num::NonZeroUsize;
+        use nix::sys::mman::{mmap, munmap, ProtFlags};
+
+        fn resize(space: &mut Mmap, bytes: usize) -> bool {
+            let Ok(addr) = (unsafe { space.map_around(bytes) }) else {
+                return false;
+            };
+            (space.addr, space.len, space.mapped) = (addr.cast(), bytes, bytes);
+            true
+        }
+
+        for mut space in [Mmap::new(), Mmap::movable()] {
+            // mapped by hand, with a page mapped 64 KB in to block it, as the pages right after a
+            // new mapping may be taken
+            let len = NonZeroUsize::new(68 << 10).unwrap();
+            let prot = ProtFlags::PROT_READ | ProtFlags::PROT_WRITE;
+            let base = unsafe { mmap(None, len, prot, space.map_flags(), -1, 0) }
+                .unwrap()
+                .cast::<u8>();
+            let blocker = unsafe { base.add(64 << 10) };
+            unsafe { munmap(base.add(16 << 10).cast(), 48 << 10) }.unwrap();
+            (space.addr, space.len, space.mapped) = (base, 16 << 10, 16 << 10);
+            space.fill(1);
+
+            // in place up to the blocker, with the new pages zeroed
+            assert!(resize(&mut space, (32 << 10) + 100));
+            assert!(resize(&mut space, 64 << 10));
+            assert_eq!(space.as_mut_ptr(), base);
+            assert_eq!((space[(16 << 10) - 1], space[16 << 10]), (1, 0));
+            // and moved past it, if movable
+            assert_eq!(resize(&mut space, 1 << 20), space.movable);
+            if space.movable {
+                assert_ne!(space.as_mut_ptr(), base);
+                assert_eq!((space[(16 << 10) - 1], space[16 << 10]), (1, 0));
+            }
+            assert!(resize(&mut space, 4 << 10));
+            assert_eq!(space[(4 << 10) - 1], 1);
+            unsafe { munmap(blocker.cast(), 4096) }.unwrap()
+        }
+    }
+
+    #[cfg(all(feature = "nix", target_os = "linux"))]
+    #[test]
+    fn mmap_reserve() {
+        use core::alloc::{GlobalAlloc, Layout};
+
+        use crate::linked::Allocator;
+
+        let mut space = Mmap::with_reserve(64 << 20);
+        assert!(space.set_size(16 << 10));
+        let addr = space.as_ptr();
+        space.fill(1);
+        assert!(space.set_size(32 << 20));
+        assert_eq!(space.as_ptr(), addr);
+        assert_eq!((space[(16 << 10) - 1], space[16 << 10]), (1, 0));
+        // given back, and zero when committed again
+        assert!(space.set_size(4 << 10));
+        assert!(space.set_size(16 << 10));
+        assert_eq!((space[(4 << 10) - 1], space[4 << 10]), (1, 0));
+        assert!(space.set_size(100));
+        assert!(space.set_size(200));
+        assert_eq!((space[99], space[100]), (1, 0));
+        assert!(space.set_size(16 << 10));
+        assert!(!space.set_size((64 << 20) + 1));
+        assert_eq!(space.len(), 16 << 10);
+        #[cfg(feature = "mmap-stats")]
+        {
+            let stats = space.syscall_stats();
+            assert_eq!((stats.mmap, stats.mprotect, stats.munmap), (3, 4, 0));
+            assert_eq!(stats.mapped_bytes - stats.unmapped_bytes, 16 << 10);
+        }
+
+        let alloc = Allocator::lazy(Mmap::with_reserve(64 << 20));
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        let base = alloc.base();
+        let large = Layout::from_size_align(16 << 20, 8).unwrap();
+        let large_ptr = unsafe { alloc.alloc(large) };
+        assert!(!large_ptr.is_null());
+        assert_eq!(alloc.base(), base);
+        unsafe {
+            alloc.dealloc(large_ptr, large);
+            alloc.dealloc(ptr, layout)
+        }
+        assert_eq!(alloc.check(), Ok(()));
+    }
+
+    #[cfg(feature = "nix")]
+    #[test]
+    fn mmap_huge_pages() {
+        let mut space = Mmap::new();
+        space.set_huge_pages(HugePageSize::Size2M);
+        assert!(space.set_size(100 << 10));
+        assert_eq!(space.len(), 100 << 10);
+        assert_eq!(space.reservation(), None);
+        space.fill(1);
+        // within the first huge page, without remapping
+        assert!(space.set_size(1 << 20));
+        assert_eq!(space[100 << 10], 0);
+        assert!(space.set_size(4 << 10));
+        #[cfg(feature = "mmap-stats")]
+        {
+            let stats = space.syscall_stats();
+            assert_eq!((stats.mmap, stats.mremap), (1, 0));
+            assert_eq!(stats.mapped_bytes, 2 << 20);
+        }
+
+        let mut space = Mmap::with_reserve(8 << 20);
+        space.set_huge_pages(HugePageSize::Size2M);
+        assert!(space.set_size(100 << 10));
+        assert!(space.set_size(3 << 20));
+        #[cfg(feature = "mmap-stats")]
+        assert_eq!(space.syscall_stats().mapped_bytes, 4 << 20);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn file_map() {
+        use core::alloc::{GlobalAlloc, Layout};
+        use std::fs::{self, OpenOptions};
+
+        use crate::linked::Allocator;
+
+        let path = std::env::temp_dir().join(std::format!("simpile-{}", std::process::id()));
+        let open = || {
+            let mut options = OpenOptions::new();
+            options.read(true).write(true).create(true);
+            FileMap::new(options.open(&path).unwrap()).unwrap()
+        };
+        let layout = Layout::from_size_align(8, 8).unwrap();
+        let offset = {
+            let alloc = Allocator::lazy(open());
+            let ptr = unsafe { alloc.alloc(layout) };
+            unsafe { ptr.copy_from(b"persists".as_ptr(), 8) };
+            // growing, unless something else is mapped right after
+            let large = Layout::from_size_align(1 << 20, 8).unwrap();
+            let large_ptr = unsafe { alloc.alloc(large) };
+            if !large_ptr.is_null() {
+                unsafe { alloc.dealloc(large_ptr, large) }
+            }
+            alloc.with_space(|space| space.flush()).unwrap();
+            ptr as usize - alloc.base() as usize
+        };
+
+        // a later run
+        let alloc = Allocator::attach(open()).unwrap();
+        assert_eq!(alloc.check(), Ok(()));
+        let ptr = unsafe { alloc.base().add(offset) };
+        assert!(alloc.find_chunk(ptr).unwrap().in_use);
+        assert_eq!(unsafe { *ptr.cast::<[u8; 8]>() }, *b"persists");
+        unsafe { alloc.dealloc(ptr, layout) }
+        drop(alloc);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "mmap-stats")]
+    #[test]
+    fn mmap_syscalls() {
+        let mut space = Mmap::new();
+        assert!(space.set_size(16 << 10));
+        assert!(space.set_size(8 << 10));
+        assert!(space.set_size(4 << 10));
+        let stats = space.syscall_stats();
+        assert_eq!((stats.mmap, stats.mremap, stats.munmap), (1, 2, 0));
+        assert_eq!(stats.mapped_bytes, 16 << 10);
+        assert_eq!(stats.unmapped_bytes, 12 << 10);
+
+        // growing in place fails if something else is mapped right after
+        let reservation = space.reservation().unwrap();
+        let reserved = unsafe { Mmap::reserve(reservation, 8 << 10) };
+        if reserved {
+            space.set_reserved(8 << 10);
+        }
+        space.clear();
+        let stats = space.syscall_stats();
+        let grown = if reserved { 4 << 10 } else { 0 };
+        assert_eq!(stats.mremap, 2 + reserved as usize);
+        assert_eq!(stats.munmap, 1);
+        assert_eq!(stats.mapped_bytes, (16 << 10) + grown);
+        assert_eq!(stats.unmapped_bytes, stats.mapped_bytes);
     }
 }
@@ -4,19 +4,19 @@ use std::{
     thread::panicking,
 };
 
-use crate::{linked::Allocator, Space};
+use crate::Owns;
 
+// kept for compatibility, implement `Owns` instead
 pub trait EnablePtr {
     fn enable_ptr(&self, ptr: *mut u8) -> bool;
 }
 
-impl<S> EnablePtr for Allocator<S>
+impl<A> EnablePtr for A
 where
-    S: Space,
+    A: Owns,
 {
     fn enable_ptr(&self, ptr: *mut u8) -> bool {
-        let mut space = self.acquire_space();
-        space.as_mut_ptr_range().contains(&ptr)
+        self.owns(ptr)
     }
 }
 
@@ -44,13 +44,27 @@ impl<A> Switchable<A> {
     }
 
     fn enable_alloc(&self) -> bool {
-        self.enable.load(SeqCst) && !panicking()
+        let enable = self.enable.load(SeqCst) && !panicking();
+        // the switch may be flipped before the chosen allocator is reached
+        #[cfg(feature = "chaos")]
+        crate::chaos::delay();
+        enable
+    }
+}
+
+// only pointers from `A`, the ones from the fallback are not told apart from foreign ones
+impl<A> Owns for Switchable<A>
+where
+    A: Owns,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        self.alloc.owns(ptr)
     }
 }
 
 unsafe impl<A> GlobalAlloc for Switchable<A>
 where
-    A: GlobalAlloc + EnablePtr,
+    A: GlobalAlloc + Owns,
 {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         if self.enable_alloc() {
@@ -69,7 +83,7 @@ where
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        if self.alloc.enable_ptr(ptr) {
+        if self.alloc.owns(ptr) {
             unsafe { self.alloc.dealloc(ptr, layout) }
         } else {
             unsafe { System.dealloc(ptr, layout) }
@@ -77,7 +91,7 @@ where
     }
 
     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
-        if self.alloc.enable_ptr(ptr) {
+        if self.alloc.owns(ptr) {
             unsafe { self.alloc.realloc(ptr, layout, new_size) }
         } else {
             unsafe { System.realloc(ptr, layout, new_size) }
@@ -0,0 +1,185 @@
+use lock_api::RawMutex;
+
+use crate::{linked::Allocator, Space};
+
+// assertions for test suites that the code under test does not leak from a heap, only the walk
+// statistics go into the panic message, so these are fine to use on the global allocator
+
+#[track_caller]
+pub fn assert_heap_empty<S: Space, R: RawMutex>(alloc: &Allocator<S, R>) {
+    let stats = alloc.stats();
+    assert!(
+        stats.allocation_count == 0,
+        "heap is not empty: {} allocations in {} bytes",
+        stats.allocation_count,
+        stats.allocated_bytes
+    );
+}
+
+// `bytes` counts whole chunks including metadata, as reported by `Allocator::stats`
+#[track_caller]
+pub fn assert_used_bytes<S: Space, R: RawMutex>(alloc: &Allocator<S, R>, bytes: usize) {
+    let stats = alloc.stats();
+    assert!(
+        stats.allocated_bytes == bytes,
+        "expect {bytes} bytes in use, found {} bytes in {} allocations",
+        stats.allocated_bytes,
+        stats.allocation_count
+    );
+}
+
+// run `f` and assert that every allocation it made from the heap has been freed when it returns
+#[track_caller]
+pub fn assert_no_leak<S: Space, R: RawMutex, T>(
+    alloc: &Allocator<S, R>,
+    f: impl FnOnce() -> T,
+) -> T {
+    let before = alloc.stats();
+    let result = f();
+    let after = alloc.stats();
+    assert!(
+        after.allocation_count <= before.allocation_count
+            && after.allocated_bytes <= before.allocated_bytes,
+        "leaked {} allocations in {} bytes",
+        after
+            .allocation_count
+            .saturating_sub(before.allocation_count),
+        after.allocated_bytes.saturating_sub(before.allocated_bytes)
+    );
+    result
+}
+
+// the chunk structure of a heap, compared by a hash of every chunk so that taking it does not
+// allocate. a soak test takes it at start and checks that the heap collapses back to it after an
+// arbitrary workload, which fails if anything leaks or some free chunks are left uncoalesced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shape {
+    pub chunk_count: usize,
+    pub free_count: usize,
+    pub free_bytes: usize,
+    hash: u64,
+}
+
+impl Shape {
+    pub fn of<S: Space, R: RawMutex>(alloc: &Allocator<S, R>) -> Self {
+        // FNV-1a over the offset, size and state of every chunk
+        let mut hash = 0xcbf29ce484222325u64;
+        let mut add = |word: u64| {
+            for byte in word.to_le_bytes() {
+                hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            }
+        };
+        let mut shape = Self {
+            chunk_count: 0,
+            free_count: 0,
+            free_bytes: 0,
+            hash: 0,
+        };
+        let mut start = None;
+        for info in alloc.chunks() {
+            let start = *start.get_or_insert(info.addr);
+            add(info.addr as u64 - start as u64);
+            add(info.size as u64);
+            add(info.in_use as u64);
+            shape.chunk_count += 1;
+            if !info.in_use {
+                shape.free_count += 1;
+                shape.free_bytes += info.size;
+            }
+        }
+        shape.hash = hash;
+        shape
+    }
+}
+
+#[track_caller]
+pub fn assert_restored<S: Space, R: RawMutex>(alloc: &Allocator<S, R>, shape: &Shape) {
+    let restored = Shape::of(alloc);
+    assert!(
+        restored == *shape,
+        "heap is not restored: expect {shape:?}, found {restored:?}"
+    );
+}
+
+// run `f` as a soak workload and assert the heap is restored when it returns
+#[track_caller]
+pub fn assert_soak<S: Space, R: RawMutex, T>(alloc: &Allocator<S, R>, f: impl FnOnce() -> T) -> T {
+    let shape = Shape::of(alloc);
+    let result = f();
+    assert_restored(alloc, &shape);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+    use std::vec;
+
+    use crate::{
+        linked::{Config, TopPolicy},
+        space::Fixed,
+    };
+
+    use super::*;
+
+    #[test]
+    fn assertions() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        assert_heap_empty(&alloc);
+        assert_used_bytes(&alloc, 0);
+
+        let size = assert_no_leak(&alloc, || {
+            let ptr = unsafe { alloc.alloc(layout) };
+            let size = alloc.find_chunk(ptr).unwrap().size;
+            unsafe { alloc.dealloc(ptr, layout) };
+            size
+        });
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert_used_bytes(&alloc, size);
+        unsafe { alloc.dealloc(ptr, layout) };
+        assert_heap_empty(&alloc);
+    }
+
+    #[test]
+    fn soak() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::with_config(
+            Fixed::from(data),
+            Config {
+                top_policy: TopPolicy::KeepWilderness,
+                ..Default::default()
+            },
+        );
+        let shape = Shape::of(&alloc);
+        assert_eq!(shape.chunk_count, 2);
+        assert_soak(&alloc, || {
+            let layouts = [(16, 1), (100, 64), (300, 8), (24, 16)]
+                .map(|(size, align)| Layout::from_size_align(size, align).unwrap());
+            let ptrs = layouts.map(|layout| unsafe { alloc.alloc(layout) });
+            for (ptr, layout) in ptrs.into_iter().zip(layouts).rev().step_by(2) {
+                unsafe { alloc.dealloc(ptr, layout) }
+            }
+            for (ptr, layout) in ptrs.into_iter().zip(layouts).step_by(2) {
+                unsafe { alloc.dealloc(ptr, layout) }
+            }
+        });
+
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert_ne!(Shape::of(&alloc), shape);
+        unsafe { alloc.dealloc(ptr, layout) };
+        assert_restored(&alloc, &shape);
+    }
+
+    #[test]
+    #[should_panic(expected = "leaked 1 allocations")]
+    fn leak() {
+        let data = &mut *vec![0; 4 << 10];
+        let alloc = Allocator::new(Fixed::from(data));
+        assert_no_leak(&alloc, || unsafe {
+            alloc.alloc(Layout::from_size_align(16, 1).unwrap())
+        });
+    }
+}
@@ -0,0 +1,461 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ptr::{copy_nonoverlapping, null_mut},
+};
+
+use spin::{Mutex, MutexGuard};
+
+use crate::{Owns, Space};
+
+// two level segregated fit, for real-time callers that need a bound on every operation rather
+// than the best fit. free blocks are kept in a list per size class, a first level of power of two
+// ranges each split into `SL_COUNT` linear second levels, and a bitmap per level tells the
+// non-empty lists, so both allocating and freeing are O(1). the space is not grown
+// the space starts with the header:
+// slot 0: marker
+// slot 1: first level bitmap
+// slots 2..2 + FL_COUNT: second level bitmap of each first level
+// then the head of each list, and the blocks from `blocks_offset()` on, ending with an in-use
+// sentinel header of size 0 so every block has a higher neighbor
+// a block starts with its size and the flags, a free block holds next and prev of its list after
+// that and a size footer at its end, like a chunk in `linked`
+struct Overlay {
+    space: *mut u8,
+    len: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Block(*mut u8);
+
+impl Block {
+    const FREE_BIT: u64 = 1;
+    const LOWER_FREE_BIT: u64 = 2;
+    const FLAGS: u64 = 7;
+    const MIN_SIZE: usize = 32;
+
+    fn size(self) -> usize {
+        (unsafe { *self.0.cast::<u64>() } & !Self::FLAGS) as usize
+    }
+
+    fn is_free(self) -> bool {
+        unsafe { *self.0.cast::<u64>() & Self::FREE_BIT != 0 }
+    }
+
+    fn is_lower_free(self) -> bool {
+        unsafe { *self.0.cast::<u64>() & Self::LOWER_FREE_BIT != 0 }
+    }
+
+    // the lower free bit is kept, the footer is written for a free block
+    fn set(self, size: usize, free: bool) {
+        let header = unsafe { &mut *self.0.cast::<u64>() };
+        *header = size as u64 | (*header & Self::LOWER_FREE_BIT) | (free as u64 * Self::FREE_BIT);
+        if free {
+            unsafe { *self.0.add(size - 8).cast::<u64>() = size as u64 }
+        }
+    }
+
+    fn set_lower_free(self, free: bool) {
+        let header = unsafe { &mut *self.0.cast::<u64>() };
+        *header = *header & !Self::LOWER_FREE_BIT | (free as u64 * Self::LOWER_FREE_BIT)
+    }
+
+    fn higher(self) -> Self {
+        Self(unsafe { self.0.add(self.size()) })
+    }
+
+    fn lower_free(self) -> Option<Self> {
+        self.is_lower_free().then(|| {
+            let size = unsafe { *self.0.sub(8).cast::<u64>() } as usize;
+            Self(unsafe { self.0.sub(size) })
+        })
+    }
+
+    fn links(self) -> *mut [*mut u8; 2] {
+        unsafe { self.0.add(8).cast() }
+    }
+}
+
+impl Overlay {
+    const SL_LOG2: u32 = 4;
+    const SL_COUNT: usize = 1 << Self::SL_LOG2;
+    const FL_COUNT: usize = 32;
+    // sizes below are all in the first level, linearly by 8 bytes
+    const SMALL_SIZE: usize = Self::SL_COUNT * 8;
+    const FL_INDEX: usize = 1;
+    const SL_INDEX: usize = 2;
+    const HEADS_INDEX: usize = Self::SL_INDEX + Self::FL_COUNT;
+    const MARKER: u8 = 0x7f;
+
+    fn new(space: &mut impl Space) -> Self {
+        Self {
+            space: space.as_mut_ptr(),
+            len: space.len(),
+        }
+    }
+
+    fn blocks_offset() -> usize {
+        8 * (Self::HEADS_INDEX + Self::FL_COUNT * Self::SL_COUNT)
+    }
+
+    unsafe fn init(&mut self) {
+        assert!(
+            self.len >= Self::blocks_offset() + Block::MIN_SIZE + 8,
+            "space too small for a tlsf heap"
+        );
+        assert_eq!(self.space as usize & 7, 0);
+        unsafe { self.space.write_bytes(0, Self::blocks_offset()) };
+        let size = (self.len - Self::blocks_offset() - 8) & !7;
+        let block = Block(unsafe { self.space.add(Self::blocks_offset()) });
+        unsafe { *block.0.cast::<u64>() = 0 };
+        block.set(size, true);
+        let sentinel = block.higher();
+        unsafe { *sentinel.0.cast::<u64>() = 0 };
+        sentinel.set_lower_free(true);
+        unsafe { self.insert(block) };
+        unsafe { *self.space = Self::MARKER };
+    }
+
+    fn mapping(size: usize) -> (usize, usize) {
+        if size < Self::SMALL_SIZE {
+            (0, size / 8)
+        } else {
+            let log2 = size.ilog2();
+            let fl = (log2 - Self::SL_LOG2 - 3 + 1) as usize;
+            let sl = (size >> (log2 - Self::SL_LOG2)) ^ Self::SL_COUNT;
+            (fl, sl)
+        }
+    }
+
+    unsafe fn word(&self, index: usize) -> *mut u64 {
+        unsafe { self.space.add(8 * index).cast() }
+    }
+
+    unsafe fn head(&self, fl: usize, sl: usize) -> *mut *mut u8 {
+        unsafe {
+            self.word(Self::HEADS_INDEX + fl * Self::SL_COUNT + sl)
+                .cast()
+        }
+    }
+
+    unsafe fn insert(&mut self, block: Block) {
+        let (fl, sl) = Self::mapping(block.size());
+        unsafe {
+            let head = *self.head(fl, sl);
+            *block.links() = [head, null_mut()];
+            if !head.is_null() {
+                (*Block(head).links())[1] = block.0
+            }
+            *self.head(fl, sl) = block.0;
+            *self.word(Self::FL_INDEX) |= 1 << fl;
+            *self.word(Self::SL_INDEX + fl) |= 1 << sl;
+        }
+    }
+
+    unsafe fn remove(&mut self, block: Block) {
+        let (fl, sl) = Self::mapping(block.size());
+        unsafe {
+            let [next, prev] = *block.links();
+            if prev.is_null() {
+                *self.head(fl, sl) = next;
+                if next.is_null() {
+                    *self.word(Self::SL_INDEX + fl) &= !(1 << sl);
+                    if *self.word(Self::SL_INDEX + fl) == 0 {
+                        *self.word(Self::FL_INDEX) &= !(1 << fl)
+                    }
+                }
+            } else {
+                (*Block(prev).links())[0] = next
+            }
+            if !next.is_null() {
+                (*Block(next).links())[1] = prev
+            }
+        }
+    }
+
+    // a free block of at least `size` bytes out of its list, `None` if there is none. the size is
+    // rounded up to the next class, so any block of the class fits without searching the list
+    unsafe fn find(&mut self, size: usize) -> Option<Block> {
+        let size = if size < Self::SMALL_SIZE {
+            size
+        } else {
+            size.checked_add((1 << (size.ilog2() - Self::SL_LOG2)) - 1)?
+        };
+        let (mut fl, sl) = Self::mapping(size);
+        if fl >= Self::FL_COUNT {
+            return None;
+        }
+        let mut sl_map = unsafe { *self.word(Self::SL_INDEX + fl) } & (!0 << sl);
+        if sl_map == 0 {
+            let fl_map = unsafe { *self.word(Self::FL_INDEX) } & (!0 << fl << 1);
+            if fl_map == 0 {
+                return None;
+            }
+            fl = fl_map.trailing_zeros() as usize;
+            sl_map = unsafe { *self.word(Self::SL_INDEX + fl) };
+        }
+        let block = Block(unsafe { *self.head(fl, sl_map.trailing_zeros() as usize) });
+        unsafe { self.remove(block) };
+        Some(block)
+    }
+
+    // mark `block` in use with `size` bytes, the rest goes back to the lists if it can be a block
+    unsafe fn take(&mut self, block: Block, size: usize) {
+        let rest = block.size() - size;
+        if rest >= Block::MIN_SIZE {
+            block.set(size, false);
+            let higher = block.higher();
+            unsafe { *higher.0.cast::<u64>() = 0 };
+            higher.set(rest, true);
+            higher.higher().set_lower_free(true);
+            unsafe { self.insert(higher) }
+        } else {
+            block.set(block.size(), false);
+            block.higher().set_lower_free(false);
+        }
+    }
+
+    // the block size for `size` bytes of user data
+    fn block_size(size: usize) -> Option<usize> {
+        Some(usize::max(size.checked_add(8 + 7)? & !7, Block::MIN_SIZE))
+    }
+
+    unsafe fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let Some(size) = Self::block_size(layout.size()) else {
+            return null_mut();
+        };
+        if layout.align() <= 8 {
+            let Some(block) = (unsafe { self.find(size) }) else {
+                return null_mut();
+            };
+            unsafe { self.take(block, size) };
+            return unsafe { block.0.add(8) };
+        }
+        // the gap before the aligned user data is split off as a free block, so it must be either
+        // empty or large enough for one
+        let Some(Some(block)) = size
+            .checked_add(layout.align() + Block::MIN_SIZE)
+            .map(|size| unsafe { self.find(size) })
+        else {
+            return null_mut();
+        };
+        let mut user_data = unsafe { block.0.add(8) };
+        user_data = unsafe { user_data.add(user_data.align_offset(layout.align())) };
+        while (1..Block::MIN_SIZE).contains(&(user_data as usize - 8 - block.0 as usize)) {
+            user_data = unsafe { user_data.add(layout.align()) }
+        }
+        let gap = user_data as usize - 8 - block.0 as usize;
+        let block = if gap == 0 {
+            block
+        } else {
+            let aligned = Block(unsafe { block.0.add(gap) });
+            let aligned_size = block.size() - gap;
+            block.set(gap, true);
+            unsafe { *aligned.0.cast::<u64>() = 0 };
+            aligned.set_lower_free(true);
+            aligned.set(aligned_size, false);
+            unsafe { self.insert(block) };
+            aligned
+        };
+        unsafe { self.take(block, size) };
+        user_data
+    }
+
+    unsafe fn dealloc(&mut self, user_data: *mut u8) {
+        let mut block = Block(unsafe { user_data.sub(8) });
+        let mut size = block.size();
+        if let Some(lower) = block.lower_free() {
+            unsafe { self.remove(lower) };
+            size += lower.size();
+            block = lower;
+        }
+        let higher = Block(unsafe { block.0.add(size) });
+        if higher.is_free() {
+            unsafe { self.remove(higher) };
+            size += higher.size();
+        }
+        block.set(size, true);
+        block.higher().set_lower_free(true);
+        unsafe { self.insert(block) }
+    }
+
+    // shrink or grow into the free higher block, false if it cannot stay in place
+    unsafe fn realloc_in_place(&mut self, user_data: *mut u8, new_size: usize) -> bool {
+        let Some(size) = Self::block_size(new_size) else {
+            return false;
+        };
+        let block = Block(unsafe { user_data.sub(8) });
+        let mut available = block.size();
+        let higher = block.higher();
+        if higher.is_free() {
+            available += higher.size()
+        }
+        if available < size {
+            return false;
+        }
+        if higher.is_free() {
+            unsafe { self.remove(higher) };
+            block.set(available, false);
+        } else if block.size() - size < Block::MIN_SIZE {
+            return true;
+        }
+        // free the rest, which is either taken from the higher block or merges with it
+        let rest = available - size;
+        if rest >= Block::MIN_SIZE {
+            block.set(size, false);
+            let higher = block.higher();
+            unsafe { *higher.0.cast::<u64>() = 0 };
+            higher.set(rest, false);
+            unsafe { self.dealloc(higher.0.add(8)) }
+        } else {
+            block.higher().set_lower_free(false)
+        }
+        true
+    }
+
+    fn walk(&self) -> impl Iterator<Item = Block> {
+        let mut block = Block(unsafe { self.space.add(Self::blocks_offset()) });
+        core::iter::from_fn(move || {
+            let item = block;
+            if item.size() == 0 {
+                return None;
+            }
+            block = item.higher();
+            Some(item)
+        })
+    }
+}
+
+pub struct Allocator<S>(Mutex<S>);
+
+impl<S> Allocator<S>
+where
+    S: Space,
+{
+    // the space must be aligned to 8 bytes and hold a few kilobytes of header
+    pub fn new(mut space: S) -> Self {
+        unsafe { Overlay::new(&mut space).init() };
+        Self(Mutex::new(space))
+    }
+
+    fn acquire_space(&self) -> MutexGuard<'_, S> {
+        loop {
+            if let Some(space) = self.0.try_lock() {
+                break space;
+            }
+        }
+    }
+
+    // walk all blocks, so this is not O(1)
+    pub fn free_bytes(&self) -> usize {
+        let mut space = self.acquire_space();
+        let overlay = Overlay::new(&mut *space);
+        overlay
+            .walk()
+            .filter(|block| block.is_free())
+            .map(|block| block.size())
+            .sum()
+    }
+
+    // the free bytes of a heap without any allocation
+    pub fn capacity(&self) -> usize {
+        (self.acquire_space().len() - Overlay::blocks_offset() - 8) & !7
+    }
+}
+
+impl<S> Owns for Allocator<S>
+where
+    S: Space,
+{
+    fn owns(&self, ptr: *const u8) -> bool {
+        self.acquire_space().as_ptr_range().contains(&ptr)
+    }
+}
+
+unsafe impl<S> GlobalAlloc for Allocator<S>
+where
+    S: Space,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut space = self.acquire_space();
+        debug_assert_eq!(space.first(), Some(&Overlay::MARKER));
+        unsafe { Overlay::new(&mut *space).alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        let mut space = self.acquire_space();
+        unsafe { Overlay::new(&mut *space).dealloc(ptr) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align()) else {
+            return null_mut();
+        };
+        let mut space = self.acquire_space();
+        let mut overlay = Overlay::new(&mut *space);
+        if unsafe { overlay.realloc_in_place(ptr, new_size) } {
+            return ptr;
+        }
+        let new_ptr = unsafe { overlay.alloc(new_layout) };
+        if !new_ptr.is_null() {
+            unsafe {
+                copy_nonoverlapping(ptr, new_ptr, usize::min(layout.size(), new_size));
+                overlay.dealloc(ptr)
+            }
+        }
+        new_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fuzz::Method, space::SystemBuffer};
+
+    use super::*;
+
+    #[test]
+    fn mapping() {
+        assert_eq!(Overlay::mapping(32), (0, 4));
+        assert_eq!(Overlay::mapping(127), (0, 15));
+        assert_eq!(Overlay::mapping(128), (1, 0));
+        assert_eq!(Overlay::mapping(255), (1, 15));
+        assert_eq!(Overlay::mapping(256), (2, 0));
+        assert_eq!(Overlay::mapping(4096 + 256), (6, 1));
+    }
+
+    #[test]
+    fn alloc_dealloc() {
+        let alloc = Allocator::new(SystemBuffer::new(16 << 10, 8));
+        let capacity = alloc.capacity();
+        assert_eq!(alloc.free_bytes(), capacity);
+
+        // larger than any gap left before an aligned block
+        let layout = Layout::from_size_align(300, 8).unwrap();
+        let aligned = Layout::from_size_align(100, 256).unwrap();
+        let ptrs = [layout, aligned, layout].map(|layout| unsafe { alloc.alloc(layout) });
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        assert_eq!(ptrs[1] as usize % 256, 0);
+        unsafe { ptrs[1].write_bytes(0xa5, 100) };
+
+        // shrinks in place, then cannot grow past the third block
+        assert_eq!(unsafe { alloc.realloc(ptrs[1], aligned, 40) }, ptrs[1]);
+        let moved = unsafe { alloc.realloc(ptrs[1], aligned, 8000) };
+        assert!(!moved.is_null() && moved != ptrs[1]);
+        assert!((0..40).all(|index| unsafe { *moved.add(index) } == 0xa5));
+        assert!(unsafe { alloc.alloc(Layout::from_size_align(capacity, 8).unwrap()) }.is_null());
+        unsafe {
+            alloc.dealloc(moved, aligned);
+            alloc.dealloc(ptrs[0], layout);
+            alloc.dealloc(ptrs[2], layout)
+        }
+        assert_eq!(alloc.free_bytes(), capacity);
+    }
+
+    #[test]
+    fn fuzz_sequences() {
+        for methods in Method::padded_sequences() {
+            let alloc = Allocator::new(SystemBuffer::new(64 << 10, 8));
+            Method::run_fuzz_with(methods.into_iter(), &alloc, |_| {});
+            assert_eq!(alloc.free_bytes(), alloc.capacity());
+        }
+    }
+}
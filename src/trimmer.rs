@@ -0,0 +1,122 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::registry::Registry;
+
+// a background thread trimming the heaps of a registry, one heap per tick in turn, so each heap
+// is trimmed at most once every `interval` times the number of registered heaps, and never two
+// heaps back to back. the thread is stopped and joined on drop
+pub struct Trimmer {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Trimmer {
+    pub fn spawn<const N: usize>(
+        registry: &'static Registry<'static, N>,
+        interval: Duration,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = thread::Builder::new()
+            .name("simpile-trimmer".into())
+            .spawn({
+                let stop = stop.clone();
+                move || {
+                    // best effort, linux is the one where the nice value is per thread
+                    #[cfg(target_os = "linux")]
+                    unsafe {
+                        nix::libc::setpriority(nix::libc::PRIO_PROCESS, 0, 19);
+                    }
+                    let mut index = 0;
+                    loop {
+                        thread::park_timeout(interval);
+                        if stop.load(SeqCst) {
+                            break;
+                        }
+                        let mut heaps = 0;
+                        registry.for_each(|heap| {
+                            if heaps == index {
+                                heap.trim();
+                            }
+                            heaps += 1
+                        });
+                        index = if heaps == 0 { 0 } else { (index + 1) % heaps };
+                    }
+                }
+            })
+            .unwrap();
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Trimmer {
+    fn drop(&mut self) {
+        self.stop.store(true, SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            handle.join().unwrap()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{boxed::Box, sync::atomic::AtomicUsize};
+
+    use crate::{
+        linked::{ChunkInfo, Stats},
+        registry::Heap,
+    };
+
+    use super::*;
+
+    struct Counting(AtomicUsize);
+
+    impl Heap for Counting {
+        fn name(&self) -> Option<&str> {
+            None
+        }
+
+        fn stats(&self) -> Stats {
+            Stats::default()
+        }
+
+        fn sanity_check(&self) {}
+
+        fn for_each_chunk(&self, _: &mut dyn FnMut(ChunkInfo)) {}
+
+        fn trim(&self) -> usize {
+            self.0.fetch_add(1, SeqCst);
+            0
+        }
+    }
+
+    #[test]
+    fn trim_in_turn() {
+        let heaps: &'static [Counting; 2] = Box::leak(Box::new(
+            [0, 0].map(|count| Counting(AtomicUsize::new(count))),
+        ));
+        let registry: &'static Registry<'static, 4> = Box::leak(Box::new(Registry::new()));
+        for heap in heaps {
+            assert!(registry.register(heap));
+        }
+
+        let trimmer = Trimmer::spawn(registry, Duration::from_millis(1));
+        thread::sleep(Duration::from_millis(50));
+        drop(trimmer);
+        let counts = heaps.each_ref().map(|heap| heap.0.load(SeqCst));
+        assert!(counts[0] > 0 && counts[1] > 0);
+        assert!(counts[0].abs_diff(counts[1]) <= 1);
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(heaps.each_ref().map(|heap| heap.0.load(SeqCst)), counts);
+    }
+}
@@ -0,0 +1,30 @@
+use std::{fs, path::Path};
+
+use simpile::{fuzz::Method, linked::Allocator, space::Fixed};
+
+#[cfg(not(all(feature = "std", feature = "paranoid")))]
+compile_error!("features \"std\" and \"paranoid\" are required to compile");
+
+// same as the fuzz target, so a failure replays with the same alignment
+#[repr(align(4096))]
+struct Page([u8; 4096]);
+
+// every file under `tests/corpus` is the input of a failure found by the fuzzer, in the profiled
+// format of `Method::from_bytes_profiled`, so a new find is added by copying it into the directory
+#[test]
+fn replay_corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut paths = Vec::from_iter(
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path()),
+    );
+    paths.sort();
+    assert!(!paths.is_empty());
+    for path in paths {
+        eprintln!("replaying {}", path.display());
+        let methods = Method::from_bytes_profiled(&fs::read(&path).unwrap());
+        let mut page = Box::new(Page([0; 4096]));
+        Method::run_fuzz_checked(&methods, Allocator::new(Fixed::from(&mut page.0[..])));
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    fmt::Write,
+};
+
+use simpile::{dump::Buffer, linked::Allocator, registry::Registry, space::Fixed};
+
+// the allocations of each thread, as the test harness allocates on other threads meanwhile
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let count = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - count
+}
+
+// so the heap can be dumped from inside a global allocator, where allocating recurses into it
+#[test]
+fn dump_without_allocating() {
+    let data = &mut *vec![0; 4 << 10];
+    let alloc = Allocator::new_named(Fixed::from(data), "dumped");
+    let layout = Layout::from_size_align(100, 8).unwrap();
+    let ptrs = [(); 4].map(|_| unsafe { alloc.alloc(layout) });
+    unsafe { alloc.dealloc(ptrs[1], layout) }
+    let registry = Registry::<2>::new();
+    assert!(registry.register(&alloc));
+
+    let mut buf = Buffer::<4096>::new();
+    let count = allocations(|| {
+        alloc.dump(&mut buf).unwrap();
+        write!(buf, "{:?} {:?}", alloc.stats(), alloc.check()).unwrap();
+        write!(buf, "{:?}", alloc.footprint()).unwrap();
+        registry.dump(&mut buf).unwrap();
+    });
+    assert_eq!(count, 0);
+    assert!(buf.as_str().starts_with("heap dumped\n"));
+    assert!(buf.as_str().contains("in_use"));
+    assert!(!buf.is_truncated());
+
+    // cut short instead of growing
+    let mut buf = Buffer::<16>::new();
+    assert_eq!(allocations(|| assert!(alloc.dump(&mut buf).is_err())), 0);
+    assert!(buf.is_truncated());
+}
@@ -0,0 +1,49 @@
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    slice,
+};
+
+use simpile::{
+    linked::{Allocator, Config, TopPolicy},
+    space::Mmap,
+    Space,
+};
+
+// the library is built without `cfg(test)` here, so a clean free chunk is not poisoned, i.e. filled
+// with zero, which would hide bytes that are not zero
+#[test]
+fn alloc_zeroed_regrown() {
+    let mut space = Mmap::new();
+    assert!(space.set_size(8 << 20));
+    assert!(space.set_size(64 << 10));
+    let config = Config {
+        top_policy: TopPolicy::KeepWilderness,
+        fast_bins: false,
+        ..Default::default()
+    };
+    let alloc = Allocator::with_config(space, config);
+    let small = Layout::from_size_align(32, 8).unwrap();
+    let large = Layout::from_size_align(2 << 20, 8).unwrap();
+    let small_ptr = unsafe { alloc.alloc(small) };
+    let ptr = unsafe { alloc.alloc(large) };
+    unsafe { ptr.write_bytes(0xff, large.size()) }
+    unsafe { alloc.dealloc(ptr, large) }
+    // shrunk, not kept free
+    assert!(alloc.stats().free_bytes < large.size());
+    // the free chunk left below the top one, so growing again merges the grown bytes only
+    let info = alloc.find_chunk(small_ptr).unwrap();
+    let rest = alloc.find_chunk(info.addr.wrapping_add(info.size)).unwrap();
+    assert!(!rest.in_use);
+    let rest = Layout::from_size_align(rest.size - (info.size - small.size()), 8).unwrap();
+    let rest_ptr = unsafe { alloc.alloc(rest) };
+
+    // a shared mapping keeps the pages of a shrinking, and growing again brings them back
+    let ptr = unsafe { alloc.alloc_zeroed(large) };
+    assert!(unsafe { slice::from_raw_parts(ptr, large.size()) }
+        .iter()
+        .all(|byte| *byte == 0));
+    for (ptr, layout) in [(ptr, large), (rest_ptr, rest), (small_ptr, small)] {
+        unsafe { alloc.dealloc(ptr, layout) }
+    }
+    assert_eq!(alloc.check(), Ok(()));
+}
@@ -0,0 +1,65 @@
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    env, fs,
+    panic::{catch_unwind, AssertUnwindSafe},
+    slice,
+};
+
+use simpile::{
+    linked::{Allocator, CheckError},
+    space::Fixed,
+};
+
+// the library is built without `cfg(test)` here, so the checks reading the heap only follow the
+// `check-*` features, which `paranoid` turns on, no matter whether debug assertions are on
+#[test]
+fn checks_follow_features() {
+    let len = 4 << 10;
+    let data = Box::leak(vec![0u8; len].into_boxed_slice()).as_mut_ptr();
+    let alloc = Allocator::new(Fixed::from(unsafe { slice::from_raw_parts_mut(data, len) }));
+    // the initialization marker, which is checked before every allocation
+    unsafe { *data = 0 };
+    assert_eq!(alloc.check(), Err(CheckError::Marker));
+
+    let layout = Layout::from_size_align(16, 1).unwrap();
+    let result = catch_unwind(AssertUnwindSafe(|| unsafe { alloc.alloc(layout) }));
+    assert_eq!(
+        result.is_err(),
+        cfg!(all(
+            any(feature = "check-chunks", feature = "check-bins"),
+            debug_assertions
+        ))
+    );
+}
+
+// the messages of the checks are in the binary if and only if the checks are compiled in. they are
+// written backwards, so the literals of this test do not match them
+#[test]
+fn checks_compiled_out() {
+    let len = 4 << 10;
+    let data = Box::leak(vec![0u8; len].into_boxed_slice()).as_mut_ptr();
+    let alloc = Allocator::new(Fixed::from(unsafe { slice::from_raw_parts_mut(data, len) }));
+    // reach the checks, or they are left out of the binary anyway
+    let layout = Layout::from_size_align(16, 1).unwrap();
+    let ptrs = [(); 3].map(|_| unsafe { alloc.alloc(layout) });
+    unsafe { alloc.dealloc(ptrs[1], layout) }
+    unsafe { alloc.alloc(layout) };
+
+    let binary = fs::read(env::current_exe().unwrap()).unwrap();
+    for (backwards, compiled) in [
+        (
+            "ezis_wen => )(ezis_teg.fles :deliaf noitressa",
+            cfg!(feature = "check-chunks"),
+        ),
+        ("deerf retfa nettirw ", cfg!(feature = "check-poison")),
+    ] {
+        let message = String::from_iter(backwards.chars().rev());
+        assert_eq!(
+            binary
+                .windows(message.len())
+                .any(|window| window == message.as_bytes()),
+            compiled && cfg!(debug_assertions),
+            "{message}"
+        );
+    }
+}
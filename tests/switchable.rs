@@ -3,7 +3,7 @@ use std::{
     sync::OnceLock,
 };
 
-use simpile::{linked::Allocator, space::Mmap, Space, Switchable};
+use simpile::{fuzz::Method, linked::Allocator, space::Mmap, Space, Switchable};
 
 #[cfg(not(feature = "switchable"))]
 compile_error!("feature \"switchable\" is required to compile");
@@ -49,3 +49,18 @@ fn run() {
     println!("Off: {:?}", &Box::new(42) as *const _);
     drop(on_box)
 }
+
+// same as the switchable fuzz target, with the heap toggled in a few fixed patterns
+#[test]
+fn toggled_sequences() {
+    for pattern in [0b01, 0b10, 0b0011, 0b0110] {
+        for methods in Method::padded_sequences() {
+            let mut space = Mmap::new();
+            space.set_size(4 << 10);
+            let alloc = Switchable::new(Allocator::new(space));
+            Method::run_fuzz_with(methods.into_iter(), &alloc, |index| {
+                alloc.set_enable((pattern >> (index % 4)) & 1 == 0)
+            });
+        }
+    }
+}